@@ -54,7 +54,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             );
                             display_addresses(
                                 &bitcoin_addresses,
-                                &AddressType::P2SH,
+                                &AddressType::P2SHWPKH,
                                 "SegWit-wrapped (P2SH)",
                             );
                             display_addresses(
@@ -128,10 +128,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("\n🔧 Advanced Configuration Example:");
 
     // Demonstrate custom configuration with different networks
-    let mut config = UbaConfig::default();
-    config.network = Network::Testnet;
-    config.max_addresses_per_type = 3;
-    config.relay_timeout = 5;
+    let config = UbaConfig {
+        network: Network::Testnet,
+        max_addresses_per_type: 3,
+        relay_timeout: 5,
+        ..Default::default()
+    };
 
     println!("   Network: {:?}", config.network);
     println!(
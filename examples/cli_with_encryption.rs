@@ -2,12 +2,15 @@
 //!
 //! Usage:
 //!   cargo run --example cli_with_encryption -- generate --seed "your seed" --passphrase "secret"
+//!   cargo run --example cli_with_encryption -- generate --types p2wpkh,p2tr,lightning
 //!   cargo run --example cli_with_encryption -- retrieve --uba "UBA:..." --passphrase "secret"
+//!   cargo run --example cli_with_encryption -- offline --network testnet --count 3
 
 use std::env;
+use std::str::FromStr;
 use uba::{
     default_public_relays, derive_encryption_key, generate_with_config, retrieve_with_config,
-    UbaConfig,
+    AddressGenerator, AddressType, Network, UbaConfig, UbaEncryption,
 };
 
 #[tokio::main]
@@ -26,8 +29,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             });
             let passphrase = get_arg(&args, "--passphrase");
             let label = get_arg(&args, "--label");
+            let types = match get_arg(&args, "--types") {
+                Some(list) => match parse_types(&list) {
+                    Ok(types) => Some(types),
+                    Err(e) => {
+                        eprintln!("❌ {}", e);
+                        eprintln!("Valid address types: {}", valid_type_names().join(", "));
+                        std::process::exit(1);
+                    }
+                },
+                None => None,
+            };
 
-            generate_uba(&seed, passphrase.as_deref(), label.as_deref()).await?;
+            generate_uba(&seed, passphrase.as_deref(), label.as_deref(), types).await?;
         }
         "retrieve" => {
             let uba = get_arg(&args, "--uba").expect("--uba is required for retrieve command");
@@ -35,6 +49,56 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             retrieve_uba(&uba, passphrase.as_deref()).await?;
         }
+        "offline" => {
+            let seed = get_arg(&args, "--seed").unwrap_or_else(|| {
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about".to_string()
+            });
+            let label = get_arg(&args, "--label");
+            let passphrase = get_arg(&args, "--passphrase");
+            let output = get_arg(&args, "--output");
+
+            let network = match get_arg(&args, "--network") {
+                Some(name) => Network::from_str(&name).unwrap_or_else(|_| {
+                    eprintln!("❌ Invalid network: {}", name);
+                    eprintln!("Valid networks: bitcoin, testnet, signet, regtest");
+                    std::process::exit(1);
+                }),
+                None => Network::Bitcoin,
+            };
+            let count = match get_arg(&args, "--count") {
+                Some(count) => count.parse::<usize>().unwrap_or_else(|_| {
+                    eprintln!("❌ Invalid count: {}", count);
+                    std::process::exit(1);
+                }),
+                None => 5,
+            };
+            let types = match get_arg(&args, "--types") {
+                Some(list) => match parse_types(&list) {
+                    Ok(types) => Some(types),
+                    Err(e) => {
+                        eprintln!("❌ {}", e);
+                        eprintln!("Valid address types: {}", valid_type_names().join(", "));
+                        std::process::exit(1);
+                    }
+                },
+                None => None,
+            };
+
+            if passphrase.is_some() && output.is_none() {
+                eprintln!("❌ --passphrase requires --output for offline generation");
+                std::process::exit(1);
+            }
+
+            generate_offline(
+                &seed,
+                label,
+                network,
+                count,
+                types,
+                passphrase.as_deref(),
+                output.as_deref(),
+            )?;
+        }
         "relays" => {
             list_default_relays();
         }
@@ -50,12 +114,21 @@ async fn generate_uba(
     seed: &str,
     passphrase: Option<&str>,
     label: Option<&str>,
+    types: Option<Vec<AddressType>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     println!("🔧 Generating UBA...");
 
     let mut config = UbaConfig::default();
     config.set_all_counts(5); // Generate 5 addresses per type
 
+    // Restrict generation to the selected types, if any
+    if let Some(types) = types {
+        config.disable_all_address_types();
+        for address_type in types {
+            config.set_address_type_enabled(address_type, true);
+        }
+    }
+
     // Set up encryption if passphrase provided
     if let Some(passphrase) = passphrase {
         let encryption_key = derive_encryption_key(passphrase, None);
@@ -66,7 +139,7 @@ async fn generate_uba(
     }
 
     // Generate UBA
-    let uba = generate_with_config(seed, label, &[], config).await?;
+    let uba = generate_with_config(seed, label, &[], config.clone()).await?;
 
     println!("\n✅ Generated UBA:");
     println!("{}", uba);
@@ -75,13 +148,65 @@ async fn generate_uba(
         println!("\n🔑 Remember your passphrase to retrieve the addresses!");
     }
 
-    println!("\n📊 This UBA contains 30 addresses across all Bitcoin layers:");
-    println!("   • 5 P2PKH (Legacy) addresses");
-    println!("   • 5 P2SH (SegWit v0) addresses");
-    println!("   • 5 P2WPKH (Native SegWit) addresses");
-    println!("   • 5 P2TR (Taproot) addresses");
-    println!("   • 5 Liquid addresses");
-    println!("   • 5 Lightning node public keys");
+    let enabled_types = config.get_enabled_address_types();
+    println!("\n📊 This UBA contains addresses for {} types:", enabled_types.len());
+    for address_type in &enabled_types {
+        println!(
+            "   • {} {}",
+            config.get_address_count(address_type),
+            address_type.description()
+        );
+    }
+
+    Ok(())
+}
+
+/// Generate addresses locally and print them as JSON, without contacting any relay
+fn generate_offline(
+    seed: &str,
+    label: Option<String>,
+    network: Network,
+    count: usize,
+    types: Option<Vec<AddressType>>,
+    passphrase: Option<&str>,
+    output: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = UbaConfig {
+        network,
+        ..Default::default()
+    };
+    config.set_all_counts(count);
+
+    if let Some(types) = types {
+        config.disable_all_address_types();
+        for address_type in types {
+            config.set_address_type_enabled(address_type, true);
+        }
+    }
+
+    let addresses = AddressGenerator::new(config).generate_addresses(seed, label)?;
+    let json = serde_json::to_string_pretty(&addresses)?;
+
+    match output {
+        Some(path) => {
+            // Encrypt exactly as the published event content would be
+            let contents = match passphrase {
+                Some(passphrase) => {
+                    let encryption_key = derive_encryption_key(passphrase, None);
+                    UbaEncryption::new(encryption_key).encrypt(&json)?
+                }
+                None => json,
+            };
+            std::fs::write(path, contents)?;
+            eprintln!(
+                "✅ Wrote {} addresses to {}{}",
+                addresses.len(),
+                path,
+                if passphrase.is_some() { " (encrypted)" } else { "" }
+            );
+        }
+        None => println!("{}", json),
+    }
 
     Ok(())
 }
@@ -153,6 +278,32 @@ fn list_default_relays() {
     println!("\nThese relays are automatically used when no custom relays are specified.");
 }
 
+/// Parse a comma-separated list of address type names such as "p2wpkh,p2tr"
+fn parse_types(list: &str) -> Result<Vec<AddressType>, String> {
+    let mut types = Vec::new();
+    for name in list.split(',').filter(|name| !name.trim().is_empty()) {
+        let address_type: AddressType = name
+            .parse()
+            .map_err(|_| format!("Invalid address type: {}", name.trim()))?;
+        if !types.contains(&address_type) {
+            types.push(address_type);
+        }
+    }
+
+    if types.is_empty() {
+        return Err("--types requires at least one address type".to_string());
+    }
+
+    Ok(types)
+}
+
+fn valid_type_names() -> Vec<&'static str> {
+    AddressType::ALL
+        .iter()
+        .map(|address_type| address_type.as_kebab_str())
+        .collect()
+}
+
 fn get_arg(args: &[String], flag: &str) -> Option<String> {
     args.iter()
         .position(|arg| arg == flag)
@@ -169,17 +320,29 @@ fn print_usage() {
     println!("COMMANDS:");
     println!("   generate    Generate a new UBA");
     println!("   retrieve    Retrieve addresses from a UBA");
+    println!("   offline     Generate addresses locally and print them as JSON");
     println!("   relays      List default public relays");
     println!();
     println!("GENERATE OPTIONS:");
     println!("   --seed <SEED>           BIP39 mnemonic seed (default: test seed)");
     println!("   --passphrase <PASS>     Encryption passphrase (optional)");
     println!("   --label <LABEL>         Optional label for the UBA");
+    println!("   --types <TYPES>         Comma-separated address types (default: all)");
+    println!("                           e.g. p2wpkh,p2tr,lightning");
     println!();
     println!("RETRIEVE OPTIONS:");
     println!("   --uba <UBA_STRING>      The UBA string to retrieve");
     println!("   --passphrase <PASS>     Decryption passphrase (if encrypted)");
     println!();
+    println!("OFFLINE OPTIONS:");
+    println!("   --seed <SEED>           BIP39 mnemonic seed (default: test seed)");
+    println!("   --label <LABEL>         Optional label for the collection");
+    println!("   --network <NETWORK>     bitcoin, testnet, signet or regtest (default: bitcoin)");
+    println!("   --count <N>             Addresses per type (default: 5)");
+    println!("   --types <TYPES>         Comma-separated address types (default: all)");
+    println!("   --output <FILE>         Write the JSON to a file instead of stdout");
+    println!("   --passphrase <PASS>     Encrypt the file contents (requires --output)");
+    println!();
     println!("EXAMPLES:");
     println!("   # Generate encrypted UBA");
     println!("   cargo run --example cli_with_encryption -- generate --passphrase \"my-secret\"");
@@ -187,6 +350,12 @@ fn print_usage() {
     println!("   # Generate with custom seed and label");
     println!("   cargo run --example cli_with_encryption -- generate --seed \"your seed words\" --label \"my-wallet\"");
     println!();
+    println!("   # Generate only SegWit, Taproot and Lightning entries");
+    println!("   cargo run --example cli_with_encryption -- generate --types p2wpkh,p2tr,lightning");
+    println!();
+    println!("   # Inspect what would be published, without any relay");
+    println!("   cargo run --example cli_with_encryption -- offline --network testnet --count 3 --types p2wpkh,p2tr");
+    println!();
     println!("   # Retrieve encrypted UBA");
     println!("   cargo run --example cli_with_encryption -- retrieve --uba \"UBA:abc123...\" --passphrase \"my-secret\"");
 }
@@ -25,30 +25,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     // Count addresses by type
-    let btc_l1_count = addresses1
-        .get_addresses(&uba::AddressType::P2PKH)
-        .map(|v| v.len())
-        .unwrap_or(0)
-        + addresses1
-            .get_addresses(&uba::AddressType::P2SH)
-            .map(|v| v.len())
-            .unwrap_or(0)
-        + addresses1
-            .get_addresses(&uba::AddressType::P2WPKH)
-            .map(|v| v.len())
-            .unwrap_or(0)
-        + addresses1
-            .get_addresses(&uba::AddressType::P2TR)
-            .map(|v| v.len())
-            .unwrap_or(0);
-    let liquid_count = addresses1
-        .get_addresses(&uba::AddressType::Liquid)
-        .map(|v| v.len())
-        .unwrap_or(0);
-    let lightning_count = addresses1
-        .get_addresses(&uba::AddressType::Lightning)
-        .map(|v| v.len())
-        .unwrap_or(0);
+    let counts = addresses1.counts_by_type();
+    let count = |address_type: uba::AddressType| counts.get(&address_type).copied().unwrap_or(0);
+    let btc_l1_count: usize = uba::AddressType::BITCOIN_L1.into_iter().map(count).sum();
+    let liquid_count = count(uba::AddressType::Liquid);
+    let lightning_count = count(uba::AddressType::Lightning);
 
     println!("Bitcoin L1: {}", btc_l1_count);
     println!("Liquid: {}", liquid_count);
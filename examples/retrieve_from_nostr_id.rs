@@ -5,7 +5,7 @@
 //! - Parse and display the retrieved information
 //! - Handle different relay scenarios
 
-use uba::{AddressType, NostrClient};
+use uba::AddressType;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -97,7 +97,7 @@ fn display_address_info(addresses: &uba::BitcoinAddresses) {
     // Display addresses by type
     println!("\n🟠 BITCOIN L1 ADDRESSES:");
     display_addresses_by_type(addresses, &AddressType::P2PKH, "Legacy (P2PKH)");
-    display_addresses_by_type(addresses, &AddressType::P2SH, "SegWit-wrapped (P2SH)");
+    display_addresses_by_type(addresses, &AddressType::P2SHWPKH, "SegWit-wrapped (P2SH)");
     display_addresses_by_type(addresses, &AddressType::P2WPKH, "Native SegWit (P2WPKH)");
     display_addresses_by_type(addresses, &AddressType::P2TR, "Taproot (P2TR)");
 
@@ -110,7 +110,7 @@ fn display_address_summary(addresses: &uba::BitcoinAddresses) {
 
     let types = [
         (AddressType::P2PKH, "Bitcoin Legacy (P2PKH)"),
-        (AddressType::P2SH, "Bitcoin SegWit-wrapped (P2SH)"),
+        (AddressType::P2SHWPKH, "Bitcoin SegWit-wrapped (P2SH)"),
         (AddressType::P2WPKH, "Bitcoin Native SegWit (P2WPKH)"),
         (AddressType::P2TR, "Bitcoin Taproot (P2TR)"),
         (AddressType::Liquid, "Liquid Sidechain"),
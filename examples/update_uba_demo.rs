@@ -114,11 +114,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 fn display_addresses(addresses: &BitcoinAddresses) {
     println!("   📊 Address Summary:");
-    println!("   ├─ Total address types: {}", addresses.addresses.len());
+    println!("   ├─ Total address types: {}", addresses.iter().len());
     println!("   ├─ Total addresses: {}", addresses.len());
     println!("   └─ Created at: {}", addresses.created_at);
     
-    for (addr_type, addr_list) in &addresses.addresses {
+    for (addr_type, addr_list) in addresses {
         println!("   ");
         println!("   🏷️  {:?} ({} addresses):", addr_type, addr_list.len());
         for (i, addr) in addr_list.iter().enumerate() {
@@ -136,22 +136,22 @@ fn verify_filtering(addresses: &BitcoinAddresses) {
     println!("\n   🔍 Verifying address filtering:");
     
     // Check that Lightning and Liquid are not present
-    if !addresses.addresses.contains_key(&AddressType::Lightning) {
+    if addresses.get_addresses(&AddressType::Lightning).is_none() {
         println!("   ✅ Lightning addresses correctly filtered out");
     } else {
         println!("   ❌ Lightning addresses should have been filtered out");
     }
     
-    if !addresses.addresses.contains_key(&AddressType::Liquid) {
+    if addresses.get_addresses(&AddressType::Liquid).is_none() {
         println!("   ✅ Liquid addresses correctly filtered out");
     } else {
         println!("   ❌ Liquid addresses should have been filtered out");
     }
     
     // Check that Bitcoin L1 addresses are present with correct counts
-    let bitcoin_l1_types = [AddressType::P2PKH, AddressType::P2SH, AddressType::P2WPKH, AddressType::P2TR];
+    let bitcoin_l1_types = [AddressType::P2PKH, AddressType::P2SHWPKH, AddressType::P2WPKH, AddressType::P2TR];
     for addr_type in &bitcoin_l1_types {
-        if let Some(addr_list) = addresses.addresses.get(addr_type) {
+        if let Some(addr_list) = addresses.get_addresses(addr_type) {
             if addr_list.len() == 3 {
                 println!("   ✅ {:?}: {} addresses (correct)", addr_type, addr_list.len());
             } else {
@@ -178,6 +178,7 @@ async fn demonstrate_custom_address_update(original_event_id: &str, relays: &[St
         description: Some("Custom address update demo".to_string()),
         xpub: None,
         derivation_paths: None,
+        fingerprint: None,
     });
     
     let config = UbaConfig::default();
@@ -250,8 +251,8 @@ fn demonstrate_offline_functionality() {
     match address_generator.generate_addresses(seed, Some("offline-test".to_string())) {
         Ok(addresses) => {
             println!("   ✅ Offline address generation successful!");
-            println!("   📊 Generated {} address types", addresses.addresses.len());
-            for (addr_type, addr_list) in &addresses.addresses {
+            println!("   📊 Generated {} address types", addresses.iter().len());
+            for (addr_type, addr_list) in &addresses {
                 println!("      - {:?}: {} addresses", addr_type, addr_list.len());
             }
         }
@@ -1,29 +1,37 @@
 //! Bitcoin address generation from seeds
 
-use crate::error::{Result, UbaError};
-use crate::types::{AddressMetadata, AddressType, BitcoinAddresses, UbaConfig};
+use crate::error::{validation, Result, UbaError};
+use crate::types::{AddressCursor, AddressMetadata, AddressType, BitcoinAddresses, UbaConfig};
 
-use bip39::Mnemonic;
+use bip39::{Language, Mnemonic};
+use bitcoin::bech32::{self, primitives::decode::CheckedHrpstring, Bech32, Hrp};
 use bitcoin::{
-    bip32::{ChildNumber, DerivationPath, Xpriv},
+    bip32::{ChildNumber, DerivationPath, Xpriv, Xpub},
     secp256k1::Secp256k1,
+    taproot::TapNodeHash,
     Address, PrivateKey, PublicKey, XOnlyPublicKey,
 };
+use std::collections::HashMap;
+use std::ops::Range;
 use std::str::FromStr;
 
 // Liquid support
 use elements::Address as LiquidAddress;
 
 // Lightning support
+use lightning::offers::offer::OfferBuilder;
 use secp256k1::PublicKey as Secp256k1PublicKey;
 
 // Nostr support
+#[cfg(feature = "nostr")]
 use nostr::{self, ToBech32};
 
 /// Address generator for creating Bitcoin addresses from seeds
 pub struct AddressGenerator {
     config: UbaConfig,
     secp: Secp256k1<bitcoin::secp256k1::All>,
+    /// First derivation index per type; types not listed start at 0
+    start_indices: HashMap<AddressType, u32>,
 }
 
 impl AddressGenerator {
@@ -32,13 +40,104 @@ impl AddressGenerator {
         Self {
             config,
             secp: Secp256k1::new(),
+            start_indices: HashMap::new(),
         }
     }
 
+    /// Generate the next page of addresses and advance the cursor past it
+    ///
+    /// Each enabled address type yields `page_size` addresses starting at the
+    /// cursor's index for that type. The addresses are identical to those at the
+    /// same indices from a single `generate_addresses` call, so a wallet can page
+    /// through a large range and persist the cursor between sessions.
+    ///
+    /// # Example
+    /// ```rust
+    /// use uba::{AddressCursor, AddressGenerator, UbaConfig};
+    ///
+    /// let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+    /// let generator = AddressGenerator::new(UbaConfig::default());
+    /// let mut cursor = AddressCursor::new();
+    ///
+    /// let first_page = generator.generate_next_page(&mut cursor, seed, 10)?;
+    /// let saved = serde_json::to_string(&cursor)?;
+    ///
+    /// let mut cursor: AddressCursor = serde_json::from_str(&saved)?;
+    /// let second_page = generator.generate_next_page(&mut cursor, seed, 10)?;
+    /// # Ok::<(), uba::UbaError>(())
+    /// ```
+    pub fn generate_next_page(
+        &self,
+        cursor: &mut AddressCursor,
+        seed_input: &str,
+        page_size: usize,
+    ) -> Result<BitcoinAddresses> {
+        let enabled_types = self.config.get_enabled_address_types();
+
+        let mut config = self.config.clone();
+        let mut start_indices = HashMap::new();
+        for address_type in &enabled_types {
+            config.set_address_count(address_type.clone(), page_size);
+            start_indices.insert(address_type.clone(), cursor.next_index(address_type));
+        }
+
+        let page = AddressGenerator {
+            config,
+            secp: Secp256k1::new(),
+            start_indices,
+        }
+        .generate_addresses(seed_input, None)?;
+
+        for address_type in enabled_types {
+            cursor.advance(address_type, page_size);
+        }
+        Ok(page)
+    }
+
+    /// Derive the single address of one type at `index`
+    ///
+    /// Only that address is derived, and it is identical to the one at the same
+    /// index from `generate_addresses`, which makes this the building block for
+    /// "next unused address" flows. The type does not need to be enabled in the
+    /// config.
+    ///
+    /// # Example
+    /// ```rust
+    /// use uba::{AddressGenerator, AddressType, UbaConfig};
+    ///
+    /// let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+    /// let generator = AddressGenerator::new(UbaConfig::default());
+    /// let address = generator.address_at(seed, AddressType::P2WPKH, 7)?;
+    /// # Ok::<(), uba::UbaError>(())
+    /// ```
+    pub fn address_at(
+        &self,
+        seed_input: &str,
+        address_type: AddressType,
+        index: u32,
+    ) -> Result<String> {
+        let mut config = self.config.clone();
+        config.disable_all_address_types();
+        config.set_address_type_enabled(address_type.clone(), true);
+        config.set_address_count(address_type.clone(), 1);
+
+        let addresses = AddressGenerator {
+            config,
+            secp: Secp256k1::new(),
+            start_indices: HashMap::from([(address_type.clone(), index)]),
+        }
+        .generate_addresses(seed_input, None)?;
+
+        addresses.first(&address_type).map(str::to_string).ok_or_else(|| {
+            UbaError::AddressGeneration(format!("No {:?} address at index {}", address_type, index))
+        })
+    }
+
     /// Generate Bitcoin addresses from a seed phrase or private key
     ///
     /// # Arguments
-    /// * `seed_input` - BIP39 mnemonic phrase or hex-encoded private key
+    /// * `seed_input` - BIP39 mnemonic phrase or hex-encoded private key, which may
+    ///   be prefixed with its network (`mainnet:<hex>`); see `UbaConfig::strict_network`
     /// * `label` - Optional label for the address collection
     ///
     /// # Returns
@@ -48,8 +147,15 @@ impl AddressGenerator {
         seed_input: &str,
         label: Option<String>,
     ) -> Result<BitcoinAddresses> {
+        // Reject absurd counts before doing any derivation work
+        self.config.validate_address_counts()?;
+        self.config.validate_derivation_paths()?;
+
         let master_key = self.derive_master_key(seed_input)?;
         let mut addresses = BitcoinAddresses::new();
+        if let Some(timestamp) = self.config.fixed_timestamp {
+            addresses.created_at = timestamp;
+        }
 
         // Set metadata
         addresses.metadata = Some(AddressMetadata {
@@ -57,11 +163,15 @@ impl AddressGenerator {
             description: Some("UBA generated address collection".to_string()),
             xpub: None, // We don't expose the xpub for privacy
             derivation_paths: Some(self.get_derivation_paths()),
+            fingerprint: self
+                .config
+                .include_fingerprint
+                .then(|| master_key.fingerprint(&self.secp).to_string()),
         });
 
         // Generate addresses for each supported type, but only if enabled
         if self.config.is_address_type_enabled(&AddressType::P2PKH) 
-            || self.config.is_address_type_enabled(&AddressType::P2SH) 
+            || self.config.is_address_type_enabled(&AddressType::P2SHWPKH) 
             || self.config.is_address_type_enabled(&AddressType::P2WPKH) {
             self.generate_legacy_addresses(&master_key, &mut addresses)?;
             self.generate_segwit_addresses(&master_key, &mut addresses)?;
@@ -69,6 +179,11 @@ impl AddressGenerator {
 
         if self.config.is_address_type_enabled(&AddressType::P2TR) {
             self.generate_taproot_addresses(&master_key, &mut addresses)?;
+            if let Some(taproot_addresses) = addresses.get_addresses(&AddressType::P2TR) {
+                for address in taproot_addresses {
+                    validate_taproot_address(address, self.config.network)?;
+                }
+            }
         }
 
         // Generate L2 addresses only if enabled
@@ -80,24 +195,195 @@ impl AddressGenerator {
             self.generate_lightning_addresses(&master_key, &mut addresses)?;
         }
 
+        if self.config.is_address_type_enabled(&AddressType::LightningOffer) {
+            self.generate_lightning_offers(&master_key, &mut addresses)?;
+        }
+
         // Generate Nostr public key only if enabled
         if self.config.is_address_type_enabled(&AddressType::Nostr) {
             self.generate_nostr_addresses(&master_key, &mut addresses)?;
         }
 
+        if self.config.sign_payload {
+            addresses.sign_with_seed(seed_input, &self.config)?;
+        }
+
         Ok(addresses)
     }
 
+    /// Generate script-path Taproot addresses committing to a script tree
+    ///
+    /// Each P2TR output key is the derived internal key tweaked with `merkle_root`
+    /// as specified in BIP341, so the outputs can be spent either with the tweaked
+    /// key or through any script in the tree. Only P2TR addresses are generated,
+    /// with the configured count and derivation path; the key-path-only addresses
+    /// from `generate_addresses` are different.
+    pub fn generate_taproot_with_merkle_root(
+        &self,
+        seed_input: &str,
+        merkle_root: TapNodeHash,
+    ) -> Result<BitcoinAddresses> {
+        let mut config = self.config.clone();
+        config.disable_all_address_types();
+        config.set_address_type_enabled(AddressType::P2TR, true);
+        config.taproot_merkle_root = Some(merkle_root);
+
+        AddressGenerator {
+            config,
+            secp: Secp256k1::new(),
+            start_indices: self.start_indices.clone(),
+        }
+        .generate_addresses(seed_input, None)
+    }
+
+    /// Generate Bitcoin addresses from raw BIP39 entropy
+    ///
+    /// The entropy is encoded as the English BIP39 mnemonic it represents
+    /// (`Mnemonic::from_entropy`), and that mnemonic is then used exactly as in
+    /// `generate_addresses`: its seed is derived with the configured BIP39
+    /// passphrase (empty by default). The same entropy therefore yields the same
+    /// addresses as its 12/15/18/21/24-word mnemonic, and can be recovered in any
+    /// BIP39 wallet from that mnemonic.
+    ///
+    /// # Arguments
+    /// * `entropy` - 16, 20, 24, 28 or 32 bytes of entropy
+    /// * `label` - Optional label for the address collection
+    ///
+    /// Returns `UbaError::InvalidSeed` for any other entropy length.
+    pub fn generate_from_entropy(
+        &self,
+        entropy: &[u8],
+        label: Option<String>,
+    ) -> Result<BitcoinAddresses> {
+        if !matches!(entropy.len(), 16 | 20 | 24 | 28 | 32) {
+            return Err(UbaError::InvalidSeed(format!(
+                "Entropy must be 16, 20, 24, 28 or 32 bytes, got {}",
+                entropy.len()
+            )));
+        }
+
+        let language = self.config.mnemonic_language.unwrap_or(Language::English);
+        let mnemonic = Mnemonic::from_entropy_in(language, entropy)
+            .map_err(|e| UbaError::InvalidSeed(format!("Invalid entropy: {}", e)))?;
+        let phrase = zeroize::Zeroizing::new(mnemonic.to_string());
+        self.generate_addresses(&phrase, label)
+    }
+
+    /// Generate watch-only addresses of one type from an account xpub
+    ///
+    /// The xpub stands in for the seed at its own depth of the effective derivation
+    /// path for `address_type`; the remaining path components and the address index
+    /// are derived as public children. For example, an xpub for `m/84'/0'/0'` yields
+    /// the same P2WPKH addresses as the seed does for the default `m/84'/0'/0'/0`.
+    ///
+    /// Only Bitcoin L1 types (P2PKH, P2SH, P2WPKH, P2TR) are supported. Returns
+    /// `UbaError::KeyDerivation` if the remaining path needs hardened derivation,
+    /// which is impossible without the private key.
+    pub fn from_xpub(
+        xpub: &str,
+        address_type: AddressType,
+        config: UbaConfig,
+    ) -> Result<BitcoinAddresses> {
+        let generator = Self::new(config);
+        generator.config.validate_address_counts()?;
+
+        if !matches!(
+            address_type,
+            AddressType::P2PKH | AddressType::P2SHWPKH | AddressType::P2WPKH | AddressType::P2TR
+        ) {
+            return Err(UbaError::Config(format!(
+                "{:?} addresses cannot be generated from an xpub",
+                address_type
+            )));
+        }
+
+        let account_key = Xpub::from_str(xpub.trim())
+            .map_err(|e| UbaError::KeyDerivation(format!("Invalid xpub: {}", e)))?;
+
+        // xpubs only distinguish mainnet from the test networks
+        let xpub_is_mainnet = account_key.network == bitcoin::Network::Bitcoin;
+        if xpub_is_mainnet != (generator.config.network == bitcoin::Network::Bitcoin) {
+            return Err(UbaError::Config(format!(
+                "xpub network does not match configured network {}",
+                generator.config.network
+            )));
+        }
+
+        let full_path = generator.derivation_path(&address_type)?;
+        let relative_path = xpub_relative_path(&account_key, &full_path)?;
+
+        let mut addresses = BitcoinAddresses::new();
+        if let Some(timestamp) = generator.config.fixed_timestamp {
+            addresses.created_at = timestamp;
+        }
+        addresses.metadata = Some(AddressMetadata {
+            label: None,
+            description: Some("UBA watch-only address collection".to_string()),
+            xpub: None, // We don't expose the xpub for privacy
+            derivation_paths: Some(vec![full_path.to_string()]),
+            fingerprint: None, // The xpub does not reveal the master fingerprint
+        });
+
+        for i in generator.index_range(&address_type)? {
+            let child_path = relative_path.child(ChildNumber::from_normal_idx(i)?);
+            let child_key = account_key.derive_pub(&generator.secp, &child_path)?;
+            let public_key = PublicKey::new(child_key.public_key);
+            let address = generator.bitcoin_address(&address_type, &public_key)?;
+
+            addresses.add_address(address_type.clone(), address);
+        }
+
+        if address_type == AddressType::P2TR {
+            if let Some(taproot_addresses) = addresses.get_addresses(&AddressType::P2TR) {
+                for address in taproot_addresses {
+                    validate_taproot_address(address, generator.config.network)?;
+                }
+            }
+        }
+
+        Ok(addresses)
+    }
+
+    /// Derivation indices to generate for an address type
+    ///
+    /// Explicit per-type start indices take precedence over `UbaConfig::start_index`.
+    /// Ranges reaching past the non-hardened BIP32 range (2^31) are a
+    /// `UbaError::Config`.
+    fn index_range(&self, address_type: &AddressType) -> Result<Range<u32>> {
+        let start = self
+            .start_indices
+            .get(address_type)
+            .copied()
+            .unwrap_or(self.config.start_index);
+        let count = self.config.get_address_count(address_type);
+
+        u32::try_from(count)
+            .ok()
+            .and_then(|count| start.checked_add(count))
+            .filter(|&end| end <= 1 << 31)
+            .map(|end| start..end)
+            .ok_or_else(|| {
+                UbaError::Config(format!(
+                    "{} {:?} addresses from index {} overflow the derivation index range",
+                    count, address_type, start
+                ))
+            })
+    }
+
     /// Derive the master extended private key from seed input
     fn derive_master_key(&self, seed_input: &str) -> Result<Xpriv> {
         // Try to parse as BIP39 mnemonic first
-        if let Ok(mnemonic) = Mnemonic::from_str(seed_input) {
-            let seed = mnemonic.to_seed("");
+        if let Ok(mnemonic) = parse_mnemonic(seed_input, self.config.mnemonic_language) {
+            let seed = mnemonic.to_seed(self.config.get_bip39_passphrase());
             Xpriv::new_master(self.config.network, &seed)
                 .map_err(|e| UbaError::AddressGeneration(e.to_string()))
         } else {
-            // Try to parse as hex-encoded private key
-            let key_bytes = hex::decode(seed_input.trim())?;
+            // Try to parse as hex-encoded private key, optionally prefixed with its
+            // network. The key itself carries no network, so the same bytes yield
+            // different addresses under different configured networks.
+            let key_hex = self.strip_network_prefix(seed_input.trim())?;
+            check_hex_key_length(key_hex)?;
+            let key_bytes = hex::decode(key_hex)?;
             if key_bytes.len() != 32 {
                 return Err(UbaError::InvalidSeed(
                     "Private key must be 32 bytes".to_string(),
@@ -110,6 +396,27 @@ impl AddressGenerator {
         }
     }
 
+    /// Strip and check the `<network>:` prefix of a hex private key
+    ///
+    /// A prefix naming another network than the configured one is a
+    /// `UbaError::Config`; a missing prefix is a `UbaError::InvalidSeed` under
+    /// `strict_network`.
+    fn strip_network_prefix<'a>(&self, key_input: &'a str) -> Result<&'a str> {
+        match split_network_prefix(key_input)? {
+            (None, _) if self.config.strict_network => Err(UbaError::InvalidSeed(
+                "strict_network requires hex keys prefixed with their network, e.g. mainnet:<hex>"
+                    .to_string(),
+            )),
+            (Some(network), _) if network != self.config.network => {
+                Err(UbaError::Config(format!(
+                    "Private key is for network {} but the configured network is {}",
+                    network, self.config.network
+                )))
+            }
+            (_, key_hex) => Ok(key_hex),
+        }
+    }
+
     /// Generate legacy P2PKH addresses
     fn generate_legacy_addresses(
         &self,
@@ -118,18 +425,17 @@ impl AddressGenerator {
     ) -> Result<()> {
         // Only generate P2PKH if enabled
         if self.config.is_address_type_enabled(&AddressType::P2PKH) {
-            let derivation_path = DerivationPath::from_str("m/44'/0'/0'/0")?;
-            let count = self.config.get_address_count(&AddressType::P2PKH);
+            let derivation_path = self.derivation_path(&AddressType::P2PKH)?;
 
-            for i in 0..count {
-                let child_path = derivation_path.child(ChildNumber::from_normal_idx(i as u32)?);
+            for i in self.index_range(&AddressType::P2PKH)? {
+                let child_path = derivation_path.child(ChildNumber::from_normal_idx(i)?);
                 let child_key = master_key.derive_priv(&self.secp, &child_path)?;
 
                 let private_key = PrivateKey::new(child_key.private_key, self.config.network);
                 let public_key = PublicKey::from_private_key(&self.secp, &private_key);
-                let address = Address::p2pkh(&public_key, self.config.network);
+                let address = self.bitcoin_address(&AddressType::P2PKH, &public_key)?;
 
-                addresses.add_address(AddressType::P2PKH, address.to_string());
+                addresses.add_address(AddressType::P2PKH, address);
             }
         }
 
@@ -143,36 +449,34 @@ impl AddressGenerator {
         addresses: &mut BitcoinAddresses,
     ) -> Result<()> {
         // P2SH-wrapped SegWit (P2WPKH-in-P2SH) - only if enabled
-        if self.config.is_address_type_enabled(&AddressType::P2SH) {
-            let p2sh_path = DerivationPath::from_str("m/49'/0'/0'/0")?;
-            let p2sh_count = self.config.get_address_count(&AddressType::P2SH);
+        if self.config.is_address_type_enabled(&AddressType::P2SHWPKH) {
+            let p2sh_path = self.derivation_path(&AddressType::P2SHWPKH)?;
 
-            for i in 0..p2sh_count {
-                let child_path = p2sh_path.child(ChildNumber::from_normal_idx(i as u32)?);
+            for i in self.index_range(&AddressType::P2SHWPKH)? {
+                let child_path = p2sh_path.child(ChildNumber::from_normal_idx(i)?);
                 let child_key = master_key.derive_priv(&self.secp, &child_path)?;
 
                 let private_key = PrivateKey::new(child_key.private_key, self.config.network);
                 let public_key = PublicKey::from_private_key(&self.secp, &private_key);
-                let address = Address::p2shwpkh(&public_key, self.config.network)?;
+                let address = self.bitcoin_address(&AddressType::P2SHWPKH, &public_key)?;
 
-                addresses.add_address(AddressType::P2SH, address.to_string());
+                addresses.add_address(AddressType::P2SHWPKH, address);
             }
         }
 
         // Native SegWit (P2WPKH) - only if enabled
         if self.config.is_address_type_enabled(&AddressType::P2WPKH) {
-            let p2wpkh_path = DerivationPath::from_str("m/84'/0'/0'/0")?;
-            let p2wpkh_count = self.config.get_address_count(&AddressType::P2WPKH);
+            let p2wpkh_path = self.derivation_path(&AddressType::P2WPKH)?;
 
-            for i in 0..p2wpkh_count {
-                let child_path = p2wpkh_path.child(ChildNumber::from_normal_idx(i as u32)?);
+            for i in self.index_range(&AddressType::P2WPKH)? {
+                let child_path = p2wpkh_path.child(ChildNumber::from_normal_idx(i)?);
                 let child_key = master_key.derive_priv(&self.secp, &child_path)?;
 
                 let private_key = PrivateKey::new(child_key.private_key, self.config.network);
                 let public_key = PublicKey::from_private_key(&self.secp, &private_key);
-                let address = Address::p2wpkh(&public_key, self.config.network)?;
+                let address = self.bitcoin_address(&AddressType::P2WPKH, &public_key)?;
 
-                addresses.add_address(AddressType::P2WPKH, address.to_string());
+                addresses.add_address(AddressType::P2WPKH, address);
             }
         }
 
@@ -185,97 +489,85 @@ impl AddressGenerator {
         master_key: &Xpriv,
         addresses: &mut BitcoinAddresses,
     ) -> Result<()> {
-        let derivation_path = DerivationPath::from_str("m/86'/0'/0'/0")?;
-        let count = self.config.get_address_count(&AddressType::P2TR);
+        let derivation_path = self.derivation_path(&AddressType::P2TR)?;
 
-        for i in 0..count {
-            let child_path = derivation_path.child(ChildNumber::from_normal_idx(i as u32)?);
+        for i in self.index_range(&AddressType::P2TR)? {
+            let child_path = derivation_path.child(ChildNumber::from_normal_idx(i)?);
             let child_key = master_key.derive_priv(&self.secp, &child_path)?;
 
             let private_key = PrivateKey::new(child_key.private_key, self.config.network);
             let public_key = PublicKey::from_private_key(&self.secp, &private_key);
-            let xonly_pubkey = XOnlyPublicKey::from(public_key);
-            let address = Address::p2tr(&self.secp, xonly_pubkey, None, self.config.network);
+            let address = self.bitcoin_address(&AddressType::P2TR, &public_key)?;
 
-            addresses.add_address(AddressType::P2TR, address.to_string());
+            addresses.add_address(AddressType::P2TR, address);
         }
 
         Ok(())
     }
 
     /// Generate Liquid sidechain addresses
+    ///
+    /// Confidential addresses are produced on mainnet and explicit addresses on other
+    /// networks, unless overridden via `UbaConfig::liquid_confidential`. The blinding
+    /// key of the address at index `i` is the key at index `i + 1000` of the Liquid
+    /// derivation path; this derivation is kept stable so published confidential
+    /// addresses stay reproducible.
     fn generate_liquid_addresses(
         &self,
         master_key: &Xpriv,
         addresses: &mut BitcoinAddresses,
     ) -> Result<()> {
-        // Use BIP84 path for Liquid SegWit addresses: m/84'/1776'/0'/0
-        // 1776 is the coin type for Liquid Network
-        let derivation_path = DerivationPath::from_str("m/84'/1776'/0'/0")?;
-        let count = self.config.get_address_count(&AddressType::Liquid);
-
-        for i in 0..count {
-            let child_path = derivation_path.child(ChildNumber::from_normal_idx(i as u32)?);
+        // Defaults to the BIP84-style Liquid path m/84'/1776'/0'/0
+        let derivation_path = self.derivation_path(&AddressType::Liquid)?;
+
+        // Resolve network parameters up front so unsupported networks fail loudly
+        let elements_network = match self.config.network {
+            bitcoin::Network::Bitcoin => elements::bitcoin::Network::Bitcoin,
+            bitcoin::Network::Testnet => elements::bitcoin::Network::Testnet,
+            bitcoin::Network::Signet => elements::bitcoin::Network::Signet,
+            bitcoin::Network::Regtest => elements::bitcoin::Network::Regtest,
+            network => return Err(unsupported_network(network)),
+        };
+        let address_params = self.liquid_address_params()?;
+
+        for i in self.index_range(&AddressType::Liquid)? {
+            let child_path = derivation_path.child(ChildNumber::from_normal_idx(i)?);
             let child_key = master_key.derive_priv(&self.secp, &child_path)?;
 
             // For Liquid addresses, we need to generate them differently to get the correct prefix
             // Convert the private key to elements format first
-            let elements_private_key = elements::bitcoin::PrivateKey::new(
-                child_key.private_key,
-                match self.config.network {
-                    bitcoin::Network::Bitcoin => elements::bitcoin::Network::Bitcoin,
-                    bitcoin::Network::Testnet => elements::bitcoin::Network::Testnet,
-                    bitcoin::Network::Signet => elements::bitcoin::Network::Signet,
-                    bitcoin::Network::Regtest => elements::bitcoin::Network::Regtest,
-                    _ => elements::bitcoin::Network::Testnet, // Default to testnet for unknown networks
-                },
-            );
+            let elements_private_key =
+                elements::bitcoin::PrivateKey::new(child_key.private_key, elements_network);
 
             let elements_public_key = elements::bitcoin::PublicKey::from_private_key(
                 &secp256k1::Secp256k1::new(),
                 &elements_private_key,
             );
 
-            // Generate Liquid address with proper parameters for mainnet/testnet
-            let liquid_address = match self.config.network {
-                bitcoin::Network::Bitcoin => {
-                    // For Liquid mainnet, create confidential address with proper parameters
-                    let address_params = &elements::AddressParams::LIQUID;
-
-                    // For proper Liquid mainnet addresses, we should use confidential transactions
-                    // Generate a blinding public key from the master key for this address
-                    let blinding_private_key = {
-                        let blinding_path =
-                            derivation_path.child(ChildNumber::from_normal_idx((i + 1000) as u32)?);
-                        let blinding_key = master_key.derive_priv(&self.secp, &blinding_path)?;
-                        blinding_key.private_key
-                    };
-                    let blinding_public_key =
-                        secp256k1::PublicKey::from_secret_key(&self.secp, &blinding_private_key);
-
-                    // Create confidential address with blinding key (using secp256k1::PublicKey directly)
-                    LiquidAddress::p2wpkh(
-                        &elements_public_key,
-                        Some(blinding_public_key),
-                        address_params,
-                    )
-                }
-                _ => {
-                    // For testnet/regtest, use appropriate parameters
-                    let address_params = match self.config.network {
-                        bitcoin::Network::Testnet | bitcoin::Network::Signet => {
-                            &elements::AddressParams::LIQUID_TESTNET
-                        }
-                        bitcoin::Network::Regtest => &elements::AddressParams::ELEMENTS,
-                        _ => &elements::AddressParams::LIQUID_TESTNET,
-                    };
-
-                    // Create non-confidential address for testnet (simpler for testing)
-                    LiquidAddress::p2wpkh(&elements_public_key, None, address_params)
-                }
+            // Confidential addresses embed the public key at index i + 1000 of the
+            // same path as their blinding key
+            let blinding_public_key = if self.config.is_liquid_confidential() {
+                let blinding_path =
+                    derivation_path.child(ChildNumber::from_normal_idx(i + 1000)?);
+                let blinding_key = master_key.derive_priv(&self.secp, &blinding_path)?;
+                Some(secp256k1::PublicKey::from_secret_key(
+                    &self.secp,
+                    &blinding_key.private_key,
+                ))
+            } else {
+                None
             };
 
-            addresses.add_address(AddressType::Liquid, liquid_address.to_string());
+            let liquid_address =
+                LiquidAddress::p2wpkh(&elements_public_key, blinding_public_key, address_params)
+                    .to_string();
+            validate_liquid_address(
+                &liquid_address,
+                address_params,
+                blinding_public_key.is_some(),
+            )?;
+
+            addresses.add_address(AddressType::Liquid, liquid_address);
         }
 
         Ok(())
@@ -287,13 +579,11 @@ impl AddressGenerator {
         master_key: &Xpriv,
         addresses: &mut BitcoinAddresses,
     ) -> Result<()> {
-        // Use a specific derivation path for Lightning node keys: m/1017'/0'/0'
-        // 1017 is used for Lightning node identity keys
-        let derivation_path = DerivationPath::from_str("m/1017'/0'/0'")?;
-        let count = self.config.get_address_count(&AddressType::Lightning);
+        // Defaults to the Lightning node key path m/1017'/0'/0'
+        let derivation_path = self.derivation_path(&AddressType::Lightning)?;
 
-        for i in 0..count {
-            let child_path = derivation_path.child(ChildNumber::from_normal_idx(i as u32)?);
+        for i in self.index_range(&AddressType::Lightning)? {
+            let child_path = derivation_path.child(ChildNumber::from_normal_idx(i)?);
             let child_key = master_key.derive_priv(&self.secp, &child_path)?;
 
             // Convert to secp256k1 public key for Lightning
@@ -303,70 +593,621 @@ impl AddressGenerator {
             // Format as Lightning node public key (33 bytes compressed, hex encoded)
             let lightning_node_id = hex::encode(lightning_pubkey.serialize());
 
-            // Lightning addresses are typically the node public key
-            // In the future, this could also include:
-            // - BOLT12 offers
-            // - Lightning addresses (email-like format)
-            // - Channel information
-
             addresses.add_address(AddressType::Lightning, lightning_node_id);
         }
 
         Ok(())
     }
 
+    /// Generate static BOLT12 offers for the Lightning node keys
+    ///
+    /// Each offer is signed by the node key at the same index as the corresponding
+    /// `AddressType::Lightning` entry, so the offer and node ID always belong together.
+    /// Offers carry no metadata or expiry, which keeps the encoding deterministic.
+    fn generate_lightning_offers(
+        &self,
+        master_key: &Xpriv,
+        addresses: &mut BitcoinAddresses,
+    ) -> Result<()> {
+        let derivation_path = self.derivation_path(&AddressType::LightningOffer)?;
+
+        // The lightning crate is built against an older secp256k1/bitcoin release,
+        // so keys and networks are converted through their serialized forms
+        let chain = match self.config.network {
+            bitcoin::Network::Bitcoin => bitcoin_lightning::Network::Bitcoin,
+            bitcoin::Network::Testnet => bitcoin_lightning::Network::Testnet,
+            bitcoin::Network::Signet => bitcoin_lightning::Network::Signet,
+            bitcoin::Network::Regtest => bitcoin_lightning::Network::Regtest,
+            network => return Err(unsupported_network(network)),
+        };
+
+        for i in self.index_range(&AddressType::LightningOffer)? {
+            let child_path = derivation_path.child(ChildNumber::from_normal_idx(i)?);
+            let child_key = master_key.derive_priv(&self.secp, &child_path)?;
+
+            let node_pubkey =
+                Secp256k1PublicKey::from_secret_key(&self.secp, &child_key.private_key);
+            let signing_pubkey =
+                bitcoin_lightning::secp256k1::PublicKey::from_slice(&node_pubkey.serialize())
+                    .map_err(|e| UbaError::AddressGeneration(e.to_string()))?;
+
+            let offer = OfferBuilder::new("UBA".to_string(), signing_pubkey)
+                .chain(chain)
+                .build()
+                .map_err(|e| {
+                    UbaError::AddressGeneration(format!("Failed to build BOLT12 offer: {:?}", e))
+                })?;
+
+            addresses.add_address(AddressType::LightningOffer, offer.to_string());
+        }
+
+        Ok(())
+    }
+
     /// Generate Nostr public key
     fn generate_nostr_addresses(
         &self,
         master_key: &Xpriv,
         addresses: &mut BitcoinAddresses,
     ) -> Result<()> {
-        // Use a specific derivation path for Nostr keys: m/44'/1237'/0'/0
-        // 1237 is a proposed coin type for Nostr (not officially assigned)
-        let derivation_path = DerivationPath::from_str("m/44'/1237'/0'/0")?;
-        let count = self.config.get_address_count(&AddressType::Nostr);
+        // Defaults to the Nostr key path m/44'/1237'/0'/0
+        let derivation_path = self.derivation_path(&AddressType::Nostr)?;
 
-        for i in 0..count {
-            let child_path = derivation_path.child(ChildNumber::from_normal_idx(i as u32)?);
+        for i in self.index_range(&AddressType::Nostr)? {
+            let child_path = derivation_path.child(ChildNumber::from_normal_idx(i)?);
             let child_key = master_key.derive_priv(&self.secp, &child_path)?;
 
-            // Convert the private key to a Nostr public key
-            // Nostr uses secp256k1 keys, same as Bitcoin
-            let nostr_secret_key = nostr::SecretKey::from_slice(
-                &child_key.private_key.secret_bytes(),
-            )
+            // Nostr public keys are the x-only form of the secp256k1 key, as in Taproot
+            let (public_key, _) = child_key.private_key.x_only_public_key(&self.secp);
+            addresses.add_address(AddressType::Nostr, encode_npub(&public_key)?);
+        }
+
+        Ok(())
+    }
+
+    /// Check that every address is well-formed for its declared type on the
+    /// configured network
+    ///
+    /// Intended for externally provided collections. Bitcoin L1 and Liquid
+    /// addresses must parse as their type on the network. Lightning entries
+    /// containing `@` must be valid Lightning Addresses and hex entries 66-char
+    /// node ids; Nostr entries starting with `npub1` must decode. Other Lightning
+    /// and Nostr formats pass unless `UbaConfig::strict_address_formats` is set.
+    /// All failures are collected into a single `UbaError::UpdateValidation`
+    /// naming each bad entry by type and index.
+    pub fn validate_addresses(&self, addresses: &BitcoinAddresses) -> Result<()> {
+        let network = self.config.network;
+        let mut failures = Vec::new();
+
+        for (address_type, index, address) in addresses.iter_typed() {
+            let expected = match address_type {
+                AddressType::P2PKH => bitcoin::AddressType::P2pkh,
+                AddressType::P2SHWPKH => bitcoin::AddressType::P2sh,
+                AddressType::P2WPKH => bitcoin::AddressType::P2wpkh,
+                AddressType::P2TR => bitcoin::AddressType::P2tr,
+                AddressType::Liquid => {
+                    if let Err(reason) = self.check_liquid_address(address) {
+                        failures.push(format!("Liquid[{}] {}: {}", index, address, reason));
+                    }
+                    continue;
+                }
+                AddressType::Lightning | AddressType::Nostr => {
+                    let strict = self.config.strict_address_formats;
+                    if let Err(reason) = check_entry_format(&address_type, address, strict) {
+                        failures.push(format!(
+                            "{:?}[{}] {}: {}",
+                            address_type, index, address, reason
+                        ));
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let reason = match Address::from_str(address) {
+                Err(e) => Some(e.to_string()),
+                Ok(parsed) if !parsed.is_valid_for_network(network) => {
+                    Some(format!("not a {} address", network))
+                }
+                Ok(parsed) => match parsed.assume_checked().address_type() {
+                    Some(actual) if actual == expected => None,
+                    actual => Some(format!("expected {}, found {:?}", expected, actual)),
+                },
+            };
+
+            if let Some(reason) = reason {
+                failures.push(format!("{:?}[{}] {}: {}", address_type, index, address, reason));
+            }
+        }
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(UbaError::UpdateValidation(format!(
+                "Invalid addresses for {}: {}",
+                network,
+                failures.join("; ")
+            )))
+        }
+    }
+
+    /// Check that a Liquid address belongs to the configured elements chain
+    fn check_liquid_address(&self, address: &str) -> std::result::Result<(), String> {
+        let params = self.liquid_address_params().map_err(|e| e.to_string())?;
+        let parsed = LiquidAddress::from_str(address).map_err(|e| e.to_string())?;
+        if parsed.params != params {
+            return Err("address belongs to another elements chain".to_string());
+        }
+        Ok(())
+    }
+
+    /// Get the elements address params for the configured network
+    ///
+    /// Signet has no dedicated Liquid deployment and shares the Liquid testnet
+    /// params; custom elements chains set `liquid_params_override` instead.
+    fn liquid_address_params(&self) -> Result<&'static elements::AddressParams> {
+        match (self.config.liquid_params_override, self.config.network) {
+            (Some(params), _) => Ok(params),
+            (None, bitcoin::Network::Bitcoin) => Ok(&elements::AddressParams::LIQUID),
+            (None, bitcoin::Network::Testnet | bitcoin::Network::Signet) => {
+                Ok(&elements::AddressParams::LIQUID_TESTNET)
+            }
+            (None, bitcoin::Network::Regtest) => Ok(&elements::AddressParams::ELEMENTS),
+            (None, network) => Err(unsupported_network(network)),
+        }
+    }
+
+    /// Encode the Bitcoin L1 address of the given type for a public key
+    fn bitcoin_address(&self, address_type: &AddressType, public_key: &PublicKey) -> Result<String> {
+        let network = self.config.network;
+        let address = match address_type {
+            AddressType::P2PKH => Address::p2pkh(public_key, network),
+            // P2SH addresses are P2WPKH-in-P2SH
+            AddressType::P2SHWPKH => Address::p2shwpkh(public_key, network)?,
+            AddressType::P2WPKH => Address::p2wpkh(public_key, network)?,
+            AddressType::P2TR => {
+                let xonly_pubkey = XOnlyPublicKey::from(*public_key);
+                Address::p2tr(&self.secp, xonly_pubkey, self.config.taproot_merkle_root, network)
+            }
+            other => {
+                return Err(UbaError::AddressGeneration(format!(
+                    "{:?} is not a Bitcoin address type",
+                    other
+                )))
+            }
+        };
+        Ok(address.to_string())
+    }
+
+    /// Export the Nostr identity published as the first `AddressType::Nostr` entry
+    ///
+    /// Returns the bech32-encoded `(nsec, npub)` pair for index 0 of the Nostr
+    /// derivation path (default `m/44'/1237'/0'/0`).
+    ///
+    /// **The nsec is a secret key.** Anyone holding it can sign as this identity.
+    #[cfg(feature = "nostr")]
+    pub fn nostr_keys(&self, seed_input: &str) -> Result<(String, String)> {
+        self.config.validate_derivation_paths()?;
+
+        let master_key = self.derive_master_key(seed_input)?;
+        let derivation_path = self.derivation_path(&AddressType::Nostr)?;
+        let keys = self.derive_nostr_keys(&master_key, &derivation_path, 0)?;
+
+        let encode_error =
+            |e: nostr::nips::nip19::Error| UbaError::AddressGeneration(e.to_string());
+        let nsec = keys.secret_key()?.to_bech32().map_err(encode_error)?;
+        let npub = keys.public_key().to_bech32().map_err(encode_error)?;
+        Ok((nsec, npub))
+    }
+
+    /// Derive the Nostr keys at `index` below the Nostr derivation path
+    #[cfg(feature = "nostr")]
+    fn derive_nostr_keys(
+        &self,
+        master_key: &Xpriv,
+        derivation_path: &DerivationPath,
+        index: u32,
+    ) -> Result<nostr::Keys> {
+        let child_path = derivation_path.child(ChildNumber::from_normal_idx(index)?);
+        let child_key = master_key.derive_priv(&self.secp, &child_path)?;
+
+        // Nostr uses secp256k1 keys, same as Bitcoin
+        let nostr_secret_key = nostr::SecretKey::from_slice(&child_key.private_key.secret_bytes())
             .map_err(|e| {
                 UbaError::AddressGeneration(format!("Failed to create Nostr secret key: {}", e))
             })?;
 
-            let nostr_keys = nostr::Keys::new(nostr_secret_key);
-            let nostr_public_key = nostr_keys.public_key();
+        Ok(nostr::Keys::new(nostr_secret_key))
+    }
 
-            // Convert to npub format (Bech32-encoded public key)
-            let npub_address = nostr_public_key.to_bech32().map_err(|e| {
-                UbaError::AddressGeneration(format!("Failed to create npub address: {}", e))
-            })?;
+    /// Parse the effective derivation path for an address type
+    fn derivation_path(&self, address_type: &AddressType) -> Result<DerivationPath> {
+        Ok(DerivationPath::from_str(
+            self.config.get_derivation_path(address_type),
+        )?)
+    }
 
-            addresses.add_address(AddressType::Nostr, npub_address);
+    /// Get the effective derivation paths of the enabled address types
+    ///
+    /// Types sharing a path (Lightning node IDs and offers) are listed once.
+    fn get_derivation_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = Vec::new();
+        for address_type in self.config.get_enabled_address_types() {
+            let path = self.config.get_derivation_path(&address_type);
+            if !paths.iter().any(|existing| existing == path) {
+                paths.push(path.to_string());
+            }
         }
+        paths
+    }
+}
 
-        Ok(())
+/// Check the structure of a Lightning or Nostr entry
+///
+/// Lightning entries containing `@` must be valid Lightning Addresses and
+/// all-hex entries 66-char node ids; Nostr entries starting with `npub1` must
+/// decode. Other formats (LNURL, BOLT11/12, hex Nostr keys, ...) pass unless
+/// `strict` is set, which only allows node ids, Lightning Addresses and npubs.
+/// Entries of other types are not checked.
+pub(crate) fn check_entry_format(
+    address_type: &AddressType,
+    entry: &str,
+    strict: bool,
+) -> std::result::Result<(), String> {
+    match address_type {
+        AddressType::Lightning if entry.contains('@') => {
+            validation::validate_lightning_address(entry).map_err(|e| e.to_string())
+        }
+        AddressType::Lightning if strict || entry.chars().all(|c| c.is_ascii_hexdigit()) => {
+            validation::validate_lightning_node_id(entry).map_err(|e| e.to_string())
+        }
+        AddressType::Nostr if (strict || entry.starts_with("npub1")) && !is_npub(entry) => {
+            Err("not a valid npub".to_string())
+        }
+        _ => Ok(()),
     }
+}
 
-    /// Get the derivation paths used for address generation
-    fn get_derivation_paths(&self) -> Vec<String> {
-        vec![
-            "m/44'/0'/0'/0".to_string(),    // Legacy
-            "m/49'/0'/0'/0".to_string(),    // P2SH-wrapped SegWit
-            "m/84'/0'/0'/0".to_string(),    // Native SegWit
-            "m/86'/0'/0'/0".to_string(),    // Taproot
-            "m/84'/1776'/0'/0".to_string(), // Liquid
-            "m/1017'/0'/0'".to_string(),    // Lightning
-            "m/44'/1237'/0'/0".to_string(), // Nostr
-        ]
+/// Split an optional `<network>:` prefix off a hex private key
+///
+/// Accepts `mainnet`/`bitcoin`, `testnet`, `signet` and `regtest`, in any case.
+/// Input without a prefix is returned unchanged with no network.
+pub(crate) fn split_network_prefix(key_input: &str) -> Result<(Option<bitcoin::Network>, &str)> {
+    let Some((prefix, key_hex)) = key_input.split_once(':') else {
+        return Ok((None, key_input));
+    };
+
+    let network = match prefix.trim().to_ascii_lowercase().as_str() {
+        "mainnet" | "bitcoin" => bitcoin::Network::Bitcoin,
+        "testnet" => bitcoin::Network::Testnet,
+        "signet" => bitcoin::Network::Signet,
+        "regtest" => bitcoin::Network::Regtest,
+        _ => {
+            return Err(UbaError::InvalidSeed(format!(
+                "Unknown network prefix '{}' on private key",
+                prefix
+            )))
+        }
+    };
+    Ok((Some(network), key_hex.trim()))
+}
+
+/// Parse a BIP39 mnemonic in `language`, or in whichever wordlist it belongs to
+///
+/// Words shared by several wordlists make detection ambiguous; the mnemonic is
+/// then parsed in the first candidate language whose checksum matches.
+pub(crate) fn parse_mnemonic(
+    input: &str,
+    language: Option<Language>,
+) -> std::result::Result<Mnemonic, bip39::Error> {
+    match language {
+        Some(language) => Mnemonic::parse_in(language, input),
+        None => match Mnemonic::parse(input) {
+            Err(bip39::Error::AmbiguousLanguages(candidates)) => candidates
+                .iter()
+                .find_map(|candidate| Mnemonic::parse_in(candidate, input).ok())
+                .ok_or(bip39::Error::AmbiguousLanguages(candidates)),
+            result => result,
+        },
     }
 }
 
+/// Derive the seed's identity key, which authors its UBA events and signs payloads
+///
+/// The key is the SHA-256 of the 32-byte hex key, or of the BIP39 seed for a
+/// mnemonic, using `config`'s BIP39 passphrase. Without a passphrase the key is
+/// the same as before passphrases were honored. An optional `<network>:` prefix
+/// is ignored.
+pub(crate) fn identity_secret_key(
+    seed: &str,
+    config: &UbaConfig,
+) -> Result<bitcoin::secp256k1::SecretKey> {
+    use bitcoin::hashes::{sha256, Hash};
+
+    let (_, seed) = split_network_prefix(seed.trim())?;
+    check_hex_key_length(seed)?;
+    let seed_bytes = if seed.len() == 64 {
+        // Assume hex-encoded
+        hex::decode(seed)?
+    } else {
+        // Use BIP39 seed
+        let mnemonic = parse_mnemonic(seed, config.mnemonic_language)?;
+        mnemonic.to_seed(config.get_bip39_passphrase()).to_vec()
+    };
+
+    // Hash the seed to get a 32-byte key
+    let hash = sha256::Hash::hash(&seed_bytes);
+    Ok(bitcoin::secp256k1::SecretKey::from_slice(hash.as_ref())?)
+}
+
+/// BIP340 tag for challenge signatures, so they can never double as signatures
+/// over Nostr event IDs made with the same key
+const CHALLENGE_TAG: &[u8] = b"UBA/challenge";
+
+/// Sign a challenge with the seed's Nostr identity key to prove control of its UBAs
+///
+/// The identity key is the one that authors the seed's UBA events, so a verifier
+/// who knows a UBA's author can check the signature with `verify_challenge`
+/// without learning anything about the seed. The challenge is hashed with a
+/// domain tag before signing.
+///
+/// # Example
+/// ```rust
+/// use uba::{sign_challenge, verify_challenge};
+///
+/// let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+/// let challenge = b"credit account 42, nonce 8f1c";
+/// let signature = sign_challenge(seed, challenge)?;
+///
+/// // The verifier only needs the UBA's author key (hex or npub)
+/// let author = uba::identity_public_key(seed)?;
+/// assert!(verify_challenge(&author, challenge, &signature));
+/// # Ok::<(), uba::UbaError>(())
+/// ```
+pub fn sign_challenge(
+    seed: &str,
+    challenge: &[u8],
+) -> Result<bitcoin::secp256k1::schnorr::Signature> {
+    sign_challenge_with_config(seed, challenge, &UbaConfig::default())
+}
+
+/// Sign a challenge with the identity key derived using `config`'s BIP39 passphrase
+///
+/// Use this when the UBA was generated with a passphrase, so the signature
+/// matches the author of its events.
+pub fn sign_challenge_with_config(
+    seed: &str,
+    challenge: &[u8],
+    config: &UbaConfig,
+) -> Result<bitcoin::secp256k1::schnorr::Signature> {
+    let secp = Secp256k1::new();
+    let secret_key = identity_secret_key(seed, config)?;
+    let keypair = bitcoin::secp256k1::Keypair::from_secret_key(&secp, &secret_key);
+    Ok(secp.sign_schnorr_no_aux_rand(&challenge_message(challenge), &keypair))
+}
+
+/// Check that `signature` over `challenge` was made by `author`
+///
+/// `author` is the UBA author's public key as 64 hex characters or an `npub`.
+/// Returns false for a malformed author or a signature by any other key.
+pub fn verify_challenge(
+    author: &str,
+    challenge: &[u8],
+    signature: &bitcoin::secp256k1::schnorr::Signature,
+) -> bool {
+    let author = author.trim();
+    let public_key = match decode_npub(author) {
+        Some(public_key) => public_key,
+        None => match XOnlyPublicKey::from_str(author) {
+            Ok(public_key) => public_key,
+            Err(_) => return false,
+        },
+    };
+
+    Secp256k1::verification_only()
+        .verify_schnorr(signature, &challenge_message(challenge), &public_key)
+        .is_ok()
+}
+
+/// Get the hex x-only public key of the seed's Nostr identity, the author of its
+/// UBA events
+pub fn identity_public_key(seed: &str) -> Result<String> {
+    identity_public_key_with_config(seed, &UbaConfig::default())
+}
+
+/// Get the identity public key derived using `config`'s BIP39 passphrase
+pub fn identity_public_key_with_config(seed: &str, config: &UbaConfig) -> Result<String> {
+    let secp = Secp256k1::new();
+    let (public_key, _) = identity_secret_key(seed, config)?.x_only_public_key(&secp);
+    Ok(public_key.to_string())
+}
+
+/// BIP340 tagged hash of a challenge
+fn challenge_message(challenge: &[u8]) -> bitcoin::secp256k1::Message {
+    use bitcoin::hashes::{sha256, Hash, HashEngine};
+
+    let tag = sha256::Hash::hash(CHALLENGE_TAG);
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    engine.input(challenge);
+    let digest = sha256::Hash::from_engine(engine);
+    bitcoin::secp256k1::Message::from_digest(digest.to_byte_array())
+}
+
+/// Reject hex strings that are clearly meant as a private key but have the wrong length
+///
+/// A key with a character missing or added in a copy-paste would otherwise end up
+/// being parsed as a mnemonic or fail with a generic hex error.
+pub(crate) fn check_hex_key_length(seed_input: &str) -> Result<()> {
+    let looks_like_hex =
+        !seed_input.is_empty() && seed_input.chars().all(|c| c.is_ascii_hexdigit());
+    if looks_like_hex && seed_input.len() != 64 {
+        return Err(UbaError::InvalidSeed(format!(
+            "Seed looks like a hex private key but has {} characters; expected 64 (32 bytes). \
+             Was it truncated or copied with extra characters?",
+            seed_input.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Work out which address type a string belongs to on `network`
+///
+/// Bitcoin L1 addresses must parse and be valid for `network`; any P2SH address
+/// is reported as `P2SHWPKH`, since a wrapped P2WPKH cannot be told apart from
+/// other scripts. Liquid addresses must use the network's default elements params.
+/// Lightning entries are 33-byte compressed node ids in hex, Lightning offers are
+/// BOLT12 `lno1` strings and Nostr entries are NIP-19 `npub`s.
+///
+/// Returns `None` for anything else, including segwit v0 script-hash addresses
+/// and addresses of another network.
+pub fn classify_address(address: &str, network: bitcoin::Network) -> Option<AddressType> {
+    let address = address.trim();
+
+    if let Ok(parsed) = Address::from_str(address) {
+        if !parsed.is_valid_for_network(network) {
+            return None;
+        }
+        return match parsed.assume_checked().address_type()? {
+            bitcoin::AddressType::P2pkh => Some(AddressType::P2PKH),
+            bitcoin::AddressType::P2sh => Some(AddressType::P2SHWPKH),
+            bitcoin::AddressType::P2wpkh => Some(AddressType::P2WPKH),
+            bitcoin::AddressType::P2tr => Some(AddressType::P2TR),
+            _ => None,
+        };
+    }
+
+    if let Ok(parsed) = LiquidAddress::from_str(address) {
+        let params = match network {
+            bitcoin::Network::Bitcoin => &elements::AddressParams::LIQUID,
+            bitcoin::Network::Testnet | bitcoin::Network::Signet => {
+                &elements::AddressParams::LIQUID_TESTNET
+            }
+            bitcoin::Network::Regtest => &elements::AddressParams::ELEMENTS,
+            _ => return None,
+        };
+        return (parsed.params == params).then_some(AddressType::Liquid);
+    }
+
+    if address.len() == 66 {
+        let is_node_id = hex::decode(address)
+            .ok()
+            .and_then(|bytes| Secp256k1PublicKey::from_slice(&bytes).ok())
+            .is_some();
+        return is_node_id.then_some(AddressType::Lightning);
+    }
+
+    if address.contains('@') {
+        return is_lightning_address(address).then_some(AddressType::Lightning);
+    }
+
+    if address.get(..4).is_some_and(|prefix| prefix.eq_ignore_ascii_case("lno1")) {
+        return lightning::offers::offer::Offer::from_str(address)
+            .ok()
+            .map(|_| AddressType::LightningOffer);
+    }
+
+    is_npub(address).then_some(AddressType::Nostr)
+}
+
+/// Get the part of `full_path` below an xpub, which must be derivable without hardening
+///
+/// The xpub's depth says how many leading components it already covers; its child
+/// number must match the last of them.
+fn xpub_relative_path(xpub: &Xpub, full_path: &DerivationPath) -> Result<DerivationPath> {
+    let components: Vec<ChildNumber> = full_path.into_iter().copied().collect();
+    let depth = xpub.depth as usize;
+
+    if depth > components.len() {
+        return Err(UbaError::KeyDerivation(format!(
+            "xpub at depth {} is deeper than derivation path {}",
+            depth, full_path
+        )));
+    }
+    if depth > 0 && components[depth - 1] != xpub.child_number {
+        return Err(UbaError::KeyDerivation(format!(
+            "xpub child number {} does not match derivation path {}",
+            xpub.child_number, full_path
+        )));
+    }
+
+    let remaining = &components[depth..];
+    if let Some(hardened) = remaining.iter().find(|child| child.is_hardened()) {
+        return Err(UbaError::KeyDerivation(format!(
+            "Cannot derive hardened index {} of {} from an xpub",
+            hardened, full_path
+        )));
+    }
+
+    Ok(DerivationPath::from(remaining.to_vec()))
+}
+
+/// Check that a generated Taproot address is a bech32m, witness v1, 32-byte program
+///
+/// Parsing enforces the checksum variant for the witness version (bech32 for v0,
+/// bech32m for v1+), so a v1 program encoded with a bech32 checksum is rejected here
+/// along with any v0 address that ended up in the P2TR list.
+fn validate_taproot_address(address: &str, network: bitcoin::Network) -> Result<()> {
+    let invalid = |reason: String| {
+        UbaError::AddressGeneration(format!("Invalid Taproot address {}: {}", address, reason))
+    };
+
+    let parsed = Address::from_str(address)
+        .map_err(|e| invalid(e.to_string()))?
+        .require_network(network)
+        .map_err(|e| invalid(e.to_string()))?;
+
+    // P2TR is exactly a witness v1 output with a 32-byte program
+    match parsed.address_type() {
+        Some(bitcoin::AddressType::P2tr) if parsed.script_pubkey().is_p2tr() => Ok(()),
+        Some(bitcoin::AddressType::P2tr) => {
+            Err(invalid("expected a 32-byte witness v1 program".to_string()))
+        }
+        Some(other) => Err(invalid(format!("expected p2tr, found {}", other))),
+        None => Err(invalid("not a standard address type".to_string())),
+    }
+}
+
+/// Check that a generated Liquid address re-parses under the network's address params
+///
+/// Parsing with explicit params rejects an address whose human-readable prefix
+/// belongs to another network (e.g. `lq1` under Liquid testnet params).
+fn validate_liquid_address(
+    address: &str,
+    params: &'static elements::AddressParams,
+    confidential: bool,
+) -> Result<()> {
+    let invalid = |reason: String| {
+        UbaError::AddressGeneration(format!("Invalid Liquid address {}: {}", address, reason))
+    };
+
+    let parsed = LiquidAddress::parse_with_params(address, params)
+        .map_err(|e| invalid(e.to_string()))?;
+
+    if parsed.params != params {
+        return Err(invalid("address params do not match the network".to_string()));
+    }
+
+    if parsed.is_blinded() != confidential {
+        return Err(invalid(format!(
+            "expected a {} address",
+            if confidential { "confidential" } else { "explicit" }
+        )));
+    }
+
+    Ok(())
+}
+
+/// Build the error returned for networks without explicit Liquid/Lightning mappings
+///
+/// `bitcoin::Network` is non-exhaustive, so networks added upstream (such as
+/// Testnet4 in later `bitcoin` releases) must be mapped explicitly rather than
+/// silently falling back to another network's parameters.
+fn unsupported_network(network: bitcoin::Network) -> UbaError {
+    UbaError::Config(format!("Unsupported network: {}", network))
+}
+
 impl From<bitcoin::bip32::Error> for UbaError {
     fn from(err: bitcoin::bip32::Error) -> Self {
         UbaError::AddressGeneration(err.to_string())
@@ -379,6 +1220,45 @@ impl From<elements::AddressError> for UbaError {
     }
 }
 
+/// Human-readable part of NIP-19 public keys
+const NPUB_HRP: Hrp = Hrp::parse_unchecked("npub");
+
+/// Encode an x-only public key as a NIP-19 `npub`
+///
+/// Encoded here rather than through the `nostr` crate so address generation works
+/// without the `nostr` feature; the output is the same.
+fn encode_npub(public_key: &XOnlyPublicKey) -> Result<String> {
+    bech32::encode::<Bech32>(NPUB_HRP, &public_key.serialize())
+        .map_err(|e| UbaError::AddressGeneration(format!("Failed to encode npub: {}", e)))
+}
+
+/// Check whether a string is a Lightning Address (`name@domain`, LUD-16)
+///
+/// Lightning entries of a collection hold either node ids or Lightning Addresses;
+/// this tells them apart.
+pub fn is_lightning_address(value: &str) -> bool {
+    validation::validate_lightning_address(value).is_ok()
+}
+
+/// Check that a string is a well-formed NIP-19 `npub` with a valid checksum
+fn is_npub(value: &str) -> bool {
+    decode_npub(value).is_some()
+}
+
+/// Decode a NIP-19 `npub` into its x-only public key
+fn decode_npub(value: &str) -> Option<XOnlyPublicKey> {
+    // NIP-19 entities are lowercase bech32 (not bech32m)
+    if !value.starts_with("npub1") {
+        return None;
+    }
+    let checked = CheckedHrpstring::new::<Bech32>(value).ok()?;
+    if checked.hrp() != NPUB_HRP {
+        return None;
+    }
+    let bytes: Vec<u8> = checked.byte_iter().collect();
+    XOnlyPublicKey::from_slice(&bytes).ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -439,10 +1319,138 @@ mod tests {
         let liquid_addresses = addresses.get_addresses(&AddressType::Liquid).expect("Liquid addresses should exist");
         assert!(!liquid_addresses.is_empty());
 
-        // Liquid addresses should start with appropriate prefixes
+        // Mainnet addresses are confidential by default and use the Liquid blech32 prefix
         for addr in liquid_addresses {
-            // Liquid mainnet addresses typically start with 'lq1' or similar
-            assert!(addr.len() > 10, "Liquid address should be reasonably long");
+            assert!(addr.starts_with("lq1"), "unexpected mainnet prefix: {}", addr);
+            assert!(validate_liquid_address(addr, &elements::AddressParams::LIQUID, true).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_liquid_address_prefixes_per_network() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        let first_liquid_address = |network: bitcoin::Network, confidential: bool| {
+            let mut config = UbaConfig {
+                network,
+                ..Default::default()
+            };
+            config.set_liquid_confidential(confidential);
+            let addresses = AddressGenerator::new(config)
+                .generate_addresses(mnemonic, None)
+                .unwrap();
+            addresses.get_addresses(&AddressType::Liquid).unwrap()[0].clone()
+        };
+
+        assert!(first_liquid_address(bitcoin::Network::Bitcoin, true).starts_with("lq1"));
+        assert!(first_liquid_address(bitcoin::Network::Bitcoin, false).starts_with("ex1"));
+        assert!(first_liquid_address(bitcoin::Network::Testnet, true).starts_with("tlq1"));
+        assert!(first_liquid_address(bitcoin::Network::Testnet, false).starts_with("tex1"));
+        assert!(first_liquid_address(bitcoin::Network::Regtest, false).starts_with("ert1"));
+
+        // An address from one network does not validate under another's params
+        let mainnet = first_liquid_address(bitcoin::Network::Bitcoin, true);
+        let result =
+            validate_liquid_address(&mainnet, &elements::AddressParams::LIQUID_TESTNET, true);
+        assert!(matches!(result, Err(UbaError::AddressGeneration(_))));
+    }
+
+    #[test]
+    fn test_liquid_confidential_override() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        let liquid_address = |config: UbaConfig| {
+            let addresses = AddressGenerator::new(config)
+                .generate_addresses(mnemonic, None)
+                .unwrap();
+            let address = addresses.get_addresses(&AddressType::Liquid).unwrap()[0].clone();
+            elements::Address::from_str(&address).unwrap()
+        };
+
+        // Network defaults: confidential on mainnet, explicit on testnet
+        let default_mainnet = liquid_address(UbaConfig::default());
+        assert!(default_mainnet.is_blinded());
+
+        let mut testnet_config = UbaConfig {
+            network: bitcoin::Network::Testnet,
+            ..Default::default()
+        };
+        assert!(!liquid_address(testnet_config.clone()).is_blinded());
+
+        // Explicit mainnet addresses share the same payload as the confidential default
+        let mut explicit_config = UbaConfig::default();
+        explicit_config.set_liquid_confidential(false);
+        let explicit_mainnet = liquid_address(explicit_config);
+        assert!(!explicit_mainnet.is_blinded());
+        assert_eq!(explicit_mainnet.payload, default_mainnet.payload);
+
+        // Confidential testnet addresses can be forced as well
+        testnet_config.set_liquid_confidential(true);
+        assert!(liquid_address(testnet_config).is_blinded());
+    }
+
+    #[test]
+    fn test_liquid_blinding_key_derivation_is_stable() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let addresses = AddressGenerator::new(UbaConfig::default())
+            .generate_addresses(mnemonic, None)
+            .unwrap();
+        let address = addresses.get_addresses(&AddressType::Liquid).unwrap()[0].clone();
+        let blinding_public_key = elements::Address::from_str(&address)
+            .unwrap()
+            .blinding_pubkey
+            .unwrap();
+
+        // The blinding key of index 0 is the key at index 1000 of the Liquid path
+        let secp = Secp256k1::new();
+        let seed = Mnemonic::parse(mnemonic).unwrap().to_seed("");
+        let master_key = Xpriv::new_master(bitcoin::Network::Bitcoin, &seed).unwrap();
+        let path = DerivationPath::from_str("m/84'/1776'/0'/0/1000").unwrap();
+        let blinding_key = master_key.derive_priv(&secp, &path).unwrap();
+        assert_eq!(
+            blinding_public_key,
+            secp256k1::PublicKey::from_secret_key(&secp, &blinding_key.private_key)
+        );
+    }
+
+    #[test]
+    fn test_liquid_params_override() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        // A signet deployment backed by elements regtest params
+        let mut config = UbaConfig {
+            network: bitcoin::Network::Signet,
+            ..Default::default()
+        };
+        config.set_liquid_params(&elements::AddressParams::ELEMENTS);
+        let addresses = AddressGenerator::new(config).generate_addresses(mnemonic, None).unwrap();
+
+        for address in addresses.get_addresses(&AddressType::Liquid).unwrap() {
+            assert!(address.starts_with("ert1"), "unexpected prefix: {}", address);
+        }
+    }
+
+    #[test]
+    fn test_generation_on_all_supported_networks() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        for network in [
+            bitcoin::Network::Bitcoin,
+            bitcoin::Network::Testnet,
+            bitcoin::Network::Signet,
+            bitcoin::Network::Regtest,
+        ] {
+            let mut config = UbaConfig {
+                network,
+                ..Default::default()
+            };
+            config.set_address_type_enabled(AddressType::LightningOffer, true);
+            let addresses = AddressGenerator::new(config)
+                .generate_addresses(mnemonic, None)
+                .unwrap_or_else(|e| panic!("Generation on {} should succeed: {}", network, e));
+
+            assert!(addresses.get_addresses(&AddressType::Liquid).is_some());
+            assert!(addresses.get_addresses(&AddressType::LightningOffer).is_some());
         }
     }
 
@@ -474,6 +1482,109 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_lightning_offer_generation() {
+        use lightning::offers::offer::Offer;
+
+        let mut config = UbaConfig::default();
+        config.set_address_type_enabled(AddressType::LightningOffer, true);
+        let generator = AddressGenerator::new(config);
+
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let addresses = generator.generate_addresses(mnemonic, None).unwrap();
+
+        let offers = addresses.get_addresses(&AddressType::LightningOffer).expect("Lightning offers should exist");
+        let node_ids = addresses.get_addresses(&AddressType::Lightning).expect("Lightning addresses should exist");
+        assert_eq!(offers.len(), 1);
+
+        let encoded = &offers[0];
+        assert!(encoded.starts_with("lno1"), "BOLT12 offer should start with 'lno1', got: {}", encoded);
+
+        // The offer must decode and be signed by the matching node key
+        let offer = Offer::from_str(encoded).expect("BOLT12 offer should decode");
+        assert_eq!(hex::encode(offer.signing_pubkey().serialize()), node_ids[0]);
+        assert_eq!(offer.to_string(), *encoded);
+
+        // Offers are deterministic
+        let again = generator.generate_addresses(mnemonic, None).unwrap();
+        assert_eq!(again.get_addresses(&AddressType::LightningOffer), Some(offers));
+    }
+
+    #[test]
+    fn test_lightning_offers_are_opt_in() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        // Not generated by default
+        let generator = AddressGenerator::new(UbaConfig::default());
+        let addresses = generator.generate_addresses(mnemonic, None).unwrap();
+        assert!(!addresses.addresses.contains_key(&AddressType::LightningOffer));
+        assert!(addresses.addresses.contains_key(&AddressType::Lightning));
+
+        let mut config = UbaConfig::default();
+        config.set_address_type_enabled(AddressType::LightningOffer, true);
+        let addresses = AddressGenerator::new(config).generate_addresses(mnemonic, None).unwrap();
+        assert!(addresses.addresses.contains_key(&AddressType::LightningOffer));
+    }
+
+    #[test]
+    fn test_sign_then_verify_challenge() {
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let challenge = b"prove you control this UBA";
+        let signature = sign_challenge(seed, challenge).unwrap();
+
+        let author = identity_public_key(seed).unwrap();
+        assert!(verify_challenge(&author, challenge, &signature));
+
+        let npub = encode_npub(&XOnlyPublicKey::from_str(&author).unwrap()).unwrap();
+        assert_eq!(decode_npub(&npub).unwrap().to_string(), author);
+        assert!(verify_challenge(&npub, challenge, &signature));
+
+        // Any other challenge fails
+        assert!(!verify_challenge(&author, b"another challenge", &signature));
+    }
+
+    #[test]
+    fn test_verify_challenge_rejects_wrong_key() {
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let other_seed = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        let challenge = b"prove you control this UBA";
+        let signature = sign_challenge(seed, challenge).unwrap();
+
+        let other_author = identity_public_key(other_seed).unwrap();
+        assert!(!verify_challenge(&other_author, challenge, &signature));
+        assert!(!verify_challenge("not-a-key", challenge, &signature));
+
+        // A passphrase gives a different identity
+        let mut config = UbaConfig::default();
+        config.set_bip39_passphrase("TREZOR".to_string());
+        let signature = sign_challenge_with_config(seed, challenge, &config).unwrap();
+        let author = identity_public_key_with_config(seed, &config).unwrap();
+        assert!(verify_challenge(&author, challenge, &signature));
+        assert!(!verify_challenge(&identity_public_key(seed).unwrap(), challenge, &signature));
+    }
+
+    #[test]
+    fn test_encode_npub_matches_nip19() {
+        // NIP-19 test vector
+        let public_key = XOnlyPublicKey::from_str(
+            "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d",
+        )
+        .unwrap();
+        assert_eq!(
+            encode_npub(&public_key).unwrap(),
+            "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6"
+        );
+        assert!(is_npub("npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6"));
+
+        // Bad checksum, wrong hrp, bech32m checksum
+        assert!(!is_npub("npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w7"));
+        let nsec = bech32::encode::<Bech32>(Hrp::parse_unchecked("nsec"), &public_key.serialize())
+            .unwrap();
+        assert!(!is_npub(&nsec));
+        let bech32m = bech32::encode::<bech32::Bech32m>(NPUB_HRP, &public_key.serialize()).unwrap();
+        assert!(!is_npub(&bech32m));
+    }
+
     #[test]
     fn test_nostr_address_generation() {
         let config = UbaConfig::default();
@@ -502,6 +1613,360 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_bip39_passphrase() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        let default_addresses = AddressGenerator::new(UbaConfig::default())
+            .generate_addresses(mnemonic, None)
+            .unwrap();
+
+        // An explicit empty passphrase must reproduce the default addresses
+        let mut empty_config = UbaConfig::default();
+        empty_config.set_bip39_passphrase(String::new());
+        let empty_addresses = AddressGenerator::new(empty_config)
+            .generate_addresses(mnemonic, None)
+            .unwrap();
+        assert_eq!(
+            default_addresses.get_addresses(&AddressType::P2WPKH),
+            empty_addresses.get_addresses(&AddressType::P2WPKH)
+        );
+
+        // BIP84 test vector for the "abandon ... about" mnemonic without passphrase
+        assert_eq!(
+            default_addresses.get_addresses(&AddressType::P2WPKH).unwrap()[0],
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+
+        // A non-empty passphrase must produce different addresses
+        let mut passphrase_config = UbaConfig::default();
+        passphrase_config.set_bip39_passphrase("TREZOR".to_string());
+        let passphrase_addresses = AddressGenerator::new(passphrase_config)
+            .generate_addresses(mnemonic, None)
+            .unwrap();
+        assert_ne!(
+            default_addresses.get_addresses(&AddressType::P2WPKH),
+            passphrase_addresses.get_addresses(&AddressType::P2WPKH)
+        );
+    }
+
+    #[test]
+    fn test_huge_address_count_is_rejected() {
+        let mut config = UbaConfig::default();
+        config.set_address_count(AddressType::P2PKH, usize::MAX);
+        let generator = AddressGenerator::new(config);
+
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let result = generator.generate_addresses(mnemonic, None);
+        assert!(matches!(result, Err(UbaError::Config(_))));
+    }
+
+    #[test]
+    fn test_fixed_timestamp() {
+        let config = UbaConfig {
+            fixed_timestamp: Some(1_700_000_000),
+            ..Default::default()
+        };
+        let generator = AddressGenerator::new(config);
+
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let first = generator.generate_addresses(mnemonic, None).unwrap();
+        let second = generator.generate_addresses(mnemonic, None).unwrap();
+
+        assert_eq!(first.created_at, 1_700_000_000);
+        assert_eq!(
+            serde_json::to_string(&first).unwrap(),
+            serde_json::to_string(&second).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_taproot_address_validation() {
+        let network = bitcoin::Network::Bitcoin;
+
+        // BIP350 test vector: witness v1, 32-byte program, bech32m checksum
+        assert!(validate_taproot_address(
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            network
+        )
+        .is_ok());
+
+        // Same program with a bech32 (v0-style) checksum
+        let result = validate_taproot_address(
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+            network,
+        );
+        assert!(matches!(result, Err(UbaError::AddressGeneration(_))));
+
+        // Valid witness v0 address in the Taproot list
+        let result =
+            validate_taproot_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", network);
+        assert!(matches!(result, Err(UbaError::AddressGeneration(_))));
+
+        // Taproot address for a different network
+        let result = validate_taproot_address(
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+            bitcoin::Network::Testnet,
+        );
+        assert!(matches!(result, Err(UbaError::AddressGeneration(_))));
+    }
+
+    #[test]
+    fn test_taproot_merkle_root_tweak() {
+        use bitcoin::hashes::Hash;
+
+        // BIP341 wallet test vector with a single-leaf script tree
+        let internal_key = PublicKey::from_str(
+            "02187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27",
+        )
+        .unwrap();
+        let merkle_root = TapNodeHash::from_byte_array(
+            hex::decode("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21")
+                .unwrap()
+                .try_into()
+                .unwrap(),
+        );
+
+        let config = UbaConfig {
+            taproot_merkle_root: Some(merkle_root),
+            ..Default::default()
+        };
+        let address = AddressGenerator::new(config)
+            .bitcoin_address(&AddressType::P2TR, &internal_key)
+            .unwrap();
+        assert_eq!(address, "bc1pz37fc4cn9ah8anwm4xqqhvxygjf9rjf2resrw8h8w4tmvcs0863sa2e586");
+
+        // Generated script-path addresses differ from the key-path ones
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let generator = AddressGenerator::new(UbaConfig::default());
+        let script_path = generator.generate_taproot_with_merkle_root(seed, merkle_root).unwrap();
+        let key_path = generator.generate_addresses(seed, None).unwrap();
+        assert_eq!(script_path.addresses.len(), 1);
+        let script_path_address = script_path.first(&AddressType::P2TR).unwrap();
+        assert!(script_path_address.starts_with("bc1p"));
+        assert_ne!(Some(script_path_address), key_path.first(&AddressType::P2TR));
+    }
+
+    #[test]
+    fn test_custom_derivation_paths() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        let mut config = UbaConfig::default();
+        config.disable_all_address_types();
+        config.set_address_type_enabled(AddressType::P2PKH, true);
+        config.set_address_type_enabled(AddressType::P2WPKH, true);
+        let default_addresses = AddressGenerator::new(config.clone())
+            .generate_addresses(mnemonic, None)
+            .unwrap();
+
+        config
+            .set_custom_derivation_path(AddressType::P2PKH, "m/0'/0'")
+            .unwrap();
+        let custom_addresses = AddressGenerator::new(config)
+            .generate_addresses(mnemonic, None)
+            .unwrap();
+
+        // Only the overridden type changes
+        assert_ne!(
+            default_addresses.get_addresses(&AddressType::P2PKH),
+            custom_addresses.get_addresses(&AddressType::P2PKH)
+        );
+        assert_eq!(
+            default_addresses.get_addresses(&AddressType::P2WPKH),
+            custom_addresses.get_addresses(&AddressType::P2WPKH)
+        );
+
+        // Metadata lists the effective paths of the enabled types
+        let paths = custom_addresses
+            .metadata
+            .and_then(|m| m.derivation_paths)
+            .unwrap();
+        assert_eq!(paths, vec!["m/0'/0'".to_string(), "m/84'/0'/0'/0".to_string()]);
+    }
+
+    #[test]
+    fn test_generate_next_page_matches_single_shot() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        let mut config = UbaConfig::default();
+        config.disable_all_address_types();
+        config.set_address_type_enabled(AddressType::P2WPKH, true);
+        config.set_address_type_enabled(AddressType::Liquid, true);
+        let generator = AddressGenerator::new(config.clone());
+
+        let mut cursor = AddressCursor::new();
+        let first = generator.generate_next_page(&mut cursor, mnemonic, 2).unwrap();
+        assert_eq!(cursor.next_index(&AddressType::P2WPKH), 2);
+
+        // The cursor survives a JSON round trip
+        let json = serde_json::to_string(&cursor).unwrap();
+        let mut cursor: AddressCursor = serde_json::from_str(&json).unwrap();
+        let second = generator.generate_next_page(&mut cursor, mnemonic, 2).unwrap();
+        assert_eq!(cursor.next_index(&AddressType::Liquid), 4);
+        assert_eq!(cursor.next_index(&AddressType::P2TR), 0);
+
+        config.set_address_count(AddressType::P2WPKH, 4);
+        config.set_address_count(AddressType::Liquid, 4);
+        let all = AddressGenerator::new(config).generate_addresses(mnemonic, None).unwrap();
+
+        for address_type in [AddressType::P2WPKH, AddressType::Liquid] {
+            let mut paged = first.get_addresses(&address_type).unwrap().clone();
+            paged.extend(second.get_addresses(&address_type).unwrap().iter().cloned());
+            assert_eq!(&paged, all.get_addresses(&address_type).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_master_fingerprint_in_metadata() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        let mut config = UbaConfig::default();
+        config.disable_all_address_types();
+        config.set_address_type_enabled(AddressType::P2WPKH, true);
+        let addresses = AddressGenerator::new(config.clone())
+            .generate_addresses(mnemonic, None)
+            .unwrap();
+        assert_eq!(addresses.metadata.unwrap().fingerprint, None);
+
+        config.include_fingerprint = true;
+        let addresses = AddressGenerator::new(config)
+            .generate_addresses(mnemonic, None)
+            .unwrap();
+        assert_eq!(addresses.metadata.unwrap().fingerprint.as_deref(), Some("73c5da0a"));
+    }
+
+    #[test]
+    fn test_invalid_custom_derivation_path() {
+        let mut config = UbaConfig::default();
+        let result = config.set_custom_derivation_path(AddressType::P2TR, "not/a/path");
+        assert!(matches!(result, Err(UbaError::Config(_))));
+        assert!(config.custom_derivation_paths.is_empty());
+
+        // Paths inserted directly are still validated before generation
+        config
+            .custom_derivation_paths
+            .insert(AddressType::P2TR, "m/86'/x".to_string());
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let result = AddressGenerator::new(config).generate_addresses(mnemonic, None);
+        assert!(matches!(result, Err(UbaError::Config(_))));
+    }
+
+    #[test]
+    fn test_from_xpub_matches_seed_generation() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let config = UbaConfig::default();
+        let generator = AddressGenerator::new(config.clone());
+        let from_seed = generator.generate_addresses(mnemonic, None).unwrap();
+
+        let master_key = generator.derive_master_key(mnemonic).unwrap();
+        for (address_type, account_path) in [
+            (AddressType::P2PKH, "m/44'/0'/0'"),
+            (AddressType::P2SHWPKH, "m/49'/0'/0'"),
+            (AddressType::P2WPKH, "m/84'/0'/0'"),
+            (AddressType::P2TR, "m/86'/0'/0'"),
+        ] {
+            let account_key = master_key
+                .derive_priv(&generator.secp, &DerivationPath::from_str(account_path).unwrap())
+                .unwrap();
+            let xpub = Xpub::from_priv(&generator.secp, &account_key).to_string();
+
+            let watch_only =
+                AddressGenerator::from_xpub(&xpub, address_type.clone(), config.clone()).unwrap();
+            assert_eq!(
+                watch_only.get_addresses(&address_type),
+                from_seed.get_addresses(&address_type)
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_xpub_rejects_hardened_derivation() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let generator = AddressGenerator::new(UbaConfig::default());
+        let master_key = generator.derive_master_key(mnemonic).unwrap();
+        let account_key = master_key
+            .derive_priv(&generator.secp, &DerivationPath::from_str("m/84'/0'/0'").unwrap())
+            .unwrap();
+        let xpub = Xpub::from_priv(&generator.secp, &account_key).to_string();
+
+        let mut config = UbaConfig::default();
+        config
+            .set_custom_derivation_path(AddressType::P2WPKH, "m/84'/0'/0'/0'")
+            .unwrap();
+        let result = AddressGenerator::from_xpub(&xpub, AddressType::P2WPKH, config);
+        assert!(matches!(result, Err(UbaError::KeyDerivation(_))));
+
+        // Lightning node keys are not L1 addresses and cannot be watch-only
+        let result =
+            AddressGenerator::from_xpub(&xpub, AddressType::Lightning, UbaConfig::default());
+        assert!(matches!(result, Err(UbaError::Config(_))));
+    }
+
+    #[test]
+    fn test_validate_external_addresses() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let generator = AddressGenerator::new(UbaConfig::default());
+        let mut addresses = generator.generate_addresses(mnemonic, None).unwrap();
+        assert!(generator.validate_addresses(&addresses).is_ok());
+
+        // A testnet address and a P2PKH address filed under P2WPKH
+        addresses.add_address(
+            AddressType::P2WPKH,
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx".to_string(),
+        );
+        addresses.add_address(
+            AddressType::P2WPKH,
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2".to_string(),
+        );
+        addresses.add_address(AddressType::P2TR, "not-an-address".to_string());
+
+        match generator.validate_addresses(&addresses) {
+            Err(UbaError::UpdateValidation(message)) => {
+                assert!(message.contains("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"));
+                assert!(message.contains("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2"));
+                assert!(message.contains("not-an-address"));
+            }
+            other => panic!("expected UpdateValidation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_validate_lightning_and_nostr_formats() {
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let generator = AddressGenerator::new(UbaConfig::default());
+        let addresses = generator.generate_addresses(mnemonic, None).unwrap();
+        let node_id = addresses.first(&AddressType::Lightning).unwrap().to_string();
+
+        // Truncated node ids and npubs with a bad checksum are always rejected
+        let mut malformed = addresses.clone();
+        malformed.add_address(AddressType::Lightning, node_id[..64].to_string());
+        malformed.add_address(AddressType::Nostr, "npub1notanpub".to_string());
+        match generator.validate_addresses(&malformed) {
+            Err(UbaError::UpdateValidation(message)) => {
+                assert!(message.contains(&node_id[..64]));
+                assert!(message.contains("npub1notanpub"));
+            }
+            other => panic!("expected UpdateValidation, got {:?}", other),
+        }
+
+        // An LNURL is only rejected in strict mode
+        let mut exotic = addresses.clone();
+        exotic.add_address(
+            AddressType::Lightning,
+            "LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS".to_string(),
+        );
+        assert!(generator.validate_addresses(&exotic).is_ok());
+
+        let mut config = UbaConfig::default();
+        config.strict_address_formats = true;
+        let strict = AddressGenerator::new(config);
+        assert!(strict.validate_addresses(&addresses).is_ok());
+        assert!(matches!(
+            strict.validate_addresses(&exotic),
+            Err(UbaError::UpdateValidation(_))
+        ));
+    }
+
     #[test]
     fn test_invalid_seed() {
         let config = UbaConfig::default();
@@ -511,6 +1976,177 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_non_english_mnemonic() {
+        // First Japanese vector of the bip32JP BIP39 test suite
+        // (https://github.com/bip32JP/bip32JP.github.io/blob/master/test_JP_BIP39.json)
+        let japanese = "あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あいこくしん　あおぞら";
+        let master_xprv = "xprv9s21ZrQH143K258jAiWPAM6JYT9hLA91MV3AZUKfxmLZJCjCHeSjBvMbDy8C1mJ2FL5ytExyS97FAe6pQ6SD5Jt9SwHaLorA8i5Eojokfo1";
+        let mut config = UbaConfig::default();
+        config.set_bip39_passphrase("㍍ガバヴァぱばぐゞちぢ十人十色".to_string());
+
+        // Detected from the words, and named explicitly
+        let generator = AddressGenerator::new(config.clone());
+        assert_eq!(generator.derive_master_key(japanese).unwrap().to_string(), master_xprv);
+        config.mnemonic_language = Some(Language::Japanese);
+        let generator = AddressGenerator::new(config);
+        assert_eq!(generator.derive_master_key(japanese).unwrap().to_string(), master_xprv);
+
+        // Mnemonics built from entropy use the configured language: all-zero
+        // entropy in Spanish
+        let spanish = "ábaco ábaco ábaco ábaco ábaco ábaco ábaco ábaco ábaco ábaco ábaco abierto";
+        let mut config = UbaConfig::default();
+        config.mnemonic_language = Some(Language::Spanish);
+        let generator = AddressGenerator::new(config);
+        let from_entropy = generator.generate_from_entropy(&[0u8; 16], None).unwrap();
+        let detected = AddressGenerator::new(UbaConfig::default())
+            .generate_addresses(spanish, None)
+            .unwrap();
+        assert_eq!(from_entropy.addresses, detected.addresses);
+        assert!(validation::validate_seed(spanish).is_ok());
+
+        // The wrong language does not parse
+        let mut config = UbaConfig::default();
+        config.mnemonic_language = Some(Language::English);
+        let result = AddressGenerator::new(config).generate_addresses(spanish, None);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_generate_from_entropy_matches_mnemonic() {
+        let generator = AddressGenerator::new(UbaConfig::default());
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        // All-zero 128-bit entropy is the "abandon ... about" mnemonic
+        let from_entropy = generator.generate_from_entropy(&[0u8; 16], None).unwrap();
+        let from_mnemonic = generator.generate_addresses(mnemonic, None).unwrap();
+        for address_type in [AddressType::P2PKH, AddressType::P2WPKH, AddressType::P2TR] {
+            assert_eq!(
+                from_entropy.get_addresses(&address_type),
+                from_mnemonic.get_addresses(&address_type)
+            );
+        }
+
+        for len in [20, 24, 28, 32] {
+            assert!(generator.generate_from_entropy(&vec![7u8; len], None).is_ok());
+        }
+        for len in [0, 15, 17, 31, 33, 64] {
+            assert!(matches!(
+                generator.generate_from_entropy(&vec![7u8; len], None),
+                Err(UbaError::InvalidSeed(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_hex_key_network_prefix() {
+        let key = "0101010101010101010101010101010101010101010101010101010101010101";
+        let mut config = UbaConfig::default();
+        config.disable_all_address_types();
+        config.set_address_type_enabled(AddressType::P2WPKH, true);
+
+        // A bare key is accepted under any network and yields that network's addresses
+        let mainnet = AddressGenerator::new(config.clone()).generate_addresses(key, None).unwrap();
+        let mut testnet_config = config.clone();
+        testnet_config.network = bitcoin::Network::Testnet;
+        let testnet = AddressGenerator::new(testnet_config.clone())
+            .generate_addresses(key, None)
+            .unwrap();
+        assert!(mainnet.get_addresses(&AddressType::P2WPKH).unwrap()[0].starts_with("bc1"));
+        assert!(testnet.get_addresses(&AddressType::P2WPKH).unwrap()[0].starts_with("tb1"));
+
+        // A matching prefix changes nothing, a mismatched one is rejected
+        let prefixed = AddressGenerator::new(config.clone())
+            .generate_addresses(&format!("mainnet:{}", key), None)
+            .unwrap();
+        assert_eq!(
+            prefixed.get_addresses(&AddressType::P2WPKH),
+            mainnet.get_addresses(&AddressType::P2WPKH)
+        );
+        let mismatched = AddressGenerator::new(config.clone())
+            .generate_addresses(&format!("testnet:{}", key), None);
+        assert!(matches!(mismatched, Err(UbaError::Config(_))));
+
+        // Strict mode requires the prefix
+        let mut strict = testnet_config;
+        strict.strict_network = true;
+        let generator = AddressGenerator::new(strict);
+        assert!(matches!(
+            generator.generate_addresses(key, None),
+            Err(UbaError::InvalidSeed(_))
+        ));
+        assert_eq!(
+            generator
+                .generate_addresses(&format!("testnet:{}", key), None)
+                .unwrap()
+                .get_addresses(&AddressType::P2WPKH),
+            testnet.get_addresses(&AddressType::P2WPKH)
+        );
+    }
+
+    #[test]
+    fn test_address_at_matches_generated_range() {
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut config = UbaConfig::default();
+        config.enable_all_address_types();
+        config.set_all_counts(5);
+        let generator = AddressGenerator::new(config);
+        let generated = generator.generate_addresses(seed, None).unwrap();
+
+        for address_type in AddressType::ALL {
+            for index in [0, 4] {
+                assert_eq!(
+                    generator.address_at(seed, address_type.clone(), index).unwrap(),
+                    generated.get_address(&address_type, index as usize).unwrap(),
+                    "{:?}[{}]",
+                    address_type,
+                    index
+                );
+            }
+        }
+
+        // Indices beyond the normal BIP32 range are rejected
+        let result = generator.address_at(seed, AddressType::P2WPKH, 1 << 31);
+        assert!(matches!(result, Err(UbaError::Config(_))));
+    }
+
+    #[test]
+    fn test_start_index_shifts_every_type() {
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut config = UbaConfig::default();
+        config.enable_all_address_types();
+        config.set_all_counts(7);
+        let full = AddressGenerator::new(config.clone()).generate_addresses(seed, None).unwrap();
+
+        config.set_all_counts(2);
+        config.start_index = 5;
+        let shifted = AddressGenerator::new(config.clone()).generate_addresses(seed, None).unwrap();
+        let again = AddressGenerator::new(config).generate_addresses(seed, None).unwrap();
+
+        for address_type in AddressType::ALL {
+            let expected = &full.get_addresses(&address_type).unwrap()[5..7];
+            assert_eq!(shifted.get_addresses(&address_type).unwrap(), expected, "{:?}", address_type);
+        }
+        assert_eq!(shifted.addresses, again.addresses);
+    }
+
+    #[test]
+    fn test_hex_seed_with_wrong_length_is_explained() {
+        let generator = AddressGenerator::new(UbaConfig::default());
+        let key = "0101010101010101010101010101010101010101010101010101010101010101";
+
+        for seed in [&key[..63], &key[..62], &format!("{}0", key)[..]] {
+            match generator.generate_addresses(seed, None) {
+                Err(UbaError::InvalidSeed(message)) => {
+                    assert!(message.contains(&format!("has {} characters", seed.len())));
+                    assert!(message.contains("expected 64"));
+                }
+                other => panic!("expected InvalidSeed for {}, got {:?}", seed, other),
+            }
+        }
+        assert!(generator.generate_addresses(key, None).is_ok());
+    }
+
     #[test]
     fn test_deterministic_address_generation() {
         let config = UbaConfig::default();
@@ -599,7 +2235,7 @@ mod tests {
 
         // Only Bitcoin L1 types should be present
         assert!(addresses.addresses.contains_key(&AddressType::P2PKH));
-        assert!(addresses.addresses.contains_key(&AddressType::P2SH));
+        assert!(addresses.addresses.contains_key(&AddressType::P2SHWPKH));
         assert!(addresses.addresses.contains_key(&AddressType::P2WPKH));
         assert!(addresses.addresses.contains_key(&AddressType::P2TR));
         
@@ -614,7 +2250,7 @@ mod tests {
         let mut config = UbaConfig::default();
         // Disable all except P2WPKH and Lightning
         config.set_address_type_enabled(AddressType::P2PKH, false);
-        config.set_address_type_enabled(AddressType::P2SH, false);
+        config.set_address_type_enabled(AddressType::P2SHWPKH, false);
         config.set_address_type_enabled(AddressType::P2TR, false);
         config.set_address_type_enabled(AddressType::Liquid, false);
         config.set_address_type_enabled(AddressType::Nostr, false);
@@ -630,7 +2266,7 @@ mod tests {
         
         // Others should not be present
         assert!(!addresses.addresses.contains_key(&AddressType::P2PKH));
-        assert!(!addresses.addresses.contains_key(&AddressType::P2SH));
+        assert!(!addresses.addresses.contains_key(&AddressType::P2SHWPKH));
         assert!(!addresses.addresses.contains_key(&AddressType::P2TR));
         assert!(!addresses.addresses.contains_key(&AddressType::Liquid));
         assert!(!addresses.addresses.contains_key(&AddressType::Nostr));
@@ -660,7 +2296,7 @@ mod tests {
         // Disable Lightning but keep P2WPKH
         config.set_address_type_enabled(AddressType::Lightning, false);
         config.set_address_type_enabled(AddressType::P2PKH, false);
-        config.set_address_type_enabled(AddressType::P2SH, false);
+        config.set_address_type_enabled(AddressType::P2SHWPKH, false);
         config.set_address_type_enabled(AddressType::P2TR, false);
         config.set_address_type_enabled(AddressType::Liquid, false);
         config.set_address_type_enabled(AddressType::Nostr, false);
@@ -0,0 +1,328 @@
+//! Local filesystem cache for retrieved UBAs
+//!
+//! Retrieved address collections are stored as one JSON file per Nostr event ID.
+//! Entries expire after a configurable TTL, writes go through a temporary file and
+//! an atomic rename so a crash never leaves a half-written entry behind, and a
+//! `CacheMode` lets callers refresh or bypass the cache explicitly.
+//!
+//! **Note**: entries hold the decrypted address collection. They are created
+//! readable by the owner only on unix; when retrieving encrypted UBAs, keep the
+//! cache directory as private as the encryption key. Each entry records a
+//! fingerprint of the key it was decrypted with and is only served to callers
+//! holding the same key.
+
+use crate::error::Result;
+use crate::types::BitcoinAddresses;
+use crate::parse::validate_nostr_id;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default time-to-live for cache entries (1 hour)
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// How a cached retrieval should use the cache
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheMode {
+    /// Serve fresh entries from the cache, query relays on a miss and write back
+    #[default]
+    Default,
+    /// Always query relays and overwrite the cached entry
+    Refresh,
+    /// Always query relays and leave the cache untouched
+    Bypass,
+}
+
+/// A cache entry as stored on disk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    /// Unix timestamp (seconds) when the entry was written
+    cached_at: u64,
+    /// The cached address collection
+    addresses: BitcoinAddresses,
+    /// Fingerprint of the encryption key the collection was decrypted with, if any
+    #[serde(default)]
+    key_fingerprint: Option<String>,
+}
+
+/// Filesystem cache of retrieved address collections keyed by Nostr event ID
+#[derive(Debug, Clone)]
+pub struct UbaCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl UbaCache {
+    /// Create a cache rooted at `dir` using the default TTL
+    ///
+    /// The directory is created lazily on the first write.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self::with_ttl(dir, DEFAULT_CACHE_TTL)
+    }
+
+    /// Create a cache rooted at `dir` with a custom TTL
+    pub fn with_ttl(dir: impl AsRef<Path>, ttl: Duration) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            ttl,
+        }
+    }
+
+    /// Get the cache directory
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Get the entry time-to-live
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Look up a fresh entry for the given Nostr event ID
+    ///
+    /// Missing, expired and corrupted entries are reported as a miss, and so are
+    /// entries stored under a different `encryption_key`. Other IO errors while
+    /// reading the entry are returned as errors.
+    pub fn get(
+        &self,
+        nostr_id: &str,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<Option<BitcoinAddresses>> {
+        let path = self.entry_path(nostr_id)?;
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let entry: CacheEntry = match serde_json::from_str(&data) {
+            Ok(entry) => entry,
+            // A corrupted entry is treated as a miss and will be overwritten
+            Err(_) => return Ok(None),
+        };
+
+        let age = now_secs()?.saturating_sub(entry.cached_at);
+        if age >= self.ttl.as_secs() {
+            return Ok(None);
+        }
+
+        // Never hand out data decrypted with a key the caller does not hold
+        if entry.key_fingerprint != encryption_key.map(key_fingerprint) {
+            return Ok(None);
+        }
+
+        Ok(Some(entry.addresses))
+    }
+
+    /// Store an entry for the given Nostr event ID
+    ///
+    /// `encryption_key` is the key the addresses were decrypted with; only its
+    /// fingerprint is stored. The entry is written to a temporary file and
+    /// atomically renamed into place.
+    pub fn put(
+        &self,
+        nostr_id: &str,
+        addresses: &BitcoinAddresses,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<()> {
+        let path = self.entry_path(nostr_id)?;
+        fs::create_dir_all(&self.dir)?;
+
+        let entry = CacheEntry {
+            cached_at: now_secs()?,
+            addresses: addresses.clone(),
+            key_fingerprint: encryption_key.map(key_fingerprint),
+        };
+        let data = serde_json::to_string(&entry)?;
+
+        let tmp_path = self
+            .dir
+            .join(format!("{}.json.tmp-{}", nostr_id, uuid::Uuid::new_v4()));
+        if let Err(e) = write_private(&tmp_path, data.as_bytes()) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e.into());
+        }
+        if let Err(e) = fs::rename(&tmp_path, path) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    /// Remove the entry for the given Nostr event ID, if any
+    pub fn invalidate(&self, nostr_id: &str) -> Result<()> {
+        match fs::remove_file(self.entry_path(nostr_id)?) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Remove all cache entries
+    pub fn clear(&self) -> Result<()> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+                fs::remove_file(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the entry file of a Nostr event ID, which must be 64 hex characters so
+    /// that it cannot point outside the cache directory
+    fn entry_path(&self, nostr_id: &str) -> Result<PathBuf> {
+        validate_nostr_id(nostr_id)?;
+        Ok(self.dir.join(format!("{}.json", nostr_id)))
+    }
+}
+
+/// Fingerprint an encryption key so entries can be matched to it without
+/// storing the key itself
+fn key_fingerprint(key: &[u8; 32]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"uba-cache-key-fingerprint");
+    hasher.update(key);
+    hex::encode(hasher.finalize())
+}
+
+fn now_secs() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Write `data` to a new file that only its owner can read on unix
+fn write_private(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AddressType;
+
+    const NOSTR_ID: &str = "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+
+    fn temp_cache_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("uba-cache-test-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn sample_addresses() -> BitcoinAddresses {
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+        addresses
+    }
+
+    #[test]
+    fn test_cache_roundtrip() {
+        let dir = temp_cache_dir();
+        let cache = UbaCache::new(&dir);
+
+        assert!(cache.get(NOSTR_ID, None).unwrap().is_none());
+
+        cache.put(NOSTR_ID, &sample_addresses(), None).unwrap();
+        let cached = cache.get(NOSTR_ID, None).unwrap().expect("Entry should be cached");
+        assert_eq!(cached.addresses, sample_addresses().addresses);
+
+        cache.invalidate(NOSTR_ID).unwrap();
+        assert!(cache.get(NOSTR_ID, None).unwrap().is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_expired_entry_is_miss() {
+        let dir = temp_cache_dir();
+        let cache = UbaCache::with_ttl(&dir, Duration::from_secs(0));
+
+        cache.put(NOSTR_ID, &sample_addresses(), None).unwrap();
+        assert!(cache.get(NOSTR_ID, None).unwrap().is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_corrupted_entry_is_miss() {
+        let dir = temp_cache_dir();
+        let cache = UbaCache::new(&dir);
+
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!("{}.json", NOSTR_ID)), "{not json").unwrap();
+        assert!(cache.get(NOSTR_ID, None).unwrap().is_none());
+
+        // Clearing removes entries and leaves no temporary files behind
+        cache.put(NOSTR_ID, &sample_addresses(), None).unwrap();
+        cache.clear().unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_entry_is_bound_to_key() {
+        let dir = temp_cache_dir();
+        let cache = UbaCache::new(&dir);
+        let key = [7u8; 32];
+
+        cache.put(NOSTR_ID, &sample_addresses(), Some(&key)).unwrap();
+        assert!(cache.get(NOSTR_ID, Some(&key)).unwrap().is_some());
+        assert!(cache.get(NOSTR_ID, Some(&[8u8; 32])).unwrap().is_none());
+        assert!(cache.get(NOSTR_ID, None).unwrap().is_none());
+
+        // The key itself never reaches the disk
+        let data = fs::read_to_string(dir.join(format!("{}.json", NOSTR_ID))).unwrap();
+        assert!(!data.contains(&hex::encode(key)));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache_rejects_invalid_ids() {
+        let dir = temp_cache_dir();
+        let cache = UbaCache::new(&dir);
+
+        for nostr_id in ["../escape", "", &NOSTR_ID[..63], &NOSTR_ID.replace('a', "g")] {
+            assert!(cache.get(nostr_id, None).is_err());
+            assert!(cache.put(nostr_id, &sample_addresses(), None).is_err());
+            assert!(cache.invalidate(nostr_id).is_err());
+        }
+        assert!(!dir.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cache_entries_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_cache_dir();
+        let cache = UbaCache::new(&dir);
+
+        cache.put(NOSTR_ID, &sample_addresses(), None).unwrap();
+        let mode = fs::metadata(dir.join(format!("{}.json", NOSTR_ID)))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
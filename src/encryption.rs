@@ -10,22 +10,207 @@
 //!
 //! Currently supports:
 //! - Basic ChaCha20Poly1305 encryption/decryption
-//! - Key derivation from passphrases
+//! - Key derivation from passphrases (HKDF, or Argon2id with a configurable work factor)
+//! - NIP-04 encryption to a recipient public key, readable by standard Nostr clients
+//! - Selective metadata encryption (keeping addresses public)
 //! 
 //! Future roadmap may include:
-//! - NIP-04 encryption for Nostr compatibility (if community demand exists)
 //! - NIP-17 Gift Wrap encryption for advanced privacy use cases
-//! - Selective metadata encryption (keeping addresses public)
 
 use crate::{Result, UbaError};
+use argon2::{Algorithm, Argon2, Params, Version};
 use base64::{engine::general_purpose, Engine as _};
 use chacha20poly1305::{
     aead::{Aead, KeyInit, OsRng},
     ChaCha20Poly1305, Key, Nonce,
 };
 use hkdf::Hkdf;
+#[cfg(feature = "nostr")]
+use nostr::{nips::nip04, PublicKey, SecretKey};
 use rand::RngCore;
 use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use zeroize::Zeroizing;
+
+/// A 32-byte encryption key that is zeroized when dropped
+///
+/// Equality is checked in constant time and `Debug` output is redacted. The key
+/// dereferences to its bytes so it can be passed wherever `&[u8; 32]` is expected;
+/// use `reveal` to make access explicit.
+#[derive(Clone)]
+pub struct EncryptionKey(Zeroizing<[u8; 32]>);
+
+impl EncryptionKey {
+    /// Wrap raw key bytes
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(Zeroizing::new(key))
+    }
+
+    /// Get the raw key bytes
+    pub fn reveal(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Parse a key from 64 hex characters
+    ///
+    /// Surrounding whitespace (such as a trailing newline from a pasted key) and an
+    /// optional `0x` prefix are ignored.
+    pub fn from_hex(key_hex: &str) -> Result<Self> {
+        let trimmed = key_hex.trim();
+        let key_hex = trimmed
+            .strip_prefix("0x")
+            .or_else(|| trimmed.strip_prefix("0X"))
+            .unwrap_or(trimmed);
+
+        if key_hex.len() != 64 {
+            return Err(UbaError::InvalidEncryptionKey(format!(
+                "Encryption key must be exactly 64 hex characters (32 bytes), got {}",
+                key_hex.len()
+            )));
+        }
+
+        // Don't echo the key material back in the error
+        if !key_hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(UbaError::InvalidEncryptionKey(
+                "Encryption key contains non-hex characters".to_string(),
+            ));
+        }
+
+        let mut key = Zeroizing::new([0u8; 32]);
+        hex::decode_to_slice(key_hex, key.as_mut())
+            .map_err(|e| UbaError::InvalidEncryptionKey(format!("Invalid hex string: {}", e)))?;
+        Ok(Self(key))
+    }
+
+    /// Encode the key as hex
+    ///
+    /// **Note**: the returned `String` is an ordinary heap allocation and is not
+    /// zeroized on drop. Only use it to show or store the key deliberately.
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.reveal())
+    }
+}
+
+impl From<[u8; 32]> for EncryptionKey {
+    fn from(key: [u8; 32]) -> Self {
+        Self::new(key)
+    }
+}
+
+impl std::ops::Deref for EncryptionKey {
+    type Target = [u8; 32];
+
+    fn deref(&self) -> &Self::Target {
+        self.reveal()
+    }
+}
+
+impl PartialEq for EncryptionKey {
+    fn eq(&self, other: &Self) -> bool {
+        constant_time_eq(self.reveal(), other.reveal())
+    }
+}
+
+impl Eq for EncryptionKey {}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(<redacted>)")
+    }
+}
+
+/// Compare two byte slices in constant time
+///
+/// The comparison time depends only on the lengths, never on where the inputs
+/// differ, so it is safe for comparing keys and authentication tags.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && bool::from(a.ct_eq(b))
+}
+
+/// Encryption scheme used for the content of a UBA event
+///
+/// Encrypted events carry a `["scheme", <name>]` tag so readers know how to decrypt
+/// them. Events without the tag use ChaCha20Poly1305.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EncryptionScheme {
+    /// ChaCha20Poly1305 with a shared 32-byte key (the default)
+    #[default]
+    ChaCha20Poly1305,
+    /// NIP-04 (ECDH + AES-256-CBC) between the publishing keys and a recipient
+    /// public key, as used by Nostr direct messages
+    Nip04,
+    /// ChaCha20Poly1305 applied to the metadata block only; addresses stay public
+    ChaCha20Poly1305MetadataOnly,
+}
+
+impl EncryptionScheme {
+    /// Get the value used in the event's `scheme` tag
+    pub fn tag_value(&self) -> &'static str {
+        match self {
+            EncryptionScheme::ChaCha20Poly1305 => "chacha20poly1305",
+            EncryptionScheme::Nip04 => "nip04",
+            EncryptionScheme::ChaCha20Poly1305MetadataOnly => "chacha20poly1305-metadata",
+        }
+    }
+
+    /// Parse the value of an event's `scheme` tag
+    pub fn from_tag_value(value: &str) -> Option<Self> {
+        match value {
+            "chacha20poly1305" => Some(EncryptionScheme::ChaCha20Poly1305),
+            "nip04" => Some(EncryptionScheme::Nip04),
+            "chacha20poly1305-metadata" => Some(EncryptionScheme::ChaCha20Poly1305MetadataOnly),
+            _ => None,
+        }
+    }
+}
+
+/// Content field holding the encrypted metadata block in metadata-only mode
+pub(crate) const ENCRYPTED_METADATA_FIELD: &str = "encrypted_metadata";
+
+/// Detect how the content of a UBA event is encrypted
+///
+/// `tags` are the event's tags as string lists, as in `RawUbaEvent::tags`. A known
+/// `scheme` tag wins; otherwise the `encrypted` tag and finally the shape of the
+/// content are used, so untagged blobs from other tools can be recognised too.
+/// Returns None for plaintext content.
+pub fn detect_scheme(content: &str, tags: &[Vec<String>]) -> Option<EncryptionScheme> {
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|tag| tag.len() >= 2 && tag[0] == name)
+            .map(|tag| tag[1].as_str())
+    };
+
+    if let Some(scheme) = tag("scheme").and_then(EncryptionScheme::from_tag_value) {
+        return Some(scheme);
+    }
+    match tag("encrypted") {
+        Some("metadata") => return Some(EncryptionScheme::ChaCha20Poly1305MetadataOnly),
+        Some("true") => return Some(EncryptionScheme::ChaCha20Poly1305),
+        _ => {}
+    }
+
+    let content = content.trim();
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(content) {
+        return value
+            .get(ENCRYPTED_METADATA_FIELD)
+            .is_some()
+            .then_some(EncryptionScheme::ChaCha20Poly1305MetadataOnly);
+    }
+
+    let is_base64 = |data: &str| general_purpose::STANDARD.decode(data).ok();
+    if let Some((ciphertext, iv)) = content.split_once("?iv=") {
+        // NIP-04: base64 AES-CBC ciphertext followed by a base64 16-byte IV
+        let is_nip04 = is_base64(ciphertext)
+            .is_some_and(|bytes| !bytes.is_empty() && bytes.len() % 16 == 0)
+            && is_base64(iv).is_some_and(|iv| iv.len() == 16);
+        return is_nip04.then_some(EncryptionScheme::Nip04);
+    }
+
+    // ChaCha20Poly1305: base64 of a 12-byte nonce, ciphertext and 16-byte tag
+    is_base64(content)
+        .is_some_and(|bytes| bytes.len() >= 12 + 16)
+        .then_some(EncryptionScheme::ChaCha20Poly1305)
+}
 
 /// Encryption context for UBA operations
 pub struct UbaEncryption {
@@ -102,6 +287,13 @@ impl UbaEncryption {
     }
 }
 
+/// Default salt for passphrase key derivation, versioned so a future scheme can
+/// change it without silently producing different keys for existing passphrases
+pub const DEFAULT_KEY_SALT_V1: &[u8] = b"UBA-encryption-salt-v1";
+
+/// Default HKDF info string for passphrase key derivation
+pub const DEFAULT_KEY_INFO: &[u8] = b"UBA-encryption-key";
+
 /// Derive an encryption key from a passphrase using HKDF with proper error handling
 ///
 /// This function derives a 32-byte encryption key from a passphrase using HKDF-SHA256.
@@ -114,12 +306,73 @@ impl UbaEncryption {
 /// # Returns
 /// * Result containing 32-byte derived key or error
 pub fn derive_encryption_key_safe(passphrase: &str, salt: Option<&[u8]>) -> Result<[u8; 32]> {
-    let default_salt = b"UBA-encryption-salt-v1";
-    let used_salt = salt.unwrap_or(default_salt);
+    derive_encryption_key_with_info(passphrase, salt, DEFAULT_KEY_INFO)
+}
+
+/// Derive an encryption key from a passphrase using HKDF with a custom info string
+///
+/// Different `info` values yield independent keys from the same passphrase, which
+/// isolates applications that share a passphrase. With `DEFAULT_KEY_INFO` this is
+/// identical to `derive_encryption_key_safe`.
+///
+/// # Arguments
+/// * `passphrase` - User-provided passphrase
+/// * `salt` - Optional salt (if None, uses `DEFAULT_KEY_SALT_V1`)
+/// * `info` - HKDF context string, e.g. `b"my-app-v1"`
+///
+/// # Returns
+/// * Result containing 32-byte derived key or error
+pub fn derive_encryption_key_with_info(
+    passphrase: &str,
+    salt: Option<&[u8]>,
+    info: &[u8],
+) -> Result<[u8; 32]> {
+    let used_salt = salt.unwrap_or(DEFAULT_KEY_SALT_V1);
 
     let hk = Hkdf::<Sha256>::new(Some(used_salt), passphrase.as_bytes());
     let mut key = [0u8; 32];
-    hk.expand(b"UBA-encryption-key", &mut key)?;
+    hk.expand(info, &mut key)?;
+
+    Ok(key)
+}
+
+/// Default Argon2id memory cost in KiB (19 MiB, the OWASP minimum recommendation)
+pub const DEFAULT_ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+
+/// Default Argon2id iteration count
+pub const DEFAULT_ARGON2_ITERATIONS: u32 = 2;
+
+/// Derive an encryption key from a passphrase using Argon2id
+///
+/// Unlike HKDF, Argon2id is deliberately slow and memory-hard, which makes brute
+/// forcing human-chosen passphrases expensive. Keys derived this way differ from
+/// `derive_encryption_key_safe` keys for the same passphrase.
+///
+/// # Arguments
+/// * `passphrase` - User-provided passphrase
+/// * `salt` - Optional salt of at least 8 bytes (if None, uses default UBA salt)
+/// * `memory_kib` - Memory cost in KiB (at least 8)
+/// * `iterations` - Number of passes over memory (at least 1)
+///
+/// # Returns
+/// * Result containing 32-byte derived key, or `UbaError::KeyDerivation` for
+///   invalid parameters
+pub fn derive_encryption_key_argon2(
+    passphrase: &str,
+    salt: Option<&[u8]>,
+    memory_kib: u32,
+    iterations: u32,
+) -> Result<[u8; 32]> {
+    let used_salt = salt.unwrap_or(DEFAULT_KEY_SALT_V1);
+
+    let params = Params::new(memory_kib, iterations, 1, Some(32))
+        .map_err(|e| UbaError::KeyDerivation(format!("Invalid Argon2 parameters: {}", e)))?;
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+
+    let mut key = [0u8; 32];
+    argon2
+        .hash_password_into(passphrase.as_bytes(), used_salt, &mut key)
+        .map_err(|e| UbaError::KeyDerivation(format!("Argon2 derivation failed: {}", e)))?;
 
     Ok(key)
 }
@@ -150,6 +403,52 @@ pub fn generate_random_key() -> [u8; 32] {
     key
 }
 
+/// Encrypt data with NIP-04 for the given recipient
+///
+/// The output is the standard `<base64 ciphertext>?iv=<base64 iv>` payload, so any
+/// Nostr client holding the recipient's secret key can decrypt it.
+#[cfg(feature = "nostr")]
+pub fn nip04_encrypt(
+    sender_secret_key: &SecretKey,
+    recipient_public_key: &PublicKey,
+    data: &str,
+) -> Result<String> {
+    nip04::encrypt(sender_secret_key, recipient_public_key, data)
+        .map_err(|e| UbaError::Encryption(format!("Failed to NIP-04 encrypt: {}", e)))
+}
+
+/// Decrypt a NIP-04 payload
+///
+/// NIP-04 uses an ECDH shared secret, so `public_key` is the other party: the sender
+/// when decrypting as the recipient, or the recipient when decrypting as the sender.
+#[cfg(feature = "nostr")]
+pub fn nip04_decrypt(secret_key: &SecretKey, public_key: &PublicKey, data: &str) -> Result<String> {
+    nip04::decrypt(secret_key, public_key, data)
+        .map_err(|e| UbaError::Encryption(format!("Failed to NIP-04 decrypt: {}", e)))
+}
+
+/// Encrypt a JSON document with a hex-encoded key
+///
+/// Intended for client code that builds its own address JSON and wants to encrypt
+/// it the same way the library does before publishing. The input must be valid JSON;
+/// the output is the base64 payload produced by `UbaEncryption::encrypt`.
+pub fn encrypt_json(data: &str, key_hex: &str) -> Result<String> {
+    let key = EncryptionKey::from_hex(key_hex)?;
+    serde_json::from_str::<serde_json::Value>(data)?;
+    UbaEncryption::new(*key.reveal()).encrypt(data)
+}
+
+/// Decrypt a payload produced by `encrypt_json` back to its JSON document
+///
+/// Unlike `decrypt_if_needed`, a wrong key or corrupted payload is an error rather
+/// than being passed through unchanged.
+pub fn decrypt_json(data: &str, key_hex: &str) -> Result<String> {
+    let key = EncryptionKey::from_hex(key_hex)?;
+    let json = UbaEncryption::new(*key.reveal()).decrypt(data)?;
+    serde_json::from_str::<serde_json::Value>(&json)?;
+    Ok(json)
+}
+
 /// Utility function to encrypt JSON data if encryption is enabled
 ///
 /// # Arguments
@@ -228,6 +527,22 @@ mod tests {
         assert_eq!(json, decrypted);
     }
 
+    #[test]
+    fn test_encrypt_json_with_hex_key() {
+        let key_hex = hex::encode(generate_random_key());
+        let json = r#"{"addresses":{"P2WPKH":["bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"]}}"#;
+
+        let encrypted = encrypt_json(json, &key_hex).unwrap();
+        assert_ne!(encrypted, json);
+        assert_eq!(decrypt_json(&encrypted, &format!("0x{}\n", key_hex)).unwrap(), json);
+
+        // Wrong keys, malformed keys and non-JSON input are all errors
+        let other_key = hex::encode(generate_random_key());
+        assert!(decrypt_json(&encrypted, &other_key).is_err());
+        assert!(encrypt_json(json, "abcd").is_err());
+        assert!(encrypt_json("not json", &key_hex).is_err());
+    }
+
     #[test]
     fn test_no_encryption_passthrough() {
         let json = r#"{"addresses": {"P2PKH": ["1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"]}}"#;
@@ -239,6 +554,128 @@ mod tests {
         assert_eq!(json, result);
     }
 
+    #[test]
+    #[cfg(feature = "nostr")]
+    fn test_nip04_interop_with_nostr_crate() {
+        let sender = nostr::Keys::generate();
+        let recipient = nostr::Keys::generate();
+        let json = r#"{"addresses": {"P2WPKH": ["bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"]}}"#;
+
+        let encrypted = nip04_encrypt(
+            sender.secret_key().unwrap(),
+            &recipient.public_key(),
+            json,
+        )
+        .unwrap();
+        assert!(encrypted.contains("?iv="));
+
+        // A standard NIP-04 implementation can read what this crate encrypted
+        let decrypted =
+            nip04::decrypt(recipient.secret_key().unwrap(), &sender.public_key(), &encrypted)
+                .unwrap();
+        assert_eq!(decrypted, json);
+
+        // ...and the other way around, from either side of the conversation
+        let encrypted = nip04::encrypt(sender.secret_key().unwrap(), &recipient.public_key(), json)
+            .unwrap();
+        assert_eq!(
+            nip04_decrypt(recipient.secret_key().unwrap(), &sender.public_key(), &encrypted)
+                .unwrap(),
+            json
+        );
+        assert_eq!(
+            nip04_decrypt(sender.secret_key().unwrap(), &recipient.public_key(), &encrypted)
+                .unwrap(),
+            json
+        );
+
+        // A third party cannot decrypt
+        let outsider = nostr::Keys::generate();
+        assert!(nip04_decrypt(outsider.secret_key().unwrap(), &sender.public_key(), &encrypted)
+            .is_err());
+    }
+
+    #[test]
+    fn test_encryption_scheme_tag_values() {
+        for scheme in [
+            EncryptionScheme::ChaCha20Poly1305,
+            EncryptionScheme::Nip04,
+            EncryptionScheme::ChaCha20Poly1305MetadataOnly,
+        ] {
+            assert_eq!(EncryptionScheme::from_tag_value(scheme.tag_value()), Some(scheme));
+        }
+        assert_eq!(EncryptionScheme::from_tag_value("nip44"), None);
+    }
+
+    #[test]
+    fn test_key_derivation_with_info() {
+        let passphrase = "my secret passphrase";
+
+        // The default info reproduces the original derivation byte for byte
+        let hk = Hkdf::<Sha256>::new(Some(b"UBA-encryption-salt-v1"), passphrase.as_bytes());
+        let mut expected = [0u8; 32];
+        hk.expand(b"UBA-encryption-key", &mut expected).unwrap();
+        assert_eq!(derive_encryption_key_safe(passphrase, None).unwrap(), expected);
+        assert_eq!(
+            derive_encryption_key_with_info(passphrase, None, DEFAULT_KEY_INFO).unwrap(),
+            expected
+        );
+
+        // Other contexts get independent keys
+        let app_a = derive_encryption_key_with_info(passphrase, None, b"app-a").unwrap();
+        let app_b = derive_encryption_key_with_info(passphrase, None, b"app-b").unwrap();
+        assert_ne!(app_a, expected);
+        assert_ne!(app_a, app_b);
+    }
+
+    #[test]
+    fn test_argon2_key_derivation() {
+        // Small parameters keep the test fast
+        let key1 = derive_encryption_key_argon2("my secret passphrase", None, 64, 1).unwrap();
+        let key2 = derive_encryption_key_argon2("my secret passphrase", None, 64, 1).unwrap();
+        assert_eq!(key1, key2);
+
+        // The work factor is part of the derivation
+        let key3 = derive_encryption_key_argon2("my secret passphrase", None, 64, 2).unwrap();
+        assert_ne!(key1, key3);
+
+        // Argon2 and HKDF keys are not interchangeable
+        let hkdf_key = derive_encryption_key_safe("my secret passphrase", None).unwrap();
+        assert_ne!(key1, hkdf_key);
+
+        assert!(matches!(
+            derive_encryption_key_argon2("pass", None, 64, 0),
+            Err(UbaError::KeyDerivation(_))
+        ));
+        assert!(matches!(
+            derive_encryption_key_argon2("pass", Some(b"short"), 64, 1),
+            Err(UbaError::KeyDerivation(_))
+        ));
+    }
+
+    #[test]
+    fn test_encryption_key_wrapper() {
+        let bytes = generate_random_key();
+        let key = EncryptionKey::new(bytes);
+
+        assert_eq!(key.reveal(), &bytes);
+        assert_eq!(*key, bytes);
+        assert_eq!(key.to_hex(), hex::encode(bytes));
+        assert_eq!(key, EncryptionKey::from(bytes));
+        assert_ne!(key, EncryptionKey::new([0u8; 32]));
+
+        // The key never shows up in debug output
+        assert_eq!(format!("{:?}", key), "EncryptionKey(<redacted>)");
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"same bytes", b"same bytes"));
+        assert!(!constant_time_eq(b"same bytes", b"same bytez"));
+        assert!(!constant_time_eq(b"short", b"longer input"));
+        assert!(constant_time_eq(b"", b""));
+    }
+
     #[test]
     fn test_key_derivation_safe() {
         let passphrase = "my secret passphrase";
@@ -252,4 +689,40 @@ mod tests {
         let key3 = derive_encryption_key_safe("different passphrase", None).unwrap();
         assert_ne!(key1, key3);
     }
+
+    #[test]
+    fn test_detect_scheme() {
+        let tags = |pairs: &[(&str, &str)]| -> Vec<Vec<String>> {
+            pairs
+                .iter()
+                .map(|(name, value)| vec![name.to_string(), value.to_string()])
+                .collect()
+        };
+        let encrypted = UbaEncryption::new([7u8; 32]).encrypt(r#"{"addresses":{}}"#).unwrap();
+        let nip04 = "zJxfaJ32rN5Dg1ODjOlEew==?iv=EV5bUjcc4OX2Km/zPp4ndQ==";
+
+        // Tags take precedence over the content
+        assert_eq!(
+            detect_scheme("{}", &tags(&[("encrypted", "true"), ("scheme", "nip04")])),
+            Some(EncryptionScheme::Nip04)
+        );
+        assert_eq!(
+            detect_scheme(&encrypted, &tags(&[("encrypted", "true")])),
+            Some(EncryptionScheme::ChaCha20Poly1305)
+        );
+        assert_eq!(
+            detect_scheme("{}", &tags(&[("encrypted", "metadata")])),
+            Some(EncryptionScheme::ChaCha20Poly1305MetadataOnly)
+        );
+
+        // Untagged content is recognised by its shape
+        assert_eq!(detect_scheme(&encrypted, &[]), Some(EncryptionScheme::ChaCha20Poly1305));
+        assert_eq!(detect_scheme(nip04, &[]), Some(EncryptionScheme::Nip04));
+        assert_eq!(
+            detect_scheme(r#"{"addresses":{},"encrypted_metadata":"abc"}"#, &[]),
+            Some(EncryptionScheme::ChaCha20Poly1305MetadataOnly)
+        );
+        assert_eq!(detect_scheme(r#"{"addresses":{}}"#, &[]), None);
+        assert_eq!(detect_scheme("hello", &[]), None);
+    }
 }
@@ -32,7 +32,21 @@ pub enum UbaError {
     #[error("Network error: {0}")]
     Network(String),
 
+    /// Relays rejected an event because it exceeds their maximum size
+    ///
+    /// Callers can react by publishing the addresses in shards instead.
+    #[error("Event of {size} bytes is too large for the relays: {message}")]
+    EventTooLarge {
+        /// Serialized size of the rejected event in bytes
+        size: usize,
+        /// Per-relay rejection messages, as `url: message` pairs
+        message: String,
+    },
+
     /// Note not found on any relay
+    ///
+    /// Returned by every retrieval path (single, batch and shard retrieval) when
+    /// no relay has the requested event.
     #[error("Note not found with ID: {0}")]
     NoteNotFound(String),
 
@@ -72,11 +86,18 @@ pub enum UbaError {
     #[error("Operation timed out")]
     Timeout,
 
+    /// The operation was cancelled through its cancellation token
+    #[error("Operation cancelled")]
+    Cancelled,
+
     /// Configuration error
     #[error("Configuration error: {0}")]
     Config(String),
 
     /// Event not found error
+    ///
+    /// Returned when the event an update would replace cannot be found;
+    /// retrieval reports missing events as `NoteNotFound`.
     #[error("Event not found: {0}")]
     EventNotFound(String),
 
@@ -107,6 +128,10 @@ pub enum UbaError {
     /// Key derivation error
     #[error("Key derivation error: {0}")]
     KeyDerivation(String),
+
+    /// Payload signature missing, malformed or not matching the expected signer
+    #[error("Payload signature verification failed: {0}")]
+    InvalidSignature(String),
 }
 
 impl From<bitcoin::address::Error> for UbaError {
@@ -121,12 +146,14 @@ impl From<bitcoin::secp256k1::Error> for UbaError {
     }
 }
 
+#[cfg(feature = "nostr")]
 impl From<nostr::key::Error> for UbaError {
     fn from(err: nostr::key::Error) -> Self {
         UbaError::NostrRelay(err.to_string())
     }
 }
 
+#[cfg(feature = "nostr")]
 impl From<nostr_sdk::client::Error> for UbaError {
     fn from(err: nostr_sdk::client::Error) -> Self {
         UbaError::NostrRelay(err.to_string())
@@ -158,6 +185,7 @@ pub mod validation {
     use std::time::{Duration, Instant};
 
     /// Rate limiter for preventing abuse
+    #[derive(Debug)]
     pub struct RateLimiter {
         requests: HashMap<String, Vec<Instant>>,
         max_requests: usize,
@@ -213,8 +241,8 @@ pub mod validation {
             return Err(UbaError::InputValidation("Seed too long".to_string()));
         }
 
-        // Check if it's a valid BIP39 mnemonic
-        if let Err(e) = bip39::Mnemonic::parse(seed) {
+        // Check if it's a valid BIP39 mnemonic, in any wordlist
+        if let Err(e) = crate::address::parse_mnemonic(seed, None) {
             return Err(UbaError::InputValidation(format!("Invalid BIP39 mnemonic: {}", e)));
         }
 
@@ -274,6 +302,60 @@ pub mod validation {
         Ok(())
     }
 
+    /// Validate a Lightning Address (`name@domain`, LUD-16)
+    ///
+    /// The name may only contain lowercase letters, digits and `-_.`, and the
+    /// domain must be a valid host name.
+    pub fn validate_lightning_address(address: &str) -> Result<()> {
+        let (name, domain) = address.split_once('@').ok_or_else(|| {
+            UbaError::InputValidation("Lightning address must look like name@domain".to_string())
+        })?;
+
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-_.".contains(c))
+        {
+            return Err(UbaError::InputValidation(format!(
+                "Invalid Lightning address name '{}': only a-z, 0-9 and -_. are allowed",
+                name
+            )));
+        }
+
+        let is_host = !domain.is_empty()
+            && domain.chars().all(|c| c.is_ascii_alphanumeric() || "-.:".contains(c))
+            && url::Url::parse(&format!("https://{}", domain))
+                .is_ok_and(|url| url.host_str().is_some() && url.path() == "/");
+        if !is_host {
+            return Err(UbaError::InputValidation(format!(
+                "Invalid Lightning address domain '{}'",
+                domain
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Validate a Lightning node id: a 33-byte compressed public key as 66 hex
+    /// characters
+    pub fn validate_lightning_node_id(node_id: &str) -> Result<()> {
+        if node_id.len() != 66 {
+            return Err(UbaError::InputValidation(format!(
+                "Lightning node id must be 66 hex characters, got {}",
+                node_id.len()
+            )));
+        }
+
+        let bytes = hex::decode(node_id).map_err(|e| {
+            UbaError::InputValidation(format!("Lightning node id is not hex: {}", e))
+        })?;
+        bitcoin::secp256k1::PublicKey::from_slice(&bytes).map_err(|e| {
+            UbaError::InputValidation(format!("Invalid Lightning node id: {}", e))
+        })?;
+
+        Ok(())
+    }
+
     /// Validate UBA format
     pub fn validate_uba_format(uba: &str) -> Result<()> {
         if uba.is_empty() {
@@ -352,5 +434,29 @@ pub mod validation {
             assert!(validate_uba_format("UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef").is_ok());
             assert!(validate_uba_format("UBA:invalid").is_err());
         }
+
+        #[test]
+        fn test_validate_lightning_address() {
+            assert!(validate_lightning_address("satoshi@getalby.com").is_ok());
+            assert!(validate_lightning_address("tips_2024.me@pay.example.org").is_ok());
+            assert!(validate_lightning_address("dev@localhost:8080").is_ok());
+            assert!(validate_lightning_address("satoshi").is_err());
+            assert!(validate_lightning_address("@getalby.com").is_err());
+            assert!(validate_lightning_address("Satoshi@getalby.com").is_err());
+            assert!(validate_lightning_address("satoshi@").is_err());
+            assert!(validate_lightning_address("satoshi@get alby.com").is_err());
+            assert!(validate_lightning_address("satoshi@getalby.com/pay").is_err());
+        }
+
+        #[test]
+        fn test_validate_lightning_node_id() {
+            // The secp256k1 generator point
+            let node_id = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+            assert!(validate_lightning_node_id(node_id).is_ok());
+            assert!(validate_lightning_node_id(&node_id[..64]).is_err());
+            assert!(validate_lightning_node_id(&format!("zz{}", &node_id[2..])).is_err());
+            // Right length, but 0x05 is not a compressed key prefix
+            assert!(validate_lightning_node_id(&format!("05{}", &node_id[2..])).is_err());
+        }
     }
 }
@@ -34,25 +34,76 @@
 //! - **Optional encryption**: Secure data with ChaCha20Poly1305 encryption
 //! - **Configurable address counts**: Flexible control over address generation
 //! - **Public relay list**: Curated list of reliable Nostr relays
+//!
+//! # Cargo Features
+//!
+//! - `nostr` (default): relay networking, i.e. generating, publishing and retrieving
+//!   UBAs, `NostrClient`, NIP-04 encryption and the async runtime. Without it the
+//!   crate still offers offline address generation (`AddressGenerator`,
+//!   `BitcoinAddresses`, `UbaConfig`), encryption, the cache and UBA string parsing
+//!   (`parse_uba`, `format_uba`), without pulling in `tokio` or `nostr-sdk`
+//! - `tracing`: emit structured `tracing` spans and events for connect, publish,
+//!   retrieve, retry and decryption steps
+//! - `testing`: in-memory `MockRelay` for offline tests
+
+#[cfg(feature = "nostr")]
+#[macro_use]
+mod trace;
 
 pub mod address;
+pub mod cache;
 pub mod encryption;
 pub mod error;
+#[cfg(feature = "nostr")]
 pub mod nostr_client;
+pub mod parse;
+#[cfg(all(feature = "nostr", any(test, feature = "testing")))]
+pub mod testing;
+#[cfg(feature = "nostr")]
+pub mod transport;
 pub mod types;
+#[cfg(feature = "nostr")]
 pub mod uba;
 
 // Re-export main types and functions for convenience
-pub use address::AddressGenerator;
-pub use encryption::{derive_encryption_key, generate_random_key, UbaEncryption};
+pub use address::{
+    classify_address, identity_public_key, identity_public_key_with_config, is_lightning_address,
+    sign_challenge, sign_challenge_with_config, verify_challenge, AddressGenerator,
+};
+pub use cache::{CacheMode, UbaCache};
+pub use encryption::{
+    constant_time_eq, decrypt_json, derive_encryption_key, detect_scheme, encrypt_json,
+    generate_random_key, EncryptionKey, EncryptionScheme, UbaEncryption,
+};
 pub use error::{Result, UbaError};
+#[cfg(feature = "nostr")]
 pub use nostr_client::NostrClient;
+pub use parse::{format_uba, parse_uba};
+#[cfg(feature = "nostr")]
+pub use transport::{ConnectStatus, PublishOutput, RelaySummary, RelayTransport};
 pub use types::*;
+#[cfg(feature = "nostr")]
 pub use uba::{
-    generate, generate_with_config, parse_uba, retrieve, retrieve_full, retrieve_full_with_config,
-    retrieve_with_config, update_uba, update_uba_with_addresses,
+    generate, generate_batch, generate_batch_with_client, generate_cancellable,
+    generate_cancellable_with_client, generate_dry_run, generate_full, generate_full_with_client,
+    generate_full_with_config, generate_sharded, generate_sharded_with_client, generate_with_client,
+    generate_with_config, has_update, has_update_with_client, has_update_with_config,
+    nostr_keys_from_seed, nostr_keys_from_seed_with_config, reencrypt_uba,
+    reencrypt_uba_with_client, retrieve, retrieve_full, retrieve_full_cached,
+    retrieve_full_cached_with_client, retrieve_full_cached_with_config,
+    retrieve_full_checked, retrieve_full_checked_with_client, retrieve_full_with_client,
+    retrieve_full_with_config, retrieve_full_with_fallback, retrieve_full_with_fallback_clients,
+    retrieve_history_with_client, retrieve_history_with_config, retrieve_many,
+    retrieve_many_with_client, retrieve_with_config, retrieve_with_fallback, self_test,
+    self_test_with_client, self_test_with_config, update_addresses_from_data, update_uba,
+    update_uba_with_addresses, verify_resolvable, verify_resolvable_with_client,
+    verify_resolvable_with_config, verify_seed_matches, verify_seed_matches_with_client,
 };
 
 // Re-export commonly used external types
+pub use bip39::Language;
 pub use bitcoin::Network;
+#[cfg(feature = "nostr")]
 pub use nostr::Url;
+#[cfg(feature = "nostr")]
+pub use tokio_util::sync::CancellationToken;
@@ -1,37 +1,82 @@
 //! Nostr client for publishing and retrieving UBA data
 
-use crate::encryption::{decrypt_if_needed, encrypt_if_enabled};
+use crate::address::check_entry_format;
+use crate::encryption::{
+    decrypt_if_needed, encrypt_if_enabled, nip04_decrypt, nip04_encrypt, EncryptionScheme,
+    UbaEncryption, ENCRYPTED_METADATA_FIELD,
+};
 use crate::error::{Result, UbaError, validation};
-use crate::types::BitcoinAddresses;
+use crate::transport::{ConnectStatus, PublishOutput, RelaySummary, RelayTransport};
+use crate::types::{
+    BitcoinAddresses, RawUbaEvent, RetrievalBounds, UbaConfig, DEFAULT_EVENT_KIND,
+    MAX_FORMAT_VERSION,
+};
 
-use nostr::{EventBuilder, EventId, Filter, Keys, Kind, Tag, Url};
+use nostr::{Event, EventBuilder, EventId, Filter, Keys, Kind, PublicKey, SecretKey, Tag, Timestamp};
 use nostr_sdk::Client;
-use serde_json;
-use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 use tokio::time::timeout;
 
+/// Bytes reserved in every shard event for the group and part tags
+const SHARD_TAG_RESERVE: usize = 128;
+
+/// Content of the manifest event of a sharded UBA
+#[derive(Debug, Serialize, Deserialize)]
+struct ShardManifest {
+    /// Group ID shared by all parts
+    group: String,
+    /// Hex event IDs of the parts, in order
+    parts: Vec<String>,
+}
+
 /// Nostr client for UBA operations with retry logic
-pub struct NostrClient {
-    client: Client,
+///
+/// Generic over the relay transport, defaulting to `nostr_sdk::Client`.
+///
+/// Dropping a client that is still connected starts disconnecting its relays in
+/// the background (see `RelayTransport::disconnect_in_background`). Prefer awaiting
+/// `disconnect`, or `with_connection`, to know the connections are closed.
+pub struct NostrClient<T: RelayTransport = Client> {
+    transport: T,
     keys: Keys,
     timeout_duration: Duration,
+    connect_timeout: Duration,
+    publish_timeout: Option<Duration>,
+    query_timeout: Option<Duration>,
     max_retry_attempts: usize,
     retry_delay_ms: u64,
+    fixed_timestamp: Option<u64>,
+    metadata_only_encryption: bool,
+    strip_metadata: bool,
+    event_kind: Kind,
+    /// Set once relays were added, cleared by `disconnect`
+    connected: AtomicBool,
 }
 
-impl NostrClient {
+impl NostrClient<Client> {
     /// Create a new Nostr client with generated keys
     pub fn new(timeout_seconds: u64) -> Result<Self> {
         let keys = Keys::generate();
         let client = Client::new(&keys);
 
         Ok(Self {
-            client,
+            transport: client,
             keys,
             timeout_duration: Duration::from_secs(timeout_seconds),
+            connect_timeout: Duration::from_secs(timeout_seconds),
+            publish_timeout: None,
+            query_timeout: None,
             max_retry_attempts: 3,
             retry_delay_ms: 1000,
+            fixed_timestamp: None,
+            metadata_only_encryption: false,
+            strip_metadata: false,
+            event_kind: Kind::Custom(DEFAULT_EVENT_KIND),
+            connected: AtomicBool::new(false),
         })
     }
 
@@ -40,11 +85,19 @@ impl NostrClient {
         let client = Client::new(&keys);
 
         Self {
-            client,
+            transport: client,
             keys,
             timeout_duration: Duration::from_secs(timeout_seconds),
+            connect_timeout: Duration::from_secs(timeout_seconds),
+            publish_timeout: None,
+            query_timeout: None,
             max_retry_attempts: 3,
             retry_delay_ms: 1000,
+            fixed_timestamp: None,
+            metadata_only_encryption: false,
+            strip_metadata: false,
+            event_kind: Kind::Custom(DEFAULT_EVENT_KIND),
+            connected: AtomicBool::new(false),
         }
     }
 
@@ -58,15 +111,151 @@ impl NostrClient {
         let client = Client::new(&keys);
 
         Ok(Self {
-            client,
+            transport: client,
             keys,
             timeout_duration: Duration::from_secs(timeout_seconds),
+            connect_timeout: Duration::from_secs(timeout_seconds),
+            publish_timeout: None,
+            query_timeout: None,
             max_retry_attempts,
             retry_delay_ms,
+            fixed_timestamp: None,
+            metadata_only_encryption: false,
+            strip_metadata: false,
+            event_kind: Kind::Custom(DEFAULT_EVENT_KIND),
+            connected: AtomicBool::new(false),
         })
     }
 
+    /// Create a Nostr client with the given keys and every client setting of `config`
+    ///
+    /// The way all high-level operations build their clients, so each `UbaConfig`
+    /// setting only needs to be threaded through `apply_config`.
+    pub fn from_config(keys: Keys, config: &UbaConfig) -> Self {
+        let mut client = Self::with_keys(keys, config.relay_timeout);
+        client.apply_config(config);
+        client
+    }
+}
+
+impl<T: RelayTransport> NostrClient<T> {
+    /// Create a new Nostr client on top of a custom relay transport
+    pub fn with_transport(transport: T, keys: Keys, timeout_seconds: u64) -> Self {
+        Self {
+            transport,
+            keys,
+            timeout_duration: Duration::from_secs(timeout_seconds),
+            connect_timeout: Duration::from_secs(timeout_seconds),
+            publish_timeout: None,
+            query_timeout: None,
+            max_retry_attempts: 3,
+            retry_delay_ms: 1000,
+            fixed_timestamp: None,
+            metadata_only_encryption: false,
+            strip_metadata: false,
+            event_kind: Kind::Custom(DEFAULT_EVENT_KIND),
+            connected: AtomicBool::new(false),
+        }
+    }
+
+    /// Apply the client settings of a `UbaConfig`: timeouts, retries, timestamps,
+    /// metadata handling and event kind
+    pub fn apply_config(&mut self, config: &UbaConfig) {
+        self.set_timeout(Duration::from_secs(config.relay_timeout));
+        self.set_connect_timeout(config.connect_timeout());
+        self.set_publish_timeout(config.publish_timeout_secs.map(Duration::from_secs));
+        self.set_query_timeout(config.query_timeout_secs.map(Duration::from_secs));
+        self.set_retry_config(config.max_retry_attempts, config.retry_delay_ms);
+        self.set_fixed_timestamp(config.fixed_timestamp);
+        self.set_metadata_only_encryption(config.encrypt_metadata_only);
+        self.set_strip_metadata(config.strip_metadata_on_publish);
+        self.set_event_kind(config.event_kind);
+    }
+
+    /// Set the retry configuration used when connecting to relays
+    pub fn set_retry_config(&mut self, max_retry_attempts: usize, retry_delay_ms: u64) {
+        self.max_retry_attempts = max_retry_attempts;
+        self.retry_delay_ms = retry_delay_ms;
+    }
+
+    /// Set the Nostr event kind used for publishing and in every retrieval filter
+    ///
+    /// Readers must use the same kind as the writer to find its events.
+    pub fn set_event_kind(&mut self, kind: u16) {
+        self.event_kind = Kind::Custom(kind);
+    }
+
+    /// Set the timeout applied to each relay operation (connect, publish, query)
+    pub fn set_timeout(&mut self, timeout_duration: Duration) {
+        self.timeout_duration = timeout_duration;
+    }
+
+    /// Set how long to wait for at least one relay to connect
+    ///
+    /// Defaults to the operation timeout the client was created with, matching
+    /// `UbaConfig::connect_timeout` when `connect_timeout_ms` is unset.
+    pub fn set_connect_timeout(&mut self, connect_timeout: Duration) {
+        self.connect_timeout = connect_timeout;
+    }
+
+    /// Set the timeout for publishing an event; None uses the `set_timeout` value
+    pub fn set_publish_timeout(&mut self, publish_timeout: Option<Duration>) {
+        self.publish_timeout = publish_timeout;
+    }
+
+    /// Set the timeout for relay queries; None uses the `set_timeout` value
+    pub fn set_query_timeout(&mut self, query_timeout: Option<Duration>) {
+        self.query_timeout = query_timeout;
+    }
+
+    /// Use a fixed `created_at` (Unix seconds) for every event this client signs
+    ///
+    /// With a fixed timestamp and unencrypted content, the same addresses always
+    /// produce the same event ID. None restores the current time.
+    pub fn set_fixed_timestamp(&mut self, timestamp: Option<u64>) {
+        self.fixed_timestamp = timestamp;
+    }
+
+    /// Get the keys this client signs events with
+    pub fn keys(&self) -> &Keys {
+        &self.keys
+    }
+
+    /// Replace the keys this client signs events with
+    ///
+    /// The relay connection is unaffected, so one connected client can publish
+    /// events on behalf of several identities.
+    pub fn set_keys(&mut self, keys: Keys) {
+        self.keys = keys;
+    }
+
+    /// Encrypt only the metadata block when an encryption key is given
+    ///
+    /// Addresses are then published in cleartext, so anyone can retrieve them, while
+    /// the label, description, xpub and derivation paths need the key. The label is
+    /// also left out of the event tags.
+    pub fn set_metadata_only_encryption(&mut self, enabled: bool) {
+        self.metadata_only_encryption = enabled;
+    }
+
+    /// Leave the metadata block out of published events entirely
+    ///
+    /// The label, description, xpub and derivation paths are then never sent to
+    /// relays, not even encrypted. The caller's `BitcoinAddresses` is unchanged.
+    pub fn set_strip_metadata(&mut self, enabled: bool) {
+        self.strip_metadata = enabled;
+    }
+
+    /// Get the underlying relay transport
+    pub fn transport(&self) -> &T {
+        &self.transport
+    }
+
     /// Connect to the specified relay URLs with retry logic
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(relays = ?relay_urls))
+    )]
     pub async fn connect_to_relays(&self, relay_urls: &[String]) -> Result<()> {
         // Validate relay URLs first
         validation::validate_relay_urls(relay_urls)?;
@@ -75,8 +264,17 @@ impl NostrClient {
 
         for attempt in 0..self.max_retry_attempts {
             match self.try_connect_to_relays(relay_urls).await {
-                Ok(()) => return Ok(()),
+                Ok(()) => {
+                    trace_debug!(attempt = attempt + 1, "Connected to relays");
+                    return Ok(());
+                }
                 Err(e) => {
+                    trace_warn!(
+                        attempt = attempt + 1,
+                        max_attempts = self.max_retry_attempts,
+                        error = %e,
+                        "Relay connection attempt failed"
+                    );
                     last_error = Some(e);
                     if attempt < self.max_retry_attempts - 1 {
                         tokio::time::sleep(Duration::from_millis(self.retry_delay_ms)).await;
@@ -92,26 +290,131 @@ impl NostrClient {
         )))
     }
 
+    /// Connect to the specified relay URLs and report the state of each relay
+    ///
+    /// Returns `UbaError::NostrRelay` if no relay ended up connected, so callers
+    /// find out before a publish or retrieval times out.
+    pub async fn connect_to_relays_detailed(
+        &self,
+        relay_urls: &[String],
+    ) -> Result<Vec<(String, ConnectStatus)>> {
+        self.connect_to_relays(relay_urls).await?;
+
+        let statuses = self.relay_statuses().await;
+        if !statuses.iter().any(|(_, status)| status.is_connected()) {
+            return Err(UbaError::NostrRelay(format!(
+                "None of the {} relays connected",
+                relay_urls.len()
+            )));
+        }
+
+        Ok(statuses)
+    }
+
+    /// Get the connection state of every relay added to this client
+    pub async fn relay_statuses(&self) -> Vec<(String, ConnectStatus)> {
+        self.transport.relay_statuses().await
+    }
+
+    /// Ping every relay configured in `config` concurrently and summarize which are online
+    ///
+    /// Each relay is checked independently of this client's connections and must
+    /// answer within `config.relay_timeout` seconds.
+    pub async fn ping_configured_relays(&self, config: &UbaConfig) -> RelaySummary {
+        let relay_urls = config.get_relay_urls();
+        let results = self
+            .transport
+            .ping_relays(&relay_urls, Duration::from_secs(config.relay_timeout))
+            .await;
+
+        let mut summary = RelaySummary::default();
+        for url in relay_urls {
+            match results.iter().find(|(pinged, _)| *pinged == url) {
+                Some((_, Ok(latency))) => {
+                    summary.latencies.push((url.clone(), *latency));
+                    summary.reachable.push(url);
+                }
+                _ => summary.unreachable.push(url),
+            }
+        }
+
+        summary
+    }
+
+    /// Pick the `n` candidate relays that connect fastest
+    ///
+    /// All candidates are pinged concurrently, independently of this client's
+    /// connections. Relays that fail to connect within `timeout` are dropped; the
+    /// rest are ordered by connect latency, so fewer than `n` URLs are returned
+    /// when fewer relays are reachable.
+    pub async fn select_fastest(
+        &self,
+        candidate_relays: &[String],
+        n: usize,
+        timeout: Duration,
+    ) -> Vec<String> {
+        let mut reachable: Vec<(String, Duration)> = self
+            .transport
+            .ping_relays(candidate_relays, timeout)
+            .await
+            .into_iter()
+            .filter_map(|(url, result)| result.ok().map(|latency| (url, latency)))
+            .collect();
+
+        // Ties keep the URL order from ping_relays, so selection is deterministic
+        reachable.sort_by_key(|(_, latency)| *latency);
+        reachable.into_iter().take(n).map(|(url, _)| url).collect()
+    }
+
     /// Single attempt to connect to relays
     async fn try_connect_to_relays(&self, relay_urls: &[String]) -> Result<()> {
-        for url_str in relay_urls {
-            let url = Url::parse(url_str).map_err(|_| UbaError::InvalidRelayUrl(url_str.clone()))?;
+        // Relays may be added even if the attempt fails, so mark before connecting
+        self.connected.store(true, Ordering::Relaxed);
 
-            self.client
-                .add_relay(url)
-                .await
-                .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
-        }
+        // Connect to all relays with timeout
+        timeout(
+            self.timeout_duration,
+            self.transport.connect(relay_urls, self.connect_timeout),
+        )
+            .await
+            .map_err(|_| UbaError::Timeout)?
+    }
 
-        // Connect to all added relays with timeout
-        timeout(self.timeout_duration, self.client.connect())
+    /// Send a signed event to the connected relays
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(event_id = %event.id))
+    )]
+    async fn send_event(&self, event: Event) -> Result<PublishOutput> {
+        let publish_timeout = self.publish_timeout.unwrap_or(self.timeout_duration);
+        let output = timeout(publish_timeout, self.transport.publish(event))
             .await
-            .map_err(|_| UbaError::Timeout)?;
+            .map_err(|_| {
+                trace_warn!("Publishing timed out");
+                UbaError::Timeout
+            })??;
+
+        trace_info!(
+            event_id = %output.event_id,
+            confirmed_relays = ?output.confirmed_relays,
+            "Event published"
+        );
+        Ok(output)
+    }
 
-        // Wait a moment for connections to establish
-        tokio::time::sleep(Duration::from_millis(500)).await;
+    /// Query the connected relays for events matching the filter
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+    async fn query_events(&self, filter: Filter) -> Result<Vec<Event>> {
+        let query_timeout = self.query_timeout.unwrap_or(self.timeout_duration);
+        let events = timeout(query_timeout, self.transport.query(vec![filter], query_timeout))
+            .await
+            .map_err(|_| {
+                trace_warn!("Relay query timed out");
+                UbaError::Timeout
+            })??;
 
-        Ok(())
+        trace_debug!(events = events.len(), "Relay query answered");
+        Ok(events)
     }
 
     /// Publish Bitcoin addresses as a Nostr event and return the event ID
@@ -120,6 +423,7 @@ impl NostrClient {
         addresses: &BitcoinAddresses,
         encrypt: bool,
     ) -> Result<String> {
+        let addresses = &*self.publishable(addresses);
         let content = if encrypt {
             // For now, we'll just serialize as JSON
             // TODO: Implement proper encryption using Nostr's NIP-04 or similar
@@ -130,7 +434,7 @@ impl NostrClient {
 
         // Create a custom event for UBA data
         // Using Kind 1000-9999 range for application-specific events
-        let kind = Kind::Custom(30000); // Parametrized replaceable event
+        let kind = self.event_kind;
 
         let mut tags = Vec::new();
 
@@ -156,17 +460,12 @@ impl NostrClient {
                 .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
         );
 
-        let event = EventBuilder::new(kind, content, tags)
-            .to_event(&self.keys)
-            .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
+        let event = self.sign_event(EventBuilder::new(kind, content, tags))?;
 
         // Publish the event with timeout
-        let event_id = timeout(self.timeout_duration, self.client.send_event(event))
-            .await
-            .map_err(|_| UbaError::Timeout)?
-            .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
+        let output = self.send_event(event).await?;
 
-        Ok(event_id.to_hex())
+        Ok(output.event_id.to_hex())
     }
 
     /// Publish Bitcoin addresses with optional encryption
@@ -175,17 +474,257 @@ impl NostrClient {
         addresses: &BitcoinAddresses,
         encryption_key: Option<&[u8; 32]>,
     ) -> Result<String> {
+        let output = self
+            .publish_addresses_detailed(addresses, encryption_key)
+            .await?;
+
+        Ok(output.event_id.to_hex())
+    }
+
+    /// Publish Bitcoin addresses and report which relays accepted the event
+    pub async fn publish_addresses_detailed(
+        &self,
+        addresses: &BitcoinAddresses,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<PublishOutput> {
+        let event = self.build_addresses_event(addresses, encryption_key)?;
+
+        // Publish the event with timeout
+        self.send_event(event).await
+    }
+
+    /// Publish a collection split across linked events that each fit `max_event_size`
+    ///
+    /// Every part is published as its own UBA event tagged with a shared group ID and
+    /// its part index. A final manifest event lists the part event IDs in order; its
+    /// ID is returned and is what the UBA string points to. Retrieval detects the
+    /// manifest and reassembles the parts transparently.
+    pub async fn publish_addresses_sharded(
+        &self,
+        addresses: &BitcoinAddresses,
+        encryption_key: Option<&[u8; 32]>,
+        max_event_size: usize,
+    ) -> Result<PublishOutput> {
+        let addresses = &*self.publishable(addresses);
+        self.validate_address_update(addresses)?;
+
+        let parts = addresses.split_by_event_size(
+            max_event_size,
+            SHARD_TAG_RESERVE,
+            encryption_key.is_some(),
+            self.event_kind.as_u16(),
+        )?;
+        let group_id = uuid::Uuid::new_v4().to_string();
+        let total = parts.len().to_string();
+
+        let mut part_ids = Vec::with_capacity(parts.len());
+        let mut confirmed_relays: Option<Vec<String>> = None;
+        for (index, part) in parts.iter().enumerate() {
+            let (content, scheme) = self.encode_content(part, encryption_key)?;
+            let extra_tags = vec![
+                Tag::parse(&["group", &group_id])
+                    .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
+                Tag::parse(&["part", &index.to_string(), &total])
+                    .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
+            ];
+            let event = self.build_event(part, content, scheme, extra_tags)?;
+            let output = self.send_event(event).await?;
+
+            // Only relays that accepted every part can serve the whole collection
+            confirmed_relays = Some(match confirmed_relays {
+                None => output.confirmed_relays,
+                Some(relays) => relays
+                    .into_iter()
+                    .filter(|relay| output.confirmed_relays.contains(relay))
+                    .collect(),
+            });
+            part_ids.push(output.event_id.to_hex());
+        }
+
+        let manifest = ShardManifest {
+            group: group_id.clone(),
+            parts: part_ids,
+        };
+        let tags = vec![
+            Tag::parse(&["uba", "bitcoin-addresses"])
+                .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
+            Tag::parse(&["sharded", &total]).map_err(|e| UbaError::NostrRelay(e.to_string()))?,
+            Tag::parse(&["group", &group_id]).map_err(|e| UbaError::NostrRelay(e.to_string()))?,
+            Tag::parse(&["version", &addresses.version.to_string()])
+                .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
+        ];
+        let event = self.sign_event(EventBuilder::new(
+            self.event_kind,
+            serde_json::to_string(&manifest)?,
+            tags,
+        ))?;
+        let output = self.send_event(event).await?;
+
+        Ok(PublishOutput {
+            event_id: output.event_id,
+            confirmed_relays: output
+                .confirmed_relays
+                .into_iter()
+                .filter(|relay| match &confirmed_relays {
+                    Some(relays) => relays.contains(relay),
+                    None => true,
+                })
+                .collect(),
+        })
+    }
+
+    /// Compute the ID of the event `publish_addresses_with_encryption` would publish
+    ///
+    /// The event is built and signed locally without any networking. Event IDs cover
+    /// the creation timestamp and the content, so the ID is only reproducible for
+    /// unencrypted content built within the same second (or with a fixed timestamp);
+    /// to publish exactly the previewed event, use `build_addresses_event` followed
+    /// by `publish_event`.
+    pub fn compute_event_id(
+        &self,
+        addresses: &BitcoinAddresses,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<String> {
+        let event = self.build_addresses_event(addresses, encryption_key)?;
+        Ok(event.id.to_hex())
+    }
+
+    /// Publish an already signed event, e.g. one built by `build_addresses_event`
+    pub async fn publish_event(&self, event: Event) -> Result<PublishOutput> {
+        self.send_event(event).await
+    }
+
+    /// Build and sign the UBA event for the given addresses without publishing it
+    ///
+    /// This performs no networking, which makes it suitable for previews and tests.
+    pub fn build_addresses_event(
+        &self,
+        addresses: &BitcoinAddresses,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<Event> {
+        let addresses = &*self.publishable(addresses);
+
         // Validate addresses before publishing
         self.validate_address_update(addresses)?;
 
-        // Serialize addresses to JSON
+        let (content, scheme) = self.encode_content(addresses, encryption_key)?;
+
+        self.build_event(addresses, content, scheme, Vec::new())
+    }
+
+    /// Build and sign a UBA event whose content is NIP-04 encrypted for `recipient`
+    ///
+    /// The event carries a `p` tag for the recipient, so both the recipient and this
+    /// client's keys can decrypt it, as with a Nostr direct message.
+    pub fn build_addresses_event_nip04(
+        &self,
+        addresses: &BitcoinAddresses,
+        recipient: &PublicKey,
+    ) -> Result<Event> {
+        let addresses = &*self.publishable(addresses);
+        self.validate_address_update(addresses)?;
+
         let json_content = serde_json::to_string(addresses)?;
+        let content = nip04_encrypt(self.secret_key()?, recipient, &json_content)?;
+
+        let recipient_tag = Tag::parse(&["p", &recipient.to_hex()])
+            .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
+
+        self.build_event(
+            addresses,
+            content,
+            Some(EncryptionScheme::Nip04),
+            vec![recipient_tag],
+        )
+    }
+
+    /// Publish Bitcoin addresses NIP-04 encrypted for `recipient`
+    pub async fn publish_addresses_nip04(
+        &self,
+        addresses: &BitcoinAddresses,
+        recipient: &PublicKey,
+    ) -> Result<PublishOutput> {
+        let event = self.build_addresses_event_nip04(addresses, recipient)?;
+        self.send_event(event).await
+    }
+
+    /// Get the collection as it should be published, without metadata if stripping
+    fn publishable<'a>(&self, addresses: &'a BitcoinAddresses) -> Cow<'a, BitcoinAddresses> {
+        if self.strip_metadata && addresses.metadata.is_some() {
+            let mut stripped = addresses.clone();
+            stripped.metadata = None;
+            Cow::Owned(stripped)
+        } else {
+            Cow::Borrowed(addresses)
+        }
+    }
+
+    /// Serialize addresses into event content, encrypting it if a key is provided
+    fn encode_content(
+        &self,
+        addresses: &BitcoinAddresses,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<(String, Option<EncryptionScheme>)> {
+        match encryption_key {
+            None => Ok((serde_json::to_string(addresses)?, None)),
+            Some(key) if self.metadata_only_encryption => {
+                // Publish the collection without metadata and attach the metadata
+                // block encrypted under a separate field
+                let mut public_addresses = addresses.clone();
+                let metadata = public_addresses.metadata.take();
+                let mut content = serde_json::to_value(&public_addresses)?;
+
+                if let Some(metadata) = metadata {
+                    let metadata_json = serde_json::to_string(&metadata)?;
+                    let encrypted = UbaEncryption::new(*key).encrypt(&metadata_json)?;
+                    content[ENCRYPTED_METADATA_FIELD] = serde_json::Value::String(encrypted);
+                }
+
+                Ok((
+                    serde_json::to_string(&content)?,
+                    Some(EncryptionScheme::ChaCha20Poly1305MetadataOnly),
+                ))
+            }
+            Some(key) => Ok((
+                encrypt_if_enabled(&serde_json::to_string(addresses)?, Some(key))?,
+                Some(EncryptionScheme::ChaCha20Poly1305),
+            )),
+        }
+    }
 
-        // Encrypt if key is provided
-        let content = encrypt_if_enabled(&json_content, encryption_key)?;
+    /// Decode metadata-only encrypted content
+    ///
+    /// Without a key the addresses are returned with `metadata: None`.
+    fn decode_metadata_only_content(
+        content: &str,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<BitcoinAddresses> {
+        let value: serde_json::Value = serde_json::from_str(content)?;
+        let encrypted_metadata = value
+            .get(ENCRYPTED_METADATA_FIELD)
+            .and_then(|field| field.as_str())
+            .map(String::from);
+
+        let mut addresses: BitcoinAddresses = serde_json::from_value(value)?;
+
+        if let (Some(encrypted), Some(key)) = (encrypted_metadata, encryption_key) {
+            let metadata_json = UbaEncryption::new(*key).decrypt(&encrypted)?;
+            addresses.metadata = Some(serde_json::from_str(&metadata_json)?);
+        }
+
+        Ok(addresses)
+    }
 
+    /// Build and sign a UBA event around already prepared content
+    fn build_event(
+        &self,
+        addresses: &BitcoinAddresses,
+        content: String,
+        scheme: Option<EncryptionScheme>,
+        extra_tags: Vec<Tag>,
+    ) -> Result<Event> {
         // Create a custom event for UBA data
-        let kind = Kind::Custom(30000); // Parametrized replaceable event
+        let kind = self.event_kind;
 
         let mut tags = Vec::new();
 
@@ -195,16 +734,22 @@ impl NostrClient {
                 .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
         );
 
-        // Add encryption indicator if encrypted
-        if encryption_key.is_some() {
+        // Add encryption indicator and scheme if encrypted
+        let metadata_encrypted = scheme == Some(EncryptionScheme::ChaCha20Poly1305MetadataOnly);
+        if let Some(scheme) = scheme {
+            let encrypted = if metadata_encrypted { "metadata" } else { "true" };
+            tags.push(
+                Tag::parse(&["encrypted", encrypted])
+                    .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
+            );
             tags.push(
-                Tag::parse(&["encrypted", "true"])
+                Tag::parse(&["scheme", scheme.tag_value()])
                     .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
             );
         }
 
-        // Add metadata tags if available
-        if let Some(metadata) = &addresses.metadata {
+        // Add metadata tags if available, unless the metadata is meant to be private
+        if let Some(metadata) = addresses.metadata.as_ref().filter(|_| !metadata_encrypted) {
             if let Some(label) = &metadata.label {
                 tags.push(
                     Tag::parse(&["label", label])
@@ -219,17 +764,64 @@ impl NostrClient {
                 .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
         );
 
-        let event = EventBuilder::new(kind, content, tags)
+        tags.extend(extra_tags);
+
+        self.sign_event(EventBuilder::new(kind, content, tags))
+    }
+
+    /// Get this client's secret key, needed for NIP-04 encryption
+    fn secret_key(&self) -> Result<&SecretKey> {
+        self.keys
+            .secret_key()
+            .map_err(|e| UbaError::Encryption(format!("Client keys have no secret key: {}", e)))
+    }
+
+    /// Decrypt the NIP-04 content of a UBA event with this client's keys
+    ///
+    /// The shared secret is computed with the event author, or with the tagged
+    /// recipient when this client authored the event.
+    fn decrypt_nip04_event(&self, event: &Event) -> Result<String> {
+        let counterparty = if event.pubkey == self.keys.public_key() {
+            let recipient = event
+                .tags
+                .iter()
+                .map(|tag| tag.as_vec())
+                .find(|tag_vec| tag_vec.len() >= 2 && tag_vec[0] == "p")
+                .map(|tag_vec| tag_vec[1].clone())
+                .ok_or_else(|| {
+                    UbaError::Encryption("NIP-04 event has no recipient tag".to_string())
+                })?;
+            PublicKey::from_hex(&recipient)
+                .map_err(|e| UbaError::Encryption(format!("Invalid recipient key: {}", e)))?
+        } else {
+            event.pubkey
+        };
+
+        nip04_decrypt(self.secret_key()?, &counterparty, &event.content)
+    }
+
+    /// Sign an event with this client's keys, applying the fixed timestamp if set
+    fn sign_event(&self, builder: EventBuilder) -> Result<Event> {
+        let builder = match self.fixed_timestamp {
+            Some(timestamp) => builder.custom_created_at(Timestamp::from(timestamp)),
+            None => builder,
+        };
+
+        builder
             .to_event(&self.keys)
-            .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
+            .map_err(|e| UbaError::NostrRelay(e.to_string()))
+    }
 
-        // Publish the event with timeout
-        let event_id = timeout(self.timeout_duration, self.client.send_event(event))
-            .await
-            .map_err(|_| UbaError::Timeout)?
-            .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
+    /// Ask relays to delete an event published by this client (NIP-09)
+    ///
+    /// Relays only honour deletions signed by the event's author and may ignore
+    /// them altogether, so a successful publish does not guarantee removal.
+    pub async fn delete_event(&self, event_id_hex: &str) -> Result<PublishOutput> {
+        let event_id = EventId::from_hex(event_id_hex)
+            .map_err(|e| UbaError::InvalidUbaFormat(format!("Invalid event ID: {}", e)))?;
 
-        Ok(event_id.to_hex())
+        let event = self.sign_event(EventBuilder::delete([event_id]))?;
+        self.send_event(event).await
     }
 
     /// Update Bitcoin addresses by creating a new event that replaces the old one
@@ -246,96 +838,110 @@ impl NostrClient {
         self.verify_event_exists(original_event_id).await?;
 
         // Validate the updated addresses
+        let updated_addresses = &*self.publishable(updated_addresses);
         self.validate_address_update(updated_addresses)?;
 
-        // Serialize addresses to JSON
-        let json_content = serde_json::to_string(updated_addresses)?;
-
-        // Encrypt if key is provided
-        let content = encrypt_if_enabled(&json_content, encryption_key)?;
-
-        // Create a custom event for UBA data
-        let kind = Kind::Custom(30000); // Parametrized replaceable event
-
-        let mut tags = Vec::new();
-
-        // Add a tag to identify this as UBA data
-        tags.push(
-            Tag::parse(&["uba", "bitcoin-addresses"])
-                .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
-        );
+        let (content, scheme) = self.encode_content(updated_addresses, encryption_key)?;
 
-        // Add a tag to reference the original event being replaced
-        tags.push(
+        let extra_tags = vec![
+            // Reference the original event being replaced
             Tag::parse(&["replaces", original_event_id])
                 .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
-        );
+            // Add update timestamp
+            Tag::parse(&["updated_at", &updated_addresses.created_at.to_string()])
+                .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
+        ];
 
-        // Add encryption indicator if encrypted
-        if encryption_key.is_some() {
-            tags.push(
-                Tag::parse(&["encrypted", "true"])
-                    .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
-            );
-        }
+        let event = self.build_event(updated_addresses, content, scheme, extra_tags)?;
 
-        // Add metadata tags if available
-        if let Some(metadata) = &updated_addresses.metadata {
-            if let Some(label) = &metadata.label {
-                tags.push(
-                    Tag::parse(&["label", label])
-                        .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
-                );
-            }
-        }
+        // Publish the event with timeout
+        let output = self.send_event(event).await?;
 
-        // Add version tag
-        tags.push(
-            Tag::parse(&["version", &updated_addresses.version.to_string()])
-                .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
-        );
+        Ok(output.event_id.to_hex())
+    }
 
-        // Add update timestamp
-        tags.push(
-            Tag::parse(&["updated_at", &updated_addresses.created_at.to_string()])
-                .map_err(|e| UbaError::NostrRelay(e.to_string()))?,
-        );
+    /// Re-encrypt a published UBA under a new key
+    ///
+    /// The event is decrypted strictly with `old_key` and republished as an update
+    /// encrypted with `new_key`. Nothing is published if `old_key` cannot decrypt it.
+    /// Returns the ID of the replacing event.
+    ///
+    /// The replacement gets a new `created_at`, which the payload signature covers,
+    /// and no seed is at hand to re-sign it, so re-encryption drops the signature.
+    /// Readers requiring `expected_signer` reject the result as unsigned; use
+    /// `update_uba` with the seed to publish a re-signed collection instead.
+    pub async fn reencrypt_addresses(
+        &self,
+        event_id_hex: &str,
+        old_key: &[u8; 32],
+        new_key: &[u8; 32],
+    ) -> Result<String> {
+        let event = self.fetch_event(event_id_hex).await?;
+        let mut addresses = Self::decrypt_addresses_strict(&event, old_key)?;
 
-        let event = EventBuilder::new(kind, content, tags)
-            .to_event(&self.keys)
-            .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
+        addresses.created_at = match self.fixed_timestamp {
+            Some(timestamp) => timestamp,
+            None => Timestamp::now().as_u64(),
+        };
+        addresses.signature = None;
 
-        // Publish the event with timeout
-        let event_id = timeout(self.timeout_duration, self.client.send_event(event))
+        self.update_addresses(event_id_hex, &addresses, Some(new_key))
             .await
-            .map_err(|_| UbaError::Timeout)?
-            .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
+    }
+
+    /// Decrypt a UBA event with a key that must be the one it was encrypted with
+    ///
+    /// Unlike regular retrieval, this never falls back to treating the content as
+    /// plaintext, so a wrong key is reported as `UbaError::Encryption`.
+    fn decrypt_addresses_strict(event: &Event, key: &[u8; 32]) -> Result<BitcoinAddresses> {
+        if has_tag(event, "sharded") {
+            return Err(UbaError::Encryption(
+                "Sharded UBAs cannot be re-encrypted".to_string(),
+            ));
+        }
 
-        Ok(event_id.to_hex())
+        let scheme = tag_value(event, "scheme")
+            .as_deref()
+            .and_then(EncryptionScheme::from_tag_value);
+        match scheme {
+            Some(EncryptionScheme::Nip04) => Err(UbaError::Encryption(
+                "NIP-04 events are not encrypted with a symmetric key".to_string(),
+            )),
+            Some(EncryptionScheme::ChaCha20Poly1305MetadataOnly) => {
+                Self::decode_metadata_only_content(&event.content, Some(key))
+            }
+            _ => {
+                if tag_value(event, "encrypted").as_deref() != Some("true") {
+                    return Err(UbaError::Encryption("Event is not encrypted".to_string()));
+                }
+                let content = UbaEncryption::new(*key).decrypt(&event.content)?;
+                serde_json::from_str(&content).map_err(|e| {
+                    UbaError::Encryption(format!("Decrypted content is not UBA data: {}", e))
+                })
+            }
+        }
     }
 
-    /// Verify that an event exists and is accessible
-    async fn verify_event_exists(&self, event_id_hex: &str) -> Result<()> {
+    /// Check whether any connected relay has the UBA event with the given ID
+    ///
+    /// Issues a single `limit(1)` query for the ID. The event is not decrypted or
+    /// parsed, so no encryption key is needed.
+    pub async fn event_exists(&self, event_id_hex: &str) -> Result<bool> {
         let event_id = EventId::from_hex(event_id_hex)
             .map_err(|e| UbaError::InvalidUbaFormat(format!("Invalid event ID: {}", e)))?;
 
         // Create a filter to find the specific event
         let filter = Filter::new()
             .id(event_id)
-            .kind(Kind::Custom(30000))
+            .kind(self.event_kind)
             .limit(1);
 
-        // Try to retrieve the event
-        let events = timeout(
-            self.timeout_duration,
-            self.client
-                .get_events_of(vec![filter], Some(self.timeout_duration)),
-        )
-        .await
-        .map_err(|_| UbaError::Timeout)?
-        .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
+        Ok(!self.query_events(filter).await?.is_empty())
+    }
 
-        if events.is_empty() {
+    /// Verify that an event exists and is accessible
+    async fn verify_event_exists(&self, event_id_hex: &str) -> Result<()> {
+        if !self.event_exists(event_id_hex).await? {
             return Err(UbaError::EventNotFound(format!(
                 "Event with ID {} not found",
                 event_id_hex
@@ -374,86 +980,309 @@ impl NostrClient {
             }
         }
 
+        // Structurally invalid node ids and npubs are never published; other
+        // Lightning and Nostr formats are left to `AddressGenerator::validate_addresses`
+        for (address_type, index, address) in addresses.iter_typed() {
+            if let Err(reason) = check_entry_format(&address_type, address, false) {
+                return Err(UbaError::UpdateValidation(format!(
+                    "Invalid {:?} entry at index {} ({}): {}",
+                    address_type, index, address, reason
+                )));
+            }
+        }
+
+        // A repeated address points at a derivation collision or a copy-paste error
+        if let Some((address, types)) = addresses.find_duplicates().into_iter().next() {
+            return Err(UbaError::UpdateValidation(format!(
+                "Address {} appears more than once (in {:?})",
+                address, types
+            )));
+        }
+
         Ok(())
     }
 
     /// Retrieve Bitcoin addresses from a Nostr event ID
     pub async fn retrieve_addresses(&self, event_id_hex: &str) -> Result<BitcoinAddresses> {
-        let event_id = EventId::from_hex(event_id_hex)
-            .map_err(|e| UbaError::InvalidUbaFormat(format!("Invalid event ID: {}", e)))?;
-
-        // Create a filter to find the specific event
-        let filter = Filter::new()
-            .id(event_id)
-            .kind(Kind::Custom(30000))
-            .limit(1);
+        self.retrieve_addresses_with_decryption(event_id_hex, None).await
+    }
 
-        // Subscribe to the filter with timeout
-        let events = timeout(
-            self.timeout_duration,
-            self.client
-                .get_events_of(vec![filter], Some(self.timeout_duration)),
-        )
-        .await
-        .map_err(|_| UbaError::Timeout)?
-        .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
+    /// Retrieve Bitcoin addresses with optional decryption
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, encryption_key))
+    )]
+    pub async fn retrieve_addresses_with_decryption(
+        &self,
+        event_id_hex: &str,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<BitcoinAddresses> {
+        let event = self.fetch_event(event_id_hex).await?;
+        self.decode_event(&event, encryption_key).await
+    }
 
-        if events.is_empty() {
-            return Err(UbaError::NoteNotFound(event_id_hex.to_string()));
+    /// Decode the addresses of an already fetched UBA event, fetching its parts
+    /// first if it is the manifest of a sharded UBA
+    pub(crate) async fn decode_event(
+        &self,
+        event: &Event,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<BitcoinAddresses> {
+        if has_tag(event, "sharded") {
+            return self.retrieve_shards(event, encryption_key).await;
         }
+        self.decode_addresses_event(event, encryption_key)
+    }
 
-        let event = &events[0];
+    /// Retrieve the addresses of many UBA events with a single relay query
+    ///
+    /// Results are returned in the order of `event_ids`. An invalid or missing
+    /// event only fails its own entry (`UbaError::NoteNotFound` when no relay has
+    /// it); the outer `Result` fails only if the query itself does.
+    pub async fn retrieve_many_with_decryption(
+        &self,
+        event_ids: &[String],
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<Vec<Result<BitcoinAddresses>>> {
+        let parsed: Vec<Result<EventId>> = event_ids
+            .iter()
+            .map(|id| {
+                EventId::from_hex(id)
+                    .map_err(|e| UbaError::InvalidUbaFormat(format!("Invalid event ID: {}", e)))
+            })
+            .collect();
+
+        let mut ids: Vec<EventId> =
+            parsed.iter().filter_map(|id| id.as_ref().ok()).copied().collect();
+        ids.sort();
+        ids.dedup();
+        let events = if ids.is_empty() {
+            Vec::new()
+        } else {
+            let filter = Filter::new().ids(ids.clone()).kind(self.event_kind).limit(ids.len());
+            self.query_events(filter).await?
+        };
 
-        // Verify this is UBA data by checking tags
-        let has_uba_tag = event.tags.iter().any(|tag| {
-            let tag_vec = tag.as_vec();
-            tag_vec.len() >= 2 && tag_vec[0] == "uba" && tag_vec[1] == "bitcoin-addresses"
-        });
+        let mut results = Vec::with_capacity(event_ids.len());
+        for (event_id_hex, event_id) in event_ids.iter().zip(parsed) {
+            let result = match event_id {
+                Ok(event_id) => match events.iter().find(|event| event.id == event_id) {
+                    Some(event) if has_tag(event, "sharded") => {
+                        self.retrieve_shards(event, encryption_key).await
+                    }
+                    Some(event) => self.decode_addresses_event(event, encryption_key),
+                    None => Err(UbaError::NoteNotFound(event_id_hex.clone())),
+                },
+                Err(e) => Err(e),
+            };
+            results.push(result);
+        }
 
-        if !has_uba_tag {
-            return Err(UbaError::InvalidUbaFormat(
-                "Event is not UBA data".to_string(),
-            ));
+        Ok(results)
+    }
+
+    /// Fetch and reassemble the parts listed in a shard manifest event
+    async fn retrieve_shards(
+        &self,
+        manifest_event: &Event,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<BitcoinAddresses> {
+        let manifest: ShardManifest = serde_json::from_str(&manifest_event.content)?;
+
+        let part_ids = manifest
+            .parts
+            .iter()
+            .map(|id| {
+                EventId::from_hex(id)
+                    .map_err(|e| UbaError::InvalidUbaFormat(format!("Invalid part ID: {}", e)))
+            })
+            .collect::<Result<Vec<EventId>>>()?;
+
+        let filter = Filter::new()
+            .ids(part_ids.clone())
+            .kind(self.event_kind)
+            .limit(part_ids.len());
+        let events = self.query_events(filter).await?;
+
+        let mut combined: Option<BitcoinAddresses> = None;
+        for part_id in part_ids {
+            // Parts must come from the manifest author and belong to its group
+            let event = events
+                .iter()
+                .find(|event| {
+                    event.id == part_id
+                        && event.pubkey == manifest_event.pubkey
+                        && tag_value(event, "group") == Some(manifest.group.clone())
+                })
+                .ok_or_else(|| UbaError::NoteNotFound(part_id.to_hex()))?;
+
+            let part = self.decode_addresses_event(event, encryption_key)?;
+            match combined.as_mut() {
+                None => combined = Some(part),
+                Some(combined) => {
+                    // Parts are disjoint, so addresses can be appended directly
+                    for (address_type, addresses) in part.addresses {
+                        combined
+                            .addresses
+                            .entry(address_type)
+                            .or_default()
+                            .extend(addresses);
+                    }
+                    for (type_name, addresses) in part.unknown {
+                        combined.unknown.entry(type_name).or_default().extend(addresses);
+                    }
+                }
+            }
         }
 
-        // Deserialize the content
-        let addresses: BitcoinAddresses =
-            serde_json::from_str(&event.content).map_err(UbaError::Json)?;
+        combined.ok_or_else(|| {
+            UbaError::InvalidUbaFormat("Shard manifest lists no parts".to_string())
+        })
+    }
 
-        Ok(addresses)
+    /// Retrieve a UBA event together with the updates that replaced it
+    ///
+    /// Updates are events by the same author whose `replaces` tag points at the
+    /// previous event in the chain. `bounds` limits the scan for updates so that a
+    /// long-lived UBA does not pull in an unbounded number of events. The result is
+    /// ordered from the original event to the most recent update.
+    pub async fn retrieve_history(
+        &self,
+        event_id_hex: &str,
+        encryption_key: Option<&[u8; 32]>,
+        bounds: &RetrievalBounds,
+    ) -> Result<Vec<BitcoinAddresses>> {
+        let original = self.fetch_event(event_id_hex).await?;
+
+        let mut filter = Filter::new()
+            .kind(self.event_kind)
+            .author(original.pubkey);
+        if let Some(since) = bounds.since {
+            filter = filter.since(Timestamp::from(since));
+        }
+        if let Some(until) = bounds.until {
+            filter = filter.until(Timestamp::from(until));
+        }
+        if let Some(limit) = bounds.limit {
+            filter = filter.limit(limit);
+        }
+
+        let candidates = self.query_events(filter).await?;
+
+        let mut chain = vec![self.decode_addresses_event(&original, encryption_key)?];
+        for event in replacement_chain(&original, &candidates) {
+            chain.push(self.decode_addresses_event(event, encryption_key)?);
+        }
+
+        Ok(chain)
     }
 
-    /// Retrieve Bitcoin addresses with optional decryption
-    pub async fn retrieve_addresses_with_decryption(
+    /// Check whether a UBA event was replaced by an update newer than `since`
+    ///
+    /// `since` is a Unix timestamp in seconds, typically the `created_at` of the
+    /// cached collection. Updates are found as in `retrieve_history`, but only
+    /// their timestamps are inspected: nothing is decrypted or deserialized, so
+    /// this is a cheap poll before a full retrieve.
+    pub async fn has_update(&self, event_id_hex: &str, since: u64) -> Result<bool> {
+        let original = self.fetch_event(event_id_hex).await?;
+
+        // Updates cannot predate the event they replace
+        let filter = Filter::new()
+            .kind(self.event_kind)
+            .author(original.pubkey)
+            .since(original.created_at);
+        let candidates = self.query_events(filter).await?;
+
+        Ok(replacement_chain(&original, &candidates)
+            .iter()
+            .any(|event| event.created_at.as_u64() > since))
+    }
+
+    /// Fetch a UBA event without decoding its address data
+    ///
+    /// A debugging aid for events that fail to retrieve: returns the raw content,
+    /// tags and author. Encrypted content is also decrypted when possible (NIP-04
+    /// with this client's keys, other schemes with `encryption_key`), but never
+    /// deserialized; a failed decryption is reported in `decryption_error` instead
+    /// of failing the call.
+    pub async fn fetch_raw_event(
         &self,
         event_id_hex: &str,
         encryption_key: Option<&[u8; 32]>,
-    ) -> Result<BitcoinAddresses> {
+    ) -> Result<RawUbaEvent> {
+        let event = self.fetch_event(event_id_hex).await?;
+
+        let scheme = tag_value(&event, "scheme")
+            .as_deref()
+            .and_then(EncryptionScheme::from_tag_value);
+        let is_encrypted = tag_value(&event, "encrypted").as_deref() == Some("true");
+
+        let decrypted = match (scheme, encryption_key) {
+            (Some(EncryptionScheme::Nip04), _) => Some(self.decrypt_nip04_event(&event)),
+            (Some(EncryptionScheme::ChaCha20Poly1305MetadataOnly), Some(key)) => {
+                Some(Self::decrypt_metadata_block(&event.content, key))
+            }
+            (_, Some(key)) if is_encrypted => {
+                Some(UbaEncryption::new(*key).decrypt(&event.content))
+            }
+            _ => None,
+        };
+        let (decrypted_content, decryption_error) = match decrypted {
+            Some(Ok(content)) => (Some(content), None),
+            Some(Err(e)) => (None, Some(e.to_string())),
+            None => (None, None),
+        };
+
+        Ok(RawUbaEvent {
+            event_id: event.id.to_hex(),
+            author: event.pubkey.to_hex(),
+            created_at: event.created_at.as_u64(),
+            kind: event.kind.as_u16(),
+            tags: event.tags.iter().map(|tag| tag.as_vec().to_vec()).collect(),
+            content: event.content.clone(),
+            decrypted_content,
+            decryption_error,
+        })
+    }
+
+    /// Decrypt the metadata block of metadata-only encrypted content
+    fn decrypt_metadata_block(content: &str, encryption_key: &[u8; 32]) -> Result<String> {
+        let value: serde_json::Value = serde_json::from_str(content)?;
+        let encrypted = value
+            .get(ENCRYPTED_METADATA_FIELD)
+            .and_then(|field| field.as_str())
+            .ok_or_else(|| UbaError::Encryption("Event has no encrypted metadata".to_string()))?;
+        UbaEncryption::new(*encryption_key).decrypt(encrypted)
+    }
+
+    /// Fetch a single UBA event by its hex ID
+    pub(crate) async fn fetch_event(&self, event_id_hex: &str) -> Result<Event> {
         let event_id = EventId::from_hex(event_id_hex)
             .map_err(|e| UbaError::InvalidUbaFormat(format!("Invalid event ID: {}", e)))?;
 
         // Create a filter to find the specific event
         let filter = Filter::new()
             .id(event_id)
-            .kind(Kind::Custom(30000))
+            .kind(self.event_kind)
             .limit(1);
 
         // Subscribe to the filter with timeout
-        let events = timeout(
-            self.timeout_duration,
-            self.client
-                .get_events_of(vec![filter], Some(self.timeout_duration)),
-        )
-        .await
-        .map_err(|_| UbaError::Timeout)?
-        .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
-
-        if events.is_empty() {
-            return Err(UbaError::NoteNotFound(event_id_hex.to_string()));
-        }
-
-        let event = &events[0];
+        self.query_events(filter)
+            .await?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                trace_warn!(event_id = event_id_hex, "Event not found on any relay");
+                UbaError::NoteNotFound(event_id_hex.to_string())
+            })
+    }
 
+    /// Verify, decrypt and deserialize the address data of a UBA event
+    fn decode_addresses_event(
+        &self,
+        event: &Event,
+        encryption_key: Option<&[u8; 32]>,
+    ) -> Result<BitcoinAddresses> {
         // Verify this is UBA data by checking tags
         let has_uba_tag = event.tags.iter().any(|tag| {
             let tag_vec = tag.as_vec();
@@ -472,17 +1301,56 @@ impl NostrClient {
             tag_vec.len() >= 2 && tag_vec[0] == "encrypted" && tag_vec[1] == "true"
         });
 
+        // Events without a scheme tag predate NIP-04 support and use ChaCha20Poly1305
+        let scheme = match event
+            .tags
+            .iter()
+            .map(|tag| tag.as_vec())
+            .find(|tag_vec| tag_vec.len() >= 2 && tag_vec[0] == "scheme")
+        {
+            Some(tag_vec) => Some(EncryptionScheme::from_tag_value(&tag_vec[1]).ok_or_else(
+                || UbaError::Encryption(format!("Unsupported encryption scheme: {}", tag_vec[1])),
+            )?),
+            None => None,
+        };
+
+        if scheme == Some(EncryptionScheme::ChaCha20Poly1305MetadataOnly) {
+            return Self::decode_metadata_only_content(&event.content, encryption_key)
+                .and_then(check_format_version);
+        }
+
+        trace_debug!(event_id = %event.id, encrypted = is_encrypted, scheme = ?scheme, "Decoding event");
+
+        // NIP-04 events are decrypted with this client's keys; anything else tagged as
+        // encrypted is unreadable without a key, so say so instead of failing to parse
+        if is_encrypted && encryption_key.is_none() && scheme != Some(EncryptionScheme::Nip04) {
+            return Err(UbaError::Encryption(
+                "Event is encrypted but no key provided".to_string(),
+            ));
+        }
+
         // Decrypt if needed
-        let content = if is_encrypted || encryption_key.is_some() {
+        let content = if scheme == Some(EncryptionScheme::Nip04) {
+            self.decrypt_nip04_event(event)?
+        } else if is_encrypted || encryption_key.is_some() {
             decrypt_if_needed(&event.content, encryption_key)?
         } else {
             event.content.clone()
         };
 
         // Deserialize the content
-        let addresses: BitcoinAddresses = serde_json::from_str(&content).map_err(UbaError::Json)?;
-
-        Ok(addresses)
+        match serde_json::from_str::<BitcoinAddresses>(&content) {
+            Ok(addresses) => check_format_version(addresses),
+            Err(e) => {
+                trace_warn!(
+                    event_id = %event.id,
+                    encrypted = is_encrypted,
+                    error = %e,
+                    "Event content is not valid address data (wrong or missing key?)"
+                );
+                Err(UbaError::Json(e))
+            }
+        }
     }
 
     /// Get the public key of this client
@@ -492,28 +1360,160 @@ impl NostrClient {
 
     /// Disconnect from all relays
     pub async fn disconnect(&self) {
-        let _ = self.client.disconnect().await;
+        self.transport.disconnect().await;
+        self.connected.store(false, Ordering::Relaxed);
+    }
+
+    /// Connect to the relays, run `operation` and disconnect, whatever the outcome
+    ///
+    /// Relays are disconnected even if connecting or the operation fails, so early
+    /// returns cannot leave connections open.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// use uba::{BitcoinAddresses, NostrClient};
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let client = NostrClient::new(10)?;
+    ///     let relays = vec!["wss://relay.damus.io".to_string()];
+    ///     let addresses = BitcoinAddresses::new();
+    ///
+    ///     let event_id = client
+    ///         .with_connection(&relays, |client| client.publish_addresses(&addresses, false))
+    ///         .await?;
+    ///     println!("Published {}", event_id);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn with_connection<'a, R, F, Fut>(
+        &'a self,
+        relay_urls: &[String],
+        operation: F,
+    ) -> Result<R>
+    where
+        F: FnOnce(&'a Self) -> Fut,
+        Fut: Future<Output = Result<R>>,
+    {
+        let result = match self.connect_to_relays(relay_urls).await {
+            Ok(()) => operation(self).await,
+            Err(e) => Err(e),
+        };
+        self.disconnect().await;
+        result
+    }
+}
+
+impl<T: RelayTransport> Drop for NostrClient<T> {
+    fn drop(&mut self) {
+        if *self.connected.get_mut() {
+            self.transport.disconnect_in_background();
+        }
+    }
+}
+
+impl<T: RelayTransport + Clone> NostrClient<T> {
+    /// Create a client that shares this client's relay connections and settings
+    /// but signs with `keys`
+    ///
+    /// Used to publish events for many seeds over one connection. The returned
+    /// client never disconnects the shared connections; that is left to this one.
+    pub fn with_signer(&self, keys: Keys) -> Self {
+        Self {
+            transport: self.transport.clone(),
+            keys,
+            timeout_duration: self.timeout_duration,
+            connect_timeout: self.connect_timeout,
+            publish_timeout: self.publish_timeout,
+            query_timeout: self.query_timeout,
+            max_retry_attempts: self.max_retry_attempts,
+            retry_delay_ms: self.retry_delay_ms,
+            fixed_timestamp: self.fixed_timestamp,
+            metadata_only_encryption: self.metadata_only_encryption,
+            strip_metadata: self.strip_metadata,
+            event_kind: self.event_kind,
+            connected: AtomicBool::new(false),
+        }
+    }
+}
+
+/// Get the ID of the event a UBA update replaces, if it is an update
+fn replaced_event_id(event: &Event) -> Option<String> {
+    tag_value(event, "replaces")
+}
+
+/// Follow the `replaces` chain from `original` through `candidates`
+///
+/// If an event was replaced more than once, the most recent replacement wins.
+/// The updates are returned oldest first, without `original`.
+fn replacement_chain<'a>(original: &Event, candidates: &'a [Event]) -> Vec<&'a Event> {
+    let mut chain = Vec::new();
+    let mut current_id = original.id;
+    loop {
+        let next = candidates
+            .iter()
+            .filter(|event| {
+                event.id != current_id && replaced_event_id(event) == Some(current_id.to_hex())
+            })
+            .max_by_key(|event| event.created_at);
+
+        match next {
+            // The chain can never be longer than the candidate set, which also
+            // guards against cycles
+            Some(event) if chain.len() < candidates.len() => {
+                chain.push(event);
+                current_id = event.id;
+            }
+            _ => break,
+        }
     }
+    chain
+}
+
+/// Get the first value of the first tag with the given name
+fn tag_value(event: &Event, name: &str) -> Option<String> {
+    event
+        .tags
+        .iter()
+        .map(|tag| tag.as_vec())
+        .find(|tag_vec| tag_vec.len() >= 2 && tag_vec[0] == name)
+        .map(|tag_vec| tag_vec[1].clone())
+}
+
+/// Reject address data written in a newer format than this build understands
+///
+/// Newer formats may change the meaning of existing fields, so reading them as
+/// the current version could silently drop or misread addresses.
+fn check_format_version(addresses: BitcoinAddresses) -> Result<BitcoinAddresses> {
+    if addresses.version > MAX_FORMAT_VERSION {
+        return Err(UbaError::InvalidUbaFormat(format!(
+            "unsupported version {} (this build supports up to {})",
+            addresses.version, MAX_FORMAT_VERSION
+        )));
+    }
+    Ok(addresses)
+}
+
+/// Check whether the event has a tag with the given name
+fn has_tag(event: &Event, name: &str) -> bool {
+    tag_value(event, name).is_some()
 }
 
 /// Generate a deterministic Nostr key from a seed
+///
+/// The same seed always produces the same Nostr identity. Mnemonics are used
+/// without a BIP39 passphrase; see `generate_nostr_keys_from_seed_with_config`.
 pub fn generate_nostr_keys_from_seed(seed: &str) -> Result<Keys> {
-    // Use the seed to generate deterministic keys
-    // This ensures the same seed always produces the same Nostr identity
-    use bitcoin::hashes::{sha256, Hash};
-
-    let seed_bytes = if seed.len() == 64 {
-        // Assume hex-encoded
-        hex::decode(seed)?
-    } else {
-        // Use BIP39 seed
-        let mnemonic = bip39::Mnemonic::from_str(seed)?;
-        mnemonic.to_seed("").to_vec()
-    };
-
-    // Hash the seed to get a 32-byte key
-    let hash = sha256::Hash::hash(&seed_bytes);
-    let secret_key = nostr::SecretKey::from_slice(hash.as_ref())
+    generate_nostr_keys_from_seed_with_config(seed, &UbaConfig::default())
+}
+
+/// Generate a deterministic Nostr key from a seed and the BIP39 passphrase in `config`
+///
+/// Different passphrases give different identities, matching the different
+/// wallets they unlock.
+pub fn generate_nostr_keys_from_seed_with_config(seed: &str, config: &UbaConfig) -> Result<Keys> {
+    let secret_key = crate::address::identity_secret_key(seed, config)?;
+    let secret_key = nostr::SecretKey::from_slice(&secret_key.secret_bytes())
         .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
 
     Ok(Keys::new(secret_key))
@@ -557,6 +1557,691 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_compute_event_id_matches_published_event() {
+        use crate::testing::MockRelay;
+
+        let relay = MockRelay::new();
+        let client = relay.client(Keys::generate(), 10);
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+
+        let event = client.build_addresses_event(&addresses, None).unwrap();
+        let preview = event.id.to_hex();
+        assert_eq!(preview.len(), 64);
+
+        client.connect_to_relays(&["wss://mock.relay".to_string()]).await.unwrap();
+        let output = client.publish_event(event).await.unwrap();
+        assert_eq!(output.event_id.to_hex(), preview);
+
+        // compute_event_id signs locally and never publishes
+        let id = client.compute_event_id(&addresses, None).unwrap();
+        assert_eq!(id.len(), 64);
+        assert_eq!(relay.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_publish_oversized_event_reports_size() {
+        use crate::testing::MockRelay;
+
+        let relay = MockRelay::new();
+        relay.set_max_event_size(200);
+        let client = relay.client(Keys::generate(), 10);
+        client.connect_to_relays(&["wss://mock.relay".to_string()]).await.unwrap();
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+        let event = client.build_addresses_event(&addresses, None).unwrap();
+        let expected_size = nostr::JsonUtil::as_json(&event).len();
+
+        match client.publish_event(event).await {
+            Err(UbaError::EventTooLarge { size, message }) => {
+                assert_eq!(size, expected_size);
+                assert!(message.contains("wss://mock.relay"));
+            }
+            other => panic!("expected EventTooLarge, got {:?}", other),
+        }
+        assert!(relay.is_empty());
+    }
+
+    #[test]
+    fn test_nostr_keys_ignore_network_prefix() {
+        let key = "0101010101010101010101010101010101010101010101010101010101010101";
+        let bare = generate_nostr_keys_from_seed(key).unwrap();
+        let prefixed = generate_nostr_keys_from_seed(&format!("testnet:{}", key)).unwrap();
+        assert_eq!(bare.public_key(), prefixed.public_key());
+    }
+
+    #[tokio::test]
+    async fn test_select_fastest_relays() {
+        use crate::testing::MockRelay;
+
+        let relay = MockRelay::new();
+        relay.set_latency("wss://slow.relay", Duration::from_millis(300));
+        relay.set_latency("wss://fast.relay", Duration::from_millis(20));
+        relay.set_latency("wss://medium.relay", Duration::from_millis(90));
+        relay.set_unreachable("wss://down.relay");
+        let client = relay.client(Keys::generate(), 10);
+
+        let candidates: Vec<String> =
+            ["wss://slow.relay", "wss://down.relay", "wss://fast.relay", "wss://medium.relay"]
+                .iter()
+                .map(|url| url.to_string())
+                .collect();
+        assert_eq!(
+            client.select_fastest(&candidates, 2, Duration::from_secs(1)).await,
+            vec!["wss://fast.relay".to_string(), "wss://medium.relay".to_string()]
+        );
+        // Unreachable relays are never selected
+        assert_eq!(client.select_fastest(&candidates, 10, Duration::from_secs(1)).await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_raw_event() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let publisher = relay.client(Keys::generate(), 10);
+        let key = crate::encryption::generate_random_key();
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+        publisher.connect_to_relays(&relays).await.unwrap();
+        let event_id = publisher
+            .publish_addresses_with_encryption(&addresses, Some(&key))
+            .await
+            .unwrap();
+
+        let reader = relay.client(Keys::generate(), 10);
+        let raw = reader.fetch_raw_event(&event_id, None).await.unwrap();
+        assert_eq!(raw.event_id, event_id);
+        assert_eq!(raw.author, publisher.public_key());
+        assert_eq!(raw.kind, DEFAULT_EVENT_KIND);
+        assert!(raw.tags.iter().any(|tag| tag == &["encrypted", "true"]));
+        assert!(!raw.content.contains("bc1q"));
+        assert!(raw.decrypted_content.is_none() && raw.decryption_error.is_none());
+
+        let raw = reader.fetch_raw_event(&event_id, Some(&key)).await.unwrap();
+        assert!(raw
+            .decrypted_content
+            .unwrap()
+            .contains("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"));
+
+        // A wrong key is reported, not returned as an error
+        let raw = reader.fetch_raw_event(&event_id, Some(&[7u8; 32])).await.unwrap();
+        assert!(raw.decrypted_content.is_none());
+        assert!(raw.decryption_error.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_rejects_newer_format_version() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let client = relay.client(Keys::generate(), 10);
+        client.connect_to_relays(&relays).await.unwrap();
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+        let current_id = client.publish_addresses(&addresses, false).await.unwrap();
+        addresses.version = MAX_FORMAT_VERSION + 1;
+        let newer_id = client.publish_addresses(&addresses, false).await.unwrap();
+
+        assert!(client.retrieve_addresses(&current_id).await.is_ok());
+        match client.retrieve_addresses(&newer_id).await {
+            Err(UbaError::InvalidUbaFormat(message)) => {
+                assert!(message.contains(&format!("unsupported version {}", MAX_FORMAT_VERSION + 1)))
+            }
+            other => panic!("expected InvalidUbaFormat, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_nip04_publish_and_retrieve() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let publisher = relay.client(Keys::generate(), 10);
+        let recipient_keys = Keys::generate();
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+
+        publisher.connect_to_relays(&relays).await.unwrap();
+        let output = publisher
+            .publish_addresses_nip04(&addresses, &recipient_keys.public_key())
+            .await
+            .unwrap();
+        let event_id = output.event_id.to_hex();
+
+        // The stored event is a standard NIP-04 payload tagged with its scheme
+        let event = relay.events().pop().unwrap();
+        assert!(event.tags.iter().any(|tag| tag.as_vec() == ["scheme", "nip04"]));
+        let decrypted = nostr::nips::nip04::decrypt(
+            recipient_keys.secret_key().unwrap(),
+            &event.pubkey,
+            &event.content,
+        )
+        .unwrap();
+        assert_eq!(decrypted, serde_json::to_string(&addresses).unwrap());
+
+        // Both the recipient and the publisher can read it back
+        let recipient = relay.client(recipient_keys, 10);
+        let retrieved = recipient
+            .retrieve_addresses_with_decryption(&event_id, None)
+            .await
+            .unwrap();
+        assert_eq!(retrieved.addresses, addresses.addresses);
+
+        let retrieved = publisher
+            .retrieve_addresses_with_decryption(&event_id, None)
+            .await
+            .unwrap();
+        assert_eq!(retrieved.addresses, addresses.addresses);
+
+        // Anyone else cannot
+        let outsider = relay.client(Keys::generate(), 10);
+        assert!(outsider
+            .retrieve_addresses_with_decryption(&event_id, None)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connect_to_relays_detailed() {
+        use crate::testing::MockRelay;
+
+        let relay = MockRelay::new();
+        let client = relay.client(Keys::generate(), 10);
+        assert!(client.relay_statuses().await.is_empty());
+
+        let relays = vec![
+            "wss://b.mock.relay".to_string(),
+            "wss://a.mock.relay".to_string(),
+        ];
+        let statuses = client.connect_to_relays_detailed(&relays).await.unwrap();
+        assert_eq!(
+            statuses,
+            vec![
+                ("wss://a.mock.relay".to_string(), ConnectStatus::Connected),
+                ("wss://b.mock.relay".to_string(), ConnectStatus::Connected),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ping_configured_relays() {
+        use crate::testing::MockRelay;
+
+        let relay = MockRelay::new();
+        relay.set_unreachable("wss://b.mock.relay");
+        let client = relay.client(Keys::generate(), 10);
+
+        let mut config = UbaConfig::default();
+        config.set_custom_relays(vec![
+            "wss://a.mock.relay".to_string(),
+            "wss://b.mock.relay".to_string(),
+            "wss://c.mock.relay".to_string(),
+        ]);
+
+        let summary = client.ping_configured_relays(&config).await;
+        assert_eq!(summary.reachable, vec!["wss://a.mock.relay", "wss://c.mock.relay"]);
+        assert_eq!(summary.unreachable, vec!["wss://b.mock.relay"]);
+        assert_eq!(summary.latencies.len(), 2);
+        assert_eq!(summary.to_string(), "2/3 relays online");
+
+        // Pinging does not connect the client to anything
+        assert!(relay.connected_relays().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_on_drop_and_with_connection() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+
+        // Never connected: nothing to disconnect
+        drop(relay.client(Keys::generate(), 10));
+        assert_eq!(relay.disconnect_count(), 0);
+
+        let client = relay.client(Keys::generate(), 10);
+        client.connect_to_relays(&relays).await.unwrap();
+        drop(client);
+        assert_eq!(relay.disconnect_count(), 1);
+
+        // Disconnecting explicitly leaves nothing for drop to do
+        let client = relay.client(Keys::generate(), 10);
+        client.connect_to_relays(&relays).await.unwrap();
+        client.disconnect().await;
+        drop(client);
+        assert_eq!(relay.disconnect_count(), 2);
+
+        // The operation's error is returned after disconnecting
+        let client = relay.client(Keys::generate(), 10);
+        let missing = "0".repeat(64);
+        let result = client
+            .with_connection(&relays, |client| client.retrieve_addresses(&missing))
+            .await;
+        assert!(result.is_err());
+        assert_eq!(relay.disconnect_count(), 3);
+        drop(client);
+        assert_eq!(relay.disconnect_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_lightning_address_survives_retrieval() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let client = relay.client(Keys::generate(), 10);
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_lightning_address("satoshi@getalby.com").unwrap();
+        let event_id = client
+            .with_connection(&relays, |client| client.publish_addresses(&addresses, false))
+            .await
+            .unwrap();
+
+        let retrieved = client
+            .with_connection(&relays, |client| client.retrieve_addresses(&event_id))
+            .await
+            .unwrap();
+        assert_eq!(retrieved.lightning_addresses(), vec!["satoshi@getalby.com"]);
+        assert_eq!(
+            crate::address::classify_address("satoshi@getalby.com", bitcoin::Network::Bitcoin),
+            Some(AddressType::Lightning)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout_is_separate_from_publish() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        relay.set_query_delay(Duration::from_millis(200));
+
+        let mut client = relay.client(Keys::generate(), 10);
+        client.set_query_timeout(Some(Duration::from_millis(50)));
+        client.connect_to_relays(&relays).await.unwrap();
+
+        // Publishing is not a query and keeps the general timeout
+        let event_id = client.publish_addresses(&BitcoinAddresses::new(), false).await.unwrap();
+        assert!(matches!(client.retrieve_addresses(&event_id).await, Err(UbaError::Timeout)));
+
+        client.set_query_timeout(None);
+        assert!(client.retrieve_addresses(&event_id).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_metadata_only_encryption() {
+        use crate::testing::MockRelay;
+        use crate::types::AddressMetadata;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let mut publisher = relay.client(Keys::generate(), 10);
+        publisher.set_metadata_only_encryption(true);
+        let key = crate::encryption::generate_random_key();
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+        addresses.metadata = Some(AddressMetadata {
+            label: Some("private-label".to_string()),
+            description: None,
+            xpub: None,
+            derivation_paths: None,
+            fingerprint: None,
+        });
+
+        publisher.connect_to_relays(&relays).await.unwrap();
+        let event_id = publisher
+            .publish_addresses_with_encryption(&addresses, Some(&key))
+            .await
+            .unwrap();
+
+        // Addresses are public, the label appears neither in the content nor the tags
+        let event = relay.events().pop().unwrap();
+        assert!(event.content.contains("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"));
+        assert!(!event.content.contains("private-label"));
+        assert!(!event.tags.iter().any(|tag| tag.as_vec()[0] == "label"));
+
+        let reader = relay.client(Keys::generate(), 10);
+
+        // Without the key: addresses only
+        let retrieved = reader
+            .retrieve_addresses_with_decryption(&event_id, None)
+            .await
+            .unwrap();
+        assert_eq!(retrieved.addresses, addresses.addresses);
+        assert!(retrieved.metadata.is_none());
+
+        // With the key: addresses and metadata
+        let retrieved = reader
+            .retrieve_addresses_with_decryption(&event_id, Some(&key))
+            .await
+            .unwrap();
+        assert_eq!(retrieved.addresses, addresses.addresses);
+        assert_eq!(
+            retrieved.metadata.and_then(|m| m.label),
+            Some("private-label".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_encrypted_event_without_key() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let publisher = relay.client(Keys::generate(), 10);
+        let key = crate::encryption::generate_random_key();
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+
+        publisher.connect_to_relays(&relays).await.unwrap();
+        let event_id = publisher
+            .publish_addresses_with_encryption(&addresses, Some(&key))
+            .await
+            .unwrap();
+
+        let result = publisher.retrieve_addresses_with_decryption(&event_id, None).await;
+        match result {
+            Err(UbaError::Encryption(message)) => assert!(message.contains("no key provided")),
+            other => panic!("expected an encryption error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_strip_metadata_on_publish() {
+        use crate::testing::MockRelay;
+        use crate::types::AddressMetadata;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let mut publisher = relay.client(Keys::generate(), 10);
+        publisher.set_strip_metadata(true);
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+        addresses.metadata = Some(AddressMetadata {
+            label: Some("private-label".to_string()),
+            description: Some("savings".to_string()),
+            xpub: None,
+            derivation_paths: Some(vec!["m/84'/0'/0'/0".to_string()]),
+            fingerprint: None,
+        });
+
+        publisher.connect_to_relays(&relays).await.unwrap();
+        let event_id = publisher.publish_addresses_with_encryption(&addresses, None).await.unwrap();
+
+        // Nothing but the address map reaches the relay
+        let event = relay.events().pop().unwrap();
+        assert!(!event.content.contains("private-label"));
+        assert!(!event.content.contains("m/84'"));
+        assert!(!event.tags.iter().any(|tag| tag.as_vec()[0] == "label"));
+
+        let retrieved = publisher.retrieve_addresses(&event_id).await.unwrap();
+        assert_eq!(retrieved.addresses, addresses.addresses);
+        assert!(retrieved.metadata.is_none());
+
+        // The caller's copy keeps its metadata
+        assert!(addresses.metadata.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_history_follows_replaces_chain() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let mut client = relay.client(Keys::generate(), 10);
+        client.connect_to_relays(&relays).await.unwrap();
+
+        let mut ids: Vec<String> = Vec::new();
+        for (i, timestamp) in [1_000u64, 2_000, 3_000].into_iter().enumerate() {
+            let mut addresses = BitcoinAddresses::new();
+            addresses.created_at = timestamp;
+            addresses.add_address(AddressType::P2WPKH, format!("bc1qaddress{}", i));
+            client.set_fixed_timestamp(Some(timestamp));
+
+            let id = match ids.last() {
+                None => client
+                    .publish_addresses_with_encryption(&addresses, None)
+                    .await
+                    .unwrap(),
+                Some(previous) => client
+                    .update_addresses(previous, &addresses, None)
+                    .await
+                    .unwrap(),
+            };
+            ids.push(id);
+        }
+
+        // Unbounded: the whole chain, oldest first
+        let history = client
+            .retrieve_history(&ids[0], None, &RetrievalBounds::default())
+            .await
+            .unwrap();
+        let created: Vec<u64> = history.iter().map(|a| a.created_at).collect();
+        assert_eq!(created, vec![1_000, 2_000, 3_000]);
+
+        // Bounded: updates outside the window are not scanned
+        let bounds = RetrievalBounds {
+            until: Some(2_500),
+            ..Default::default()
+        };
+        let history = client.retrieve_history(&ids[0], None, &bounds).await.unwrap();
+        let created: Vec<u64> = history.iter().map(|a| a.created_at).collect();
+        assert_eq!(created, vec![1_000, 2_000]);
+    }
+
+    #[tokio::test]
+    async fn test_has_update_since() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let mut client = relay.client(Keys::generate(), 10);
+        client.connect_to_relays(&relays).await.unwrap();
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qoriginal".to_string());
+        client.set_fixed_timestamp(Some(1_000));
+        let original = client
+            .publish_addresses_with_encryption(&addresses, None)
+            .await
+            .unwrap();
+        assert!(!client.has_update(&original, 1_000).await.unwrap());
+
+        addresses.add_address(AddressType::P2WPKH, "bc1qupdated".to_string());
+        client.set_fixed_timestamp(Some(2_000));
+        let update = client.update_addresses(&original, &addresses, None).await.unwrap();
+        client.set_fixed_timestamp(Some(3_000));
+        client.update_addresses(&update, &addresses, None).await.unwrap();
+
+        // The second update is found through the first one
+        assert!(client.has_update(&original, 2_500).await.unwrap());
+        assert!(client.has_update(&update, 2_000).await.unwrap());
+        assert!(!client.has_update(&original, 3_000).await.unwrap());
+
+        // Updates published by someone else do not count
+        let mut other = relay.client(Keys::generate(), 10);
+        other.connect_to_relays(&relays).await.unwrap();
+        other.set_fixed_timestamp(Some(4_000));
+        other.update_addresses(&original, &addresses, None).await.unwrap();
+        assert!(!client.has_update(&original, 3_000).await.unwrap());
+    }
+
+    #[test]
+    fn test_estimated_event_size_matches_built_event() {
+        use crate::types::AddressMetadata;
+        use nostr::JsonUtil;
+
+        let mut client = NostrClient::with_keys(Keys::generate(), 10);
+        client.set_fixed_timestamp(Some(1_700_000_000));
+
+        let mut addresses = BitcoinAddresses::new();
+        for i in 0..50 {
+            addresses.add_address(AddressType::P2WPKH, format!("bc1qaddress{}", i));
+        }
+        addresses.metadata = Some(AddressMetadata {
+            label: Some("sized-wallet".to_string()),
+            description: Some("Quotes \"need\" escaping".to_string()),
+            xpub: None,
+            derivation_paths: None,
+            fingerprint: None,
+        });
+
+        let event = client.build_addresses_event(&addresses, None).unwrap();
+        assert_eq!(
+            addresses.estimated_event_size(None, DEFAULT_EVENT_KIND),
+            event.as_json().len()
+        );
+
+        let key = crate::encryption::generate_random_key();
+        let event = client.build_addresses_event(&addresses, Some(&key)).unwrap();
+        assert_eq!(
+            addresses.estimated_event_size(Some(&key), DEFAULT_EVENT_KIND),
+            event.as_json().len()
+        );
+
+        // A custom kind with a different width is accounted for
+        client.set_event_kind(1);
+        let event = client.build_addresses_event(&addresses, None).unwrap();
+        assert_eq!(addresses.estimated_event_size(None, 1), event.as_json().len());
+    }
+
+    #[tokio::test]
+    async fn test_sharded_publish_and_retrieve() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let client = relay.client(Keys::generate(), 10);
+        client.connect_to_relays(&relays).await.unwrap();
+
+        let mut addresses = BitcoinAddresses::new();
+        for i in 0..300 {
+            addresses.add_address(AddressType::P2WPKH, format!("bc1qaddress{:04}", i));
+        }
+
+        for key in [None, Some(crate::encryption::generate_random_key())] {
+            relay.clear();
+            let output = client
+                .publish_addresses_sharded(&addresses, key.as_ref(), 4_000)
+                .await
+                .unwrap();
+
+            // Several parts plus the manifest, each within the size limit
+            let events = relay.events();
+            assert!(events.len() > 2);
+            for event in &events {
+                assert!(nostr::JsonUtil::as_json(event).len() <= 4_000);
+            }
+
+            let reader = relay.client(Keys::generate(), 10);
+            let retrieved = reader
+                .retrieve_addresses_with_decryption(&output.event_id.to_hex(), key.as_ref())
+                .await
+                .unwrap();
+            assert_eq!(retrieved.addresses, addresses.addresses);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_event_kind() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let mut writer = relay.client(Keys::generate(), 10);
+        writer.set_event_kind(31234);
+        writer.connect_to_relays(&relays).await.unwrap();
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qaddress".to_string());
+        let event_id = writer
+            .publish_addresses_with_encryption(&addresses, None)
+            .await
+            .unwrap();
+        assert_eq!(relay.events()[0].kind, Kind::Custom(31234));
+
+        // A reader with the default kind does not see the event
+        let default_reader = relay.client(Keys::generate(), 10);
+        assert!(matches!(
+            default_reader.retrieve_addresses(&event_id).await,
+            Err(UbaError::NoteNotFound(_))
+        ));
+
+        let mut reader = relay.client(Keys::generate(), 10);
+        reader.set_event_kind(31234);
+        let retrieved = reader.retrieve_addresses(&event_id).await.unwrap();
+        assert_eq!(retrieved.addresses, addresses.addresses);
+    }
+
+    #[test]
+    fn test_duplicate_addresses_are_rejected() {
+        let client = NostrClient::with_keys(Keys::generate(), 10);
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+        addresses.add_address(AddressType::P2TR, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+
+        let result = client.build_addresses_event(&addresses, None);
+        assert!(matches!(result, Err(UbaError::UpdateValidation(_))));
+    }
+
+    #[test]
+    fn test_fixed_timestamp_makes_event_id_reproducible() {
+        let keys = Keys::generate();
+        let mut client = NostrClient::with_keys(keys, 10);
+        client.set_fixed_timestamp(Some(1_700_000_000));
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.created_at = 1_700_000_000;
+        addresses.add_address(AddressType::P2WPKH, "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string());
+
+        let event = client.build_addresses_event(&addresses, None).unwrap();
+        assert_eq!(event.created_at.as_u64(), 1_700_000_000);
+
+        let first = client.compute_event_id(&addresses, None).unwrap();
+        let second = client.compute_event_id(&addresses, None).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_from_config_applies_client_settings() {
+        let mut config = UbaConfig {
+            relay_timeout: 7,
+            query_timeout_secs: Some(30),
+            event_kind: 30078,
+            ..Default::default()
+        };
+
+        // The connect timeout falls back to relay_timeout
+        let client = NostrClient::from_config(Keys::generate(), &config);
+        assert_eq!(client.timeout_duration, Duration::from_secs(7));
+        assert_eq!(client.connect_timeout, Duration::from_secs(7));
+        assert_eq!(client.publish_timeout, None);
+        assert_eq!(client.query_timeout, Some(Duration::from_secs(30)));
+        assert_eq!(client.event_kind, Kind::Custom(30078));
+
+        config.connect_timeout_ms = Some(250);
+        let client = NostrClient::from_config(Keys::generate(), &config);
+        assert_eq!(client.connect_timeout, Duration::from_millis(250));
+
+        // Clients built without a config fall back to their operation timeout too
+        let client = NostrClient::with_keys(Keys::generate(), 7);
+        assert_eq!(client.connect_timeout, Duration::from_secs(7));
+    }
+
     #[test]
     fn test_validate_address_update_empty_collection() {
         let client = NostrClient::new(10).unwrap();
@@ -592,6 +2277,31 @@ mod tests {
         assert!(matches!(result.unwrap_err(), UbaError::UpdateValidation(_)));
     }
 
+    #[test]
+    fn test_validate_address_update_rejects_malformed_node_ids_and_npubs() {
+        let client = NostrClient::new(10).unwrap();
+        let node_id = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::Lightning, node_id.to_string());
+        addresses.add_address(AddressType::Lightning, "lnurl1dp68gurn8ghj7um9".to_string());
+        assert!(client.validate_address_update(&addresses).is_ok());
+
+        let mut truncated = addresses.clone();
+        truncated.add_address(AddressType::Lightning, node_id[..64].to_string());
+        assert!(matches!(
+            client.validate_address_update(&truncated),
+            Err(UbaError::UpdateValidation(_))
+        ));
+
+        let mut bad_npub = addresses.clone();
+        bad_npub.add_address(AddressType::Nostr, "npub1notanpub".to_string());
+        assert!(matches!(
+            client.validate_address_update(&bad_npub),
+            Err(UbaError::UpdateValidation(_))
+        ));
+    }
+
     #[test]
     fn test_validate_address_update_whitespace_only_address() {
         let client = NostrClient::new(10).unwrap();
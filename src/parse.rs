@@ -0,0 +1,348 @@
+//! UBA string parsing and formatting
+//!
+//! These functions only deal with the `UBA:<NostrID>&label=<label>` string format
+//! and need no relay connection, so they are available without the `nostr` feature.
+
+use crate::error::{Result, UbaError};
+use crate::types::{ParsedUba, Uba};
+
+use std::collections::HashMap;
+
+/// Format the UBA string for an already-published Nostr event
+///
+/// This is the inverse of `parse_uba`: the event ID and label are validated and the
+/// label is URL-encoded, so the result always parses back to the same components.
+///
+/// # Arguments
+/// * `event_id` - Nostr event ID (64 hex characters)
+/// * `label` - Optional human-readable label
+///
+/// # Returns
+/// The canonical UBA string
+///
+/// # Example
+/// ```rust
+/// use uba::format_uba;
+///
+/// let event_id = "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+/// let uba = format_uba(event_id, Some("my-wallet"))?;
+/// assert_eq!(uba, format!("UBA:{}&label=my-wallet", event_id));
+/// # Ok::<(), uba::UbaError>(())
+/// ```
+pub fn format_uba(event_id: &str, label: Option<&str>) -> Result<String> {
+    validate_nostr_id(event_id)?;
+    let mut uba = Uba::new(event_id)?;
+    if let Some(label) = label {
+        validate_label(label)?;
+        uba = uba.with_label(label);
+    }
+    Ok(uba.to_string())
+}
+
+/// Parse a UBA string into its components
+///
+/// # Arguments
+/// * `uba` - UBA string to parse
+///
+/// # Returns
+/// A `ParsedUba` struct containing the Nostr ID and optional label
+///
+/// # Example
+/// ```rust
+/// use uba::parse_uba;
+///
+/// let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef&label=my-wallet";
+/// let parsed = parse_uba(uba)?;
+/// println!("Nostr ID: {}", parsed.nostr_id);
+/// println!("Label: {:?}", parsed.label);
+/// # Ok::<(), uba::UbaError>(())
+/// ```
+pub fn parse_uba(uba: &str) -> Result<ParsedUba> {
+    // Strip the "UBA:" prefix; only char-boundary-safe operations are used below so
+    // arbitrary input yields an error rather than a panic
+    let content = uba.strip_prefix("UBA:").ok_or_else(|| {
+        UbaError::InvalidUbaFormat("UBA string must start with 'UBA:'".to_string())
+    })?;
+
+    // Check for label parameter
+    if let Some((nostr_id, query_string)) = content.split_once('&') {
+        // Parse query parameters
+        let label = parse_query_params(query_string)?;
+        let params = parse_all_params(query_string)?;
+
+        // Validate the Nostr ID format (should be 64 hex characters)
+        validate_nostr_id(nostr_id)?;
+
+        Ok(ParsedUba {
+            nostr_id: nostr_id.to_string(),
+            label,
+            params,
+        })
+    } else {
+        // No query parameters, just the Nostr ID
+        validate_nostr_id(content)?;
+
+        Ok(ParsedUba {
+            nostr_id: content.to_string(),
+            label: None,
+            params: HashMap::new(),
+        })
+    }
+}
+
+/// Parse query parameters from UBA string
+fn parse_query_params(query_string: &str) -> Result<Option<String>> {
+    for pair in query_string.split('&') {
+        if let Some((key, value)) = pair.split_once('=') {
+            if key == "label" {
+                // URL decode the value if needed
+                let decoded = urlencoding::decode(value).map_err(|_| {
+                    UbaError::InvalidUbaFormat("Invalid URL encoding in label".to_string())
+                })?;
+                return Ok(Some(decoded.to_string()));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Parse every query parameter of a UBA string into a map, keeping the first value
+/// of repeated keys
+fn parse_all_params(query_string: &str) -> Result<HashMap<String, String>> {
+    let decode = |part: &str| {
+        urlencoding::decode(part).map(|decoded| decoded.to_string()).map_err(|_| {
+            UbaError::InvalidUbaFormat("Invalid URL encoding in parameter".to_string())
+        })
+    };
+
+    let mut params = HashMap::new();
+    for pair in query_string.split('&') {
+        if let Some((key, value)) = pair.split_once('=') {
+            params.entry(decode(key)?).or_insert(decode(value)?);
+        }
+    }
+
+    Ok(params)
+}
+
+/// Parse the query parameters of a UBA string other than the label
+///
+/// Keys and values are URL-decoded. Pairs without a `=` are ignored, as in
+/// `parse_uba`.
+pub(crate) fn parse_extra_params(uba: &str) -> Result<Vec<(String, String)>> {
+    let query_string = match uba.split_once('&') {
+        Some((_, query_string)) => query_string,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut params = Vec::new();
+    for pair in query_string.split('&') {
+        if let Some((key, value)) = pair.split_once('=') {
+            if key == "label" {
+                continue;
+            }
+
+            let decode = |part: &str| {
+                urlencoding::decode(part).map(|decoded| decoded.to_string()).map_err(|_| {
+                    UbaError::InvalidUbaFormat("Invalid URL encoding in parameter".to_string())
+                })
+            };
+            params.push((decode(key)?, decode(value)?));
+        }
+    }
+
+    Ok(params)
+}
+
+/// Validate a Nostr event ID format
+pub(crate) fn validate_nostr_id(nostr_id: &str) -> Result<()> {
+    if nostr_id.len() != 64 {
+        return Err(UbaError::InvalidUbaFormat(
+            "Nostr ID must be 64 characters long".to_string(),
+        ));
+    }
+
+    // Check if it's valid hex
+    if !nostr_id.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(UbaError::InvalidUbaFormat(
+            "Nostr ID must be hexadecimal".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Characters other than letters and digits allowed in a label
+const LABEL_PUNCTUATION: [char; 8] = [' ', '-', '_', '.', ',', '\'', '(', ')'];
+
+/// Validate label format
+pub(crate) fn validate_label(label: &str) -> Result<()> {
+    if label.is_empty() {
+        return Err(UbaError::InvalidLabel("Label cannot be empty".to_string()));
+    }
+
+    if label.len() > 100 {
+        return Err(UbaError::InvalidLabel(
+            "Label cannot exceed 100 characters".to_string(),
+        ));
+    }
+
+    if label.trim() != label {
+        return Err(UbaError::InvalidLabel(
+            "Label cannot start or end with whitespace".to_string(),
+        ));
+    }
+
+    // Labels are URL-encoded in the UBA string, so spaces and basic punctuation are
+    // safe; anything that reads like URL structure is still rejected
+    if !label
+        .chars()
+        .all(|c| c.is_alphanumeric() || LABEL_PUNCTUATION.contains(&c))
+    {
+        return Err(UbaError::InvalidLabel(
+            "Label can only contain letters, digits, spaces and - _ . , ' ( )".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uba_never_panics_on_arbitrary_input() {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(326);
+        let prefixes = ["", "UBA:", "UBA:&", "UBA:é", "UBA:0000&label="];
+
+        for _ in 0..2_000 {
+            let len = rng.gen_range(0..96);
+            let bytes: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            let tail = String::from_utf8_lossy(&bytes);
+
+            for prefix in prefixes {
+                let input = format!("{}{}", prefix, tail);
+                // Only the absence of a panic matters here
+                let _ = parse_uba(&input);
+                let _ = parse_extra_params(&input);
+                let _ = input.parse::<Uba>();
+            }
+        }
+
+        // Multibyte characters right after the prefix are rejected cleanly
+        assert!(parse_uba("UBA:ééééé").is_err());
+        assert!(parse_uba("UBA:é&label=x").is_err());
+    }
+
+    #[test]
+    fn test_parse_uba_params() {
+        let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef&label=x&net=testnet&app=my%20app";
+        let parsed = parse_uba(uba).unwrap();
+        assert_eq!(parsed.label.as_deref(), Some("x"));
+        assert_eq!(parsed.params.get("label").map(String::as_str), Some("x"));
+        assert_eq!(parsed.params.get("net").map(String::as_str), Some("testnet"));
+        assert_eq!(parsed.params.get("app").map(String::as_str), Some("my app"));
+    }
+
+    #[test]
+    fn test_format_uba_round_trips_with_parse_uba() {
+        let nostr_id = "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+
+        let uba = format_uba(nostr_id, Some("my-wallet")).unwrap();
+        assert_eq!(uba, format!("UBA:{}&label=my-wallet", nostr_id));
+        let parsed = parse_uba(&uba).unwrap();
+        assert_eq!(parsed.nostr_id, nostr_id);
+        assert_eq!(parsed.label.as_deref(), Some("my-wallet"));
+
+        assert_eq!(format_uba(nostr_id, None).unwrap(), format!("UBA:{}", nostr_id));
+        assert!(format_uba("abc123", None).is_err());
+        assert!(format_uba(nostr_id, Some("")).is_err());
+    }
+
+    #[test]
+    fn test_parse_uba_without_label() {
+        let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+        let result = parse_uba(uba);
+
+        assert!(result.is_ok());
+        let parsed = result.unwrap();
+        assert_eq!(
+            parsed.nostr_id,
+            "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef"
+        );
+        assert_eq!(parsed.label, None);
+    }
+
+    #[test]
+    fn test_parse_uba_with_label() {
+        let uba =
+            "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef&label=my-wallet";
+        let result = parse_uba(uba);
+
+        assert!(result.is_ok());
+        let parsed = result.unwrap();
+        assert_eq!(
+            parsed.nostr_id,
+            "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef"
+        );
+        assert_eq!(parsed.label, Some("my-wallet".to_string()));
+    }
+
+    #[test]
+    fn test_uba_type_roundtrip() {
+        let nostr_id = "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+        let uba: Uba = format!("UBA:{}&label=my-wallet&v=2", nostr_id).parse().unwrap();
+
+        assert_eq!(uba.nostr_id(), nostr_id);
+        assert_eq!(uba.label(), Some("my-wallet"));
+        assert_eq!(uba.params(), &[("v".to_string(), "2".to_string())]);
+        assert_eq!(uba.to_string(), format!("UBA:{}&label=my-wallet&v=2", nostr_id));
+        assert_eq!(uba.to_string().parse::<Uba>().unwrap(), uba);
+
+        // Labels needing escaping survive a roundtrip through the canonical string
+        let labelled = Uba::new(nostr_id).unwrap().with_label("cold storage&co");
+        let reparsed: Uba = labelled.to_string().parse().unwrap();
+        assert_eq!(reparsed.label(), Some("cold storage&co"));
+
+        assert!("UBA:not-a-valid-id".parse::<Uba>().is_err());
+        assert!(Uba::new("abc").is_err());
+    }
+
+    #[test]
+    fn test_parse_uba_invalid_format() {
+        let uba = "INVALID:1234567890abcdef";
+        let result = parse_uba(uba);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parse_uba_invalid_nostr_id() {
+        let uba = "UBA:invalidhex";
+        let result = parse_uba(uba);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_validate_label() {
+        // Valid labels
+        assert!(validate_label("my-wallet").is_ok());
+        assert!(validate_label("wallet123").is_ok());
+        assert!(validate_label("a").is_ok());
+        assert!(validate_label("My Wallet (savings)").is_ok());
+        assert!(validate_label("Café").is_ok());
+
+        // Invalid labels
+        assert!(validate_label("").is_err());
+        assert!(validate_label("a".repeat(101).as_str()).is_err()); // Too long
+        assert!(validate_label(" my wallet").is_err()); // Leading space
+        assert!(validate_label("my&label=x").is_err()); // Contains & and =
+        assert!(validate_label("my@wallet").is_err()); // Contains @
+        assert!(validate_label("my/wallet").is_err()); // Contains /
+    }
+}
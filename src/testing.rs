@@ -0,0 +1,265 @@
+//! In-memory relay mock for deterministic, offline tests
+//!
+//! Enabled with the `testing` feature. A `MockRelay` stores published events in
+//! memory and answers queries using standard Nostr filter matching, so a UBA can be
+//! generated and retrieved end-to-end without touching the internet.
+//!
+//! # Example
+//!
+//! ```rust,ignore
+//! use uba::testing::MockRelay;
+//! use uba::{generate_with_client, retrieve_full_with_client, UbaConfig};
+//!
+//! let relay = MockRelay::new();
+//! let relays = vec!["wss://mock.relay".to_string()];
+//! let client = relay.client(nostr::Keys::generate(), 10);
+//!
+//! let uba = generate_with_client(seed, None, &relays, UbaConfig::default(), &client).await?;
+//! let addresses = retrieve_full_with_client(&uba, &relays, UbaConfig::default(), &client).await?;
+//! ```
+
+use crate::error::{Result, UbaError};
+use crate::nostr_client::NostrClient;
+use crate::transport::{ConnectStatus, PublishOutput, RelayTransport};
+
+use nostr::{Event, EventId, Filter, JsonUtil, Keys};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Shared in-memory relay that stores published events
+///
+/// Cloning a `MockRelay` yields a handle to the same event store, so a publisher
+/// and a reader created from clones observe the same events.
+#[derive(Debug, Clone, Default)]
+pub struct MockRelay {
+    events: Arc<Mutex<Vec<Event>>>,
+    connected_relays: Arc<Mutex<Vec<String>>>,
+    unreachable_relays: Arc<Mutex<Vec<String>>>,
+    max_event_size: Arc<Mutex<Option<usize>>>,
+    latencies: Arc<Mutex<HashMap<String, Duration>>>,
+    query_delay: Arc<Mutex<Duration>>,
+    disconnects: Arc<AtomicUsize>,
+}
+
+impl MockRelay {
+    /// Create a new empty mock relay
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a `NostrClient` that publishes to and queries this mock relay
+    pub fn client(&self, keys: Keys, timeout_seconds: u64) -> NostrClient<MockRelay> {
+        let mut client = NostrClient::with_transport(self.clone(), keys, timeout_seconds);
+        // The mock never fails to connect, so retries would only slow tests down
+        client.set_retry_config(1, 0);
+        client
+    }
+
+    /// Store an event and return its ID
+    ///
+    /// Publishing an event that is already stored is a no-op, as on a real relay.
+    pub fn publish(&self, event: Event) -> EventId {
+        let event_id = event.id;
+        let mut events = self.lock();
+        if !events.iter().any(|stored| stored.id == event_id) {
+            events.push(event);
+        }
+        event_id
+    }
+
+    /// Return all stored events matching any of the given filters
+    pub fn query(&self, filters: &[Filter]) -> Vec<Event> {
+        let events = self.lock();
+        let mut matched: Vec<Event> = Vec::new();
+
+        for filter in filters {
+            let limit = filter.limit.unwrap_or(usize::MAX);
+            for event in events
+                .iter()
+                .filter(|event| filter.match_event(event))
+                .take(limit)
+            {
+                if !matched.iter().any(|m| m.id == event.id) {
+                    matched.push(event.clone());
+                }
+            }
+        }
+
+        matched
+    }
+
+    /// Get a snapshot of all stored events
+    pub fn events(&self) -> Vec<Event> {
+        self.lock().clone()
+    }
+
+    /// Get the number of stored events
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Check if no events have been published
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Remove all stored events
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Get the relay URLs clients have connected to through this mock
+    pub fn connected_relays(&self) -> Vec<String> {
+        self.connected_relays
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Get how many times clients of this mock disconnected, including
+    /// disconnects started by dropping a connected client
+    pub fn disconnect_count(&self) -> usize {
+        self.disconnects.load(Ordering::SeqCst)
+    }
+
+    /// Make pings to the given relay URL fail, simulating a relay that is offline
+    pub fn set_unreachable(&self, relay_url: &str) {
+        self.unreachable_relays
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(relay_url.to_string());
+    }
+
+    /// Report `latency` when the given relay URL is pinged, instead of zero
+    pub fn set_latency(&self, relay_url: &str, latency: Duration) {
+        self.latencies
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(relay_url.to_string(), latency);
+    }
+
+    /// Delay every query answer by `delay`, simulating a slow relay
+    pub fn set_query_delay(&self, delay: Duration) {
+        *self.query_delay.lock().unwrap_or_else(|e| e.into_inner()) = delay;
+    }
+
+    /// Reject published events whose serialized size exceeds `max_event_size` bytes
+    pub fn set_max_event_size(&self, max_event_size: usize) {
+        *self
+            .max_event_size
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(max_event_size);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Event>> {
+        // A poisoned lock only means another test panicked mid-write; the data is still usable
+        self.events.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl RelayTransport for MockRelay {
+    fn connect(
+        &self,
+        relay_urls: &[String],
+        _connect_timeout: Duration,
+    ) -> impl Future<Output = Result<()>> + Send {
+        let mut connected = self
+            .connected_relays
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        for url in relay_urls {
+            if !connected.contains(url) {
+                connected.push(url.clone());
+            }
+        }
+        async { Ok(()) }
+    }
+
+    fn publish(&self, event: Event) -> impl Future<Output = Result<PublishOutput>> + Send {
+        let max_event_size = *self
+            .max_event_size
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let size = event.as_json().len();
+        let result = match max_event_size {
+            Some(max) if size > max => Err(UbaError::EventTooLarge {
+                size,
+                message: self
+                    .connected_relays()
+                    .iter()
+                    .map(|url| format!("{}: invalid: event too big", url))
+                    .collect::<Vec<_>>()
+                    .join("; "),
+            }),
+            _ => {
+                let event_id = MockRelay::publish(self, event);
+                Ok(PublishOutput {
+                    event_id,
+                    confirmed_relays: self.connected_relays(),
+                })
+            }
+        };
+        async move { result }
+    }
+
+    fn query(
+        &self,
+        filters: Vec<Filter>,
+        _timeout: Duration,
+    ) -> impl Future<Output = Result<Vec<Event>>> + Send {
+        let events = MockRelay::query(self, &filters);
+        let delay = *self.query_delay.lock().unwrap_or_else(|e| e.into_inner());
+        async move {
+            tokio::time::sleep(delay).await;
+            Ok(events)
+        }
+    }
+
+    fn relay_statuses(&self) -> impl Future<Output = Vec<(String, ConnectStatus)>> + Send {
+        let mut statuses: Vec<(String, ConnectStatus)> = self
+            .connected_relays()
+            .into_iter()
+            .map(|url| (url, ConnectStatus::Connected))
+            .collect();
+        statuses.sort_by(|a, b| a.0.cmp(&b.0));
+        async move { statuses }
+    }
+
+    fn ping_relays(
+        &self,
+        relay_urls: &[String],
+        _timeout: Duration,
+    ) -> impl Future<Output = Vec<(String, Result<Duration>)>> + Send {
+        let unreachable = self
+            .unreachable_relays
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let latencies = self.latencies.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let mut results: Vec<(String, Result<Duration>)> = relay_urls
+            .iter()
+            .map(|url| {
+                let result = if unreachable.contains(url) {
+                    Err(UbaError::NostrRelay(format!("{} is unreachable", url)))
+                } else {
+                    Ok(latencies.get(url).copied().unwrap_or(Duration::ZERO))
+                };
+                (url.clone(), result)
+            })
+            .collect();
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        async move { results }
+    }
+
+    fn disconnect(&self) -> impl Future<Output = ()> + Send {
+        self.disconnects.fetch_add(1, Ordering::SeqCst);
+        async {}
+    }
+
+    fn disconnect_in_background(&self) {
+        self.disconnects.fetch_add(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,326 @@
+//! Relay transport abstraction used by `NostrClient`
+//!
+//! `NostrClient` builds, signs, encrypts and validates UBA events, while a
+//! `RelayTransport` is only responsible for moving signed events to and from relays.
+//! The default transport is `nostr_sdk::Client`; other implementations (an in-memory
+//! mock, a local cache, a custom relay pool) can be plugged in via
+//! `NostrClient::with_transport`.
+
+use crate::error::{Result, UbaError};
+
+use nostr::{Event, EventId, Filter, JsonUtil, Url};
+use nostr_sdk::Client;
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+/// How often relay connection status is polled while waiting for readiness
+const CONNECT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Result of publishing an event through a `RelayTransport`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublishOutput {
+    /// ID of the published event
+    pub event_id: EventId,
+    /// URLs of the relays that accepted the event
+    pub confirmed_relays: Vec<String>,
+}
+
+/// Connection state of a single relay
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectStatus {
+    /// The relay is connected and ready
+    Connected,
+    /// The relay is not connected; the string describes its current state
+    NotConnected(String),
+}
+
+impl ConnectStatus {
+    /// Check if the relay is connected
+    pub fn is_connected(&self) -> bool {
+        matches!(self, ConnectStatus::Connected)
+    }
+}
+
+/// Aggregate reachability of a set of relays
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RelaySummary {
+    /// URLs of the relays that answered within the timeout
+    pub reachable: Vec<String>,
+    /// URLs of the relays that failed or timed out
+    pub unreachable: Vec<String>,
+    /// Connection latency of each reachable relay
+    pub latencies: Vec<(String, Duration)>,
+}
+
+impl RelaySummary {
+    /// Number of relays that were checked
+    pub fn total(&self) -> usize {
+        self.reachable.len() + self.unreachable.len()
+    }
+
+    /// Check if every relay was reachable
+    pub fn all_reachable(&self) -> bool {
+        self.unreachable.is_empty()
+    }
+}
+
+impl std::fmt::Display for RelaySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{} relays online", self.reachable.len(), self.total())
+    }
+}
+
+/// Transport used to reach Nostr relays
+///
+/// Implementations must be usable from multi-threaded async runtimes, so all
+/// returned futures are required to be `Send`.
+pub trait RelayTransport: Send + Sync {
+    /// Connect to the given relay URLs
+    ///
+    /// Implementations should return as soon as at least one relay is ready, and
+    /// fail if none is ready within `connect_timeout`.
+    fn connect(
+        &self,
+        relay_urls: &[String],
+        connect_timeout: Duration,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Publish a signed event and report which relays accepted it
+    ///
+    /// Implementations should return an error if no relay accepted the event.
+    fn publish(&self, event: Event) -> impl Future<Output = Result<PublishOutput>> + Send;
+
+    /// Query the connected relays for events matching any of the filters
+    ///
+    /// `timeout` bounds how long the transport waits for relays to answer.
+    fn query(
+        &self,
+        filters: Vec<Filter>,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<Vec<Event>>> + Send;
+
+    /// Report the connection state of every relay the transport knows about,
+    /// sorted by relay URL
+    fn relay_statuses(&self) -> impl Future<Output = Vec<(String, ConnectStatus)>> + Send;
+
+    /// Check each relay for reachability, concurrently and independently of the
+    /// relays used for publishing and querying
+    ///
+    /// Returns the connection latency of each relay, or why it could not be reached
+    /// within `timeout`, sorted by relay URL.
+    fn ping_relays(
+        &self,
+        relay_urls: &[String],
+        timeout: Duration,
+    ) -> impl Future<Output = Vec<(String, Result<Duration>)>> + Send;
+
+    /// Disconnect from all relays
+    fn disconnect(&self) -> impl Future<Output = ()> + Send;
+
+    /// Start disconnecting without waiting for it to finish
+    ///
+    /// Called when a connected `NostrClient` is dropped, where `disconnect` cannot
+    /// be awaited. The default does nothing; transports holding live connections
+    /// should override it.
+    fn disconnect_in_background(&self) {}
+}
+
+impl RelayTransport for Client {
+    async fn connect(
+        &self,
+        relay_urls: &[String],
+        connect_timeout: Duration,
+    ) -> Result<()> {
+        for url_str in relay_urls {
+            let url =
+                Url::parse(url_str).map_err(|_| UbaError::InvalidRelayUrl(url_str.clone()))?;
+
+            self.add_relay(url)
+                .await
+                .map_err(|e| UbaError::NostrRelay(e.to_string()))?;
+        }
+
+        Client::connect(self).await;
+
+        // Wait until at least one relay reports connected, returning as soon as one does
+        let deadline = tokio::time::Instant::now() + connect_timeout;
+        loop {
+            for relay in self.relays().await.values() {
+                if relay.is_connected().await {
+                    return Ok(());
+                }
+            }
+
+            if tokio::time::Instant::now() >= deadline {
+                return Err(UbaError::NostrRelay(format!(
+                    "No relay connected within {} ms",
+                    connect_timeout.as_millis()
+                )));
+            }
+
+            tokio::time::sleep(CONNECT_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn publish(&self, event: Event) -> Result<PublishOutput> {
+        let event_id = event.id;
+        let event_size = event.as_json().len();
+
+        // Send to each relay individually so we know exactly which ones accepted it
+        let mut sends = JoinSet::new();
+        for url in self.relays().await.into_keys() {
+            let client = self.clone();
+            let event = event.clone();
+            sends.spawn(async move {
+                let result = client.send_event_to([url.clone()], event).await;
+                (url, result)
+            });
+        }
+
+        let mut confirmed_relays = Vec::new();
+        let mut last_error = None;
+        let mut too_large = Vec::new();
+        while let Some(joined) = sends.join_next().await {
+            match joined {
+                Ok((url, Ok(_))) => {
+                    trace_debug!(relay = %url, "Relay accepted event");
+                    confirmed_relays.push(url.to_string());
+                }
+                Ok((url, Err(e))) => {
+                    trace_warn!(relay = %url, error = %e, "Relay rejected event");
+                    let message = format!("{}: {}", url, e);
+                    if is_too_large_message(&message) {
+                        too_large.push(message.clone());
+                    }
+                    last_error = Some(message);
+                }
+                Err(e) => last_error = Some(e.to_string()),
+            }
+        }
+
+        if confirmed_relays.is_empty() && !too_large.is_empty() {
+            too_large.sort();
+            return Err(UbaError::EventTooLarge {
+                size: event_size,
+                message: too_large.join("; "),
+            });
+        }
+
+        if confirmed_relays.is_empty() {
+            return Err(UbaError::NostrRelay(format!(
+                "Event was not accepted by any relay: {}",
+                last_error.unwrap_or_else(|| "no relays connected".to_string())
+            )));
+        }
+
+        // Keep the output stable regardless of which relay answered first
+        confirmed_relays.sort();
+
+        Ok(PublishOutput {
+            event_id,
+            confirmed_relays,
+        })
+    }
+
+    async fn query(
+        &self,
+        filters: Vec<Filter>,
+        timeout: Duration,
+    ) -> Result<Vec<Event>> {
+        self.get_events_of(filters, Some(timeout))
+            .await
+            .map_err(|e| UbaError::NostrRelay(e.to_string()))
+    }
+
+    async fn relay_statuses(&self) -> Vec<(String, ConnectStatus)> {
+        let mut statuses = Vec::new();
+        for (url, relay) in self.relays().await {
+            let status = if relay.is_connected().await {
+                ConnectStatus::Connected
+            } else {
+                ConnectStatus::NotConnected(format!("{:?}", relay.status().await))
+            };
+            statuses.push((url.to_string(), status));
+        }
+
+        statuses.sort_by(|a, b| a.0.cmp(&b.0));
+        statuses
+    }
+
+    fn ping_relays(
+        &self,
+        relay_urls: &[String],
+        timeout: Duration,
+    ) -> impl Future<Output = Vec<(String, Result<Duration>)>> + Send {
+        let relay_urls = relay_urls.to_vec();
+        async move {
+            let mut pings = JoinSet::new();
+            for url in relay_urls {
+                pings.spawn(async move {
+                    // A throwaway client keeps pings out of this client's relay pool
+                    let probe = Client::default();
+                    let started = tokio::time::Instant::now();
+                    let result = tokio::time::timeout(
+                        timeout,
+                        RelayTransport::connect(&probe, std::slice::from_ref(&url), timeout),
+                    )
+                    .await
+                    .map_err(|_| UbaError::Timeout)
+                    .and_then(|connected| connected)
+                    .map(|()| started.elapsed());
+                    RelayTransport::disconnect(&probe).await;
+                    (url, result)
+                });
+            }
+
+            let mut results = Vec::new();
+            while let Some(joined) = pings.join_next().await {
+                if let Ok(result) = joined {
+                    results.push(result);
+                }
+            }
+
+            results.sort_by(|a, b| a.0.cmp(&b.0));
+            results
+        }
+    }
+
+    async fn disconnect(&self) {
+        let _ = Client::disconnect(self).await;
+    }
+
+    fn disconnect_in_background(&self) {
+        // Without a runtime there is nothing to run the shutdown on; the relay
+        // pool then closes its connections when the last handle is dropped
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let client = self.clone();
+            runtime.spawn(async move {
+                let _ = Client::disconnect(&client).await;
+            });
+        }
+    }
+}
+
+/// Relay rejection phrases that indicate an event exceeded the relay's size limit
+const TOO_LARGE_MARKERS: [&str; 4] = ["too large", "too big", "too long", "exceeds maximum"];
+
+/// Check whether a relay rejection message reports an oversized event
+pub(crate) fn is_too_large_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    TOO_LARGE_MARKERS.iter().any(|marker| message.contains(marker))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_too_large_message() {
+        assert!(is_too_large_message("wss://relay.damus.io: invalid: event too big"));
+        assert!(is_too_large_message("wss://nos.lol: Event Too Large"));
+        assert!(is_too_large_message("message too long"));
+        assert!(!is_too_large_message("wss://nos.lol: blocked: pubkey not allowed"));
+        assert!(!is_too_large_message("timeout"));
+    }
+}
@@ -1,10 +1,24 @@
 //! Core types for the UBA library
 
+use crate::encryption::EncryptionKey;
+use crate::error::validation::RateLimiter;
 use bitcoin::Network;
-use hex;
-use rand;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// Default upper bound on the number of addresses generated per address type
+pub const DEFAULT_MAX_ADDRESS_COUNT: usize = 100_000;
+
+/// Default upper bound on the size of a single published event, in bytes
+pub const DEFAULT_MAX_EVENT_SIZE: usize = 64 * 1024;
+
+/// Default Nostr event kind of UBA events
+pub const DEFAULT_EVENT_KIND: u16 = 30000;
+
+/// Newest `BitcoinAddresses` format version this build understands, and the one
+/// it writes. Retrieval rejects data with a higher version.
+pub const MAX_FORMAT_VERSION: u32 = 1;
 
 /// Configuration for UBA generation and retrieval
 #[derive(Debug, Clone)]
@@ -14,24 +28,162 @@ pub struct UbaConfig {
     /// Whether to encrypt the address data in Nostr notes
     pub encrypt_data: bool,
     /// Optional encryption key (32 bytes) for encrypting JSON data sent to relays
-    /// If None, no encryption is applied (backward compatible)
-    pub encryption_key: Option<[u8; 32]>,
+    /// If None, no encryption is applied (backward compatible).
+    /// The key is zeroized when the config is dropped.
+    pub encryption_key: Option<EncryptionKey>,
+    /// Encrypt only the metadata (label, description, xpub, derivation paths) and
+    /// publish the addresses in cleartext. Has no effect without an encryption key.
+    pub encrypt_metadata_only: bool,
     /// Timeout for relay operations in seconds
     pub relay_timeout: u64,
+    /// How long to wait for at least one relay to connect, in milliseconds. None
+    /// uses `relay_timeout`. Connecting returns as soon as a relay is ready.
+    pub connect_timeout_ms: Option<u64>,
+    /// Timeout for publishing an event, in seconds. None uses `relay_timeout`.
+    pub publish_timeout_secs: Option<u64>,
+    /// Timeout for relay queries (retrieval, history, existence checks), in
+    /// seconds. None uses `relay_timeout`; raise it for slow queries over many relays.
+    pub query_timeout_secs: Option<u64>,
     /// Maximum number of addresses to generate per address type (default fallback)
     pub max_addresses_per_type: usize,
     /// Specific address counts per type (overrides max_addresses_per_type if set)
     pub address_counts: HashMap<AddressType, usize>,
-    /// Optional custom relay URLs to use instead of default public relays
-    /// If None, will use DEFAULT_PUBLIC_RELAYS
+    /// First child index derived for every address type, e.g. to start at a known
+    /// pre-funded test address. Shifts the whole address set: with 5, each type
+    /// yields indices 5, 6, ... instead of 0, 1, ... Calls that take explicit
+    /// indices (`address_at`, paging cursors) are unaffected.
+    pub start_index: u32,
+    /// Relays used when none are passed explicitly: the default or extended public
+    /// list, or custom URLs
+    pub relay_preset: RelayPreset,
+    /// Custom relay URLs from before `relay_preset`. When set they take precedence
+    /// over `relay_preset`, and the relay setters move them into
+    /// `RelayPreset::Custom`.
+    #[deprecated(note = "use `relay_preset` with `RelayPreset::Custom`")]
     pub custom_relays: Option<Vec<String>>,
+    /// When set, generate and retrieve calls ping their candidate relays first and
+    /// only use this many of the fastest-connecting ones. `Some(0)` is rejected.
+    pub auto_select_relays: Option<usize>,
     /// Address type filters - controls which address types to include
-    /// Default is all enabled (true for all types)
+    /// Types without an entry use `AddressType::enabled_by_default`
     pub address_filters: HashMap<AddressType, bool>,
     /// Maximum retry attempts for relay connections
     pub max_retry_attempts: usize,
     /// Delay between retry attempts in milliseconds
     pub retry_delay_ms: u64,
+    /// Optional BIP39 passphrase (the "25th word") applied when deriving the seed
+    /// from a mnemonic. None is equivalent to an empty passphrase.
+    pub bip39_passphrase: Option<String>,
+    /// Wordlist language of mnemonic seeds. None detects the language from the
+    /// words; set it for mnemonics whose words exist in several wordlists.
+    pub mnemonic_language: Option<bip39::Language>,
+    /// Optional override for the Liquid address mode. `Some(true)` always produces
+    /// confidential addresses, `Some(false)` always produces explicit ones, and None
+    /// keeps the network default (confidential on mainnet, explicit elsewhere).
+    pub liquid_confidential: Option<bool>,
+    /// Optional elements address parameters used for Liquid addresses instead of the
+    /// network default, for custom elements chains. Without an override, Bitcoin maps
+    /// to Liquid mainnet, Testnet and Signet to Liquid testnet, and Regtest to the
+    /// elements regtest params.
+    pub liquid_params_override: Option<&'static elements::AddressParams>,
+    /// Optional Taproot script tree merkle root. When set, P2TR addresses commit to
+    /// the script tree (script-path spendable) instead of being key-path only.
+    pub taproot_merkle_root: Option<bitcoin::taproot::TapNodeHash>,
+    /// Upper bound on the per-type address count; larger counts are rejected
+    /// before any key derivation happens
+    pub max_address_count: usize,
+    /// Optional fixed Unix timestamp (seconds) used instead of the current time for
+    /// `created_at` in generated content and published events, for reproducible output
+    pub fixed_timestamp: Option<u64>,
+    /// Derivation paths overriding the standard path for specific address types
+    /// (e.g. `m/0'/0'` for older wallets). Set via `set_custom_derivation_path` so
+    /// the path is validated up front.
+    pub custom_derivation_paths: HashMap<AddressType, String>,
+    /// Maximum size in bytes of a single event; larger collections can be split
+    /// across linked events with `generate_sharded`
+    pub max_event_size: usize,
+    /// Bounds applied when scanning relays for the updates of a UBA
+    /// (see `retrieve_history_with_config`). Single-event retrieval is unaffected.
+    pub retrieval_bounds: RetrievalBounds,
+    /// Check that externally provided addresses (e.g. in `update_uba_with_addresses`)
+    /// parse as their declared type on the configured network before publishing
+    pub validate_external_addresses: bool,
+    /// Reject address formats `validate_addresses` cannot check: Lightning entries
+    /// must then be 66-char hex node ids or Lightning Addresses, and Nostr entries
+    /// npubs. Off by default, so BOLT12 offers, LNURLs and other exotic formats
+    /// from external sources pass; entries that look like node ids or npubs are
+    /// checked either way.
+    pub strict_address_formats: bool,
+    /// Optional address type order for flattened output such as `retrieve`; types
+    /// not listed follow in `AddressType::ALL` order
+    pub address_type_order: Option<Vec<AddressType>>,
+    /// Nostr event kind used for publishing and retrieving UBA events. Readers and
+    /// writers must agree on it; defaults to `DEFAULT_EVENT_KIND` (30000).
+    pub event_kind: u16,
+    /// Extra query parameters appended to generated UBA strings after the label, in
+    /// key order so the same parameters always produce the same string. Set via
+    /// `set_uba_param`.
+    pub uba_params: BTreeMap<String, String>,
+    /// Store the master key fingerprint in the generated metadata so watch-only
+    /// wallets can rebuild descriptors (`[fingerprint/path]xpub`) from a retrieved
+    /// UBA. Off by default since it links the collection to the seed.
+    pub include_fingerprint: bool,
+    /// Publish events without the metadata block (label, description, xpub,
+    /// derivation paths), so relays only see the bare address map. Generation still
+    /// returns the metadata locally.
+    pub strip_metadata_on_publish: bool,
+    /// Require hex private keys to name their network with a prefix such as
+    /// `mainnet:<hex>` or `testnet:<hex>`. A prefix that differs from `network` is
+    /// always rejected; off by default, a bare hex key is also accepted and derives
+    /// addresses for whatever network is configured. Mnemonics are unaffected.
+    pub strict_network: bool,
+    /// Sign generated collections with the seed's Nostr identity key (see
+    /// `BitcoinAddresses::sign_with_seed`)
+    pub sign_payload: bool,
+    /// Hex x-only public key that retrieved collections must be signed by. When
+    /// set, retrieval fails with `UbaError::InvalidSignature` on unsigned, altered
+    /// or foreign payloads.
+    pub expected_signer: Option<String>,
+    /// Shared rate limiter checked on every generate and retrieve call, with the
+    /// identifier (client IP, pubkey, ...) the calls are counted under. Set via
+    /// `set_rate_limiter`; None disables rate limiting.
+    pub rate_limiter: Option<(Arc<Mutex<RateLimiter>>, String)>,
+}
+
+/// Which relays to use when none are passed to a generate or retrieve call
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum RelayPreset {
+    /// The curated `default_public_relays` list
+    #[default]
+    Default,
+    /// The larger `extended_public_relays` list
+    Extended,
+    /// Caller-provided relay URLs
+    Custom(Vec<String>),
+}
+
+impl RelayPreset {
+    /// Resolve the preset to relay URLs
+    pub fn relay_urls(&self) -> Vec<String> {
+        match self {
+            RelayPreset::Default => default_public_relays(),
+            RelayPreset::Extended => extended_public_relays(),
+            RelayPreset::Custom(relays) => relays.clone(),
+        }
+    }
+}
+
+/// Filter bounds applied when scanning relays for related historical events
+///
+/// All bounds default to None (unbounded). Timestamps are Unix seconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RetrievalBounds {
+    /// Only consider events created at or after this time
+    pub since: Option<u64>,
+    /// Only consider events created at or before this time
+    pub until: Option<u64>,
+    /// Maximum number of events requested from each relay
+    pub limit: Option<usize>,
 }
 
 impl UbaConfig {
@@ -40,6 +192,13 @@ impl UbaConfig {
         self.address_counts.insert(address_type, count);
     }
 
+    /// How long to wait for at least one relay to connect
+    pub fn connect_timeout(&self) -> std::time::Duration {
+        self.connect_timeout_ms
+            .map(std::time::Duration::from_millis)
+            .unwrap_or(std::time::Duration::from_secs(self.relay_timeout))
+    }
+
     /// Get the number of addresses to generate for a specific address type
     pub fn get_address_count(&self, address_type: &AddressType) -> usize {
         self.address_counts
@@ -48,20 +207,46 @@ impl UbaConfig {
             .unwrap_or(self.max_addresses_per_type)
     }
 
+    /// Check that every enabled address type requests a sane number of addresses
+    ///
+    /// Counts above `max_address_count`, or whose last index (counted from
+    /// `start_index`) would not fit in the BIP32 non-hardened index range, are
+    /// rejected with `UbaError::Config`.
+    pub fn validate_address_counts(&self) -> Result<(), crate::UbaError> {
+        for address_type in self.get_enabled_address_types() {
+            let count = self.get_address_count(&address_type);
+
+            if count > self.max_address_count {
+                return Err(crate::UbaError::Config(format!(
+                    "Address count {} for {:?} exceeds the maximum of {}",
+                    count, address_type, self.max_address_count
+                )));
+            }
+
+            // Non-hardened child indexes must stay below 2^31
+            if count > (1usize << 31) - self.start_index.min(1 << 31) as usize {
+                return Err(crate::UbaError::Config(format!(
+                    "Address count {} for {:?} from index {} overflows the derivation index range",
+                    count, address_type, self.start_index
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
     /// Set address counts for all Bitcoin L1 types at once
     pub fn set_bitcoin_l1_counts(&mut self, count: usize) {
-        self.set_address_count(AddressType::P2PKH, count);
-        self.set_address_count(AddressType::P2SH, count);
-        self.set_address_count(AddressType::P2WPKH, count);
-        self.set_address_count(AddressType::P2TR, count);
+        for address_type in AddressType::BITCOIN_L1 {
+            self.set_address_count(address_type, count);
+        }
     }
 
     /// Set counts for all address types at once
     pub fn set_all_counts(&mut self, count: usize) {
-        self.set_bitcoin_l1_counts(count);
-        self.set_address_count(AddressType::Liquid, count);
-        self.set_address_count(AddressType::Lightning, count);
-        self.set_address_count(AddressType::Nostr, count);
+        for address_type in AddressType::ALL {
+            self.set_address_count(address_type, count);
+        }
     }
 
     /// Enable or disable a specific address type
@@ -74,54 +259,40 @@ impl UbaConfig {
         self.address_filters
             .get(address_type)
             .copied()
-            .unwrap_or(true) // Default to enabled if not specified
+            .unwrap_or_else(|| address_type.enabled_by_default())
     }
 
     /// Enable all Bitcoin L1 address types
     pub fn enable_bitcoin_l1(&mut self) {
-        self.set_address_type_enabled(AddressType::P2PKH, true);
-        self.set_address_type_enabled(AddressType::P2SH, true);
-        self.set_address_type_enabled(AddressType::P2WPKH, true);
-        self.set_address_type_enabled(AddressType::P2TR, true);
+        for address_type in AddressType::BITCOIN_L1 {
+            self.set_address_type_enabled(address_type, true);
+        }
     }
 
     /// Disable all Bitcoin L1 address types
     pub fn disable_bitcoin_l1(&mut self) {
-        self.set_address_type_enabled(AddressType::P2PKH, false);
-        self.set_address_type_enabled(AddressType::P2SH, false);
-        self.set_address_type_enabled(AddressType::P2WPKH, false);
-        self.set_address_type_enabled(AddressType::P2TR, false);
+        for address_type in AddressType::BITCOIN_L1 {
+            self.set_address_type_enabled(address_type, false);
+        }
     }
 
     /// Enable all address types
     pub fn enable_all_address_types(&mut self) {
-        self.enable_bitcoin_l1();
-        self.set_address_type_enabled(AddressType::Liquid, true);
-        self.set_address_type_enabled(AddressType::Lightning, true);
-        self.set_address_type_enabled(AddressType::Nostr, true);
+        for address_type in AddressType::ALL {
+            self.set_address_type_enabled(address_type, true);
+        }
     }
 
     /// Disable all address types
     pub fn disable_all_address_types(&mut self) {
-        self.disable_bitcoin_l1();
-        self.set_address_type_enabled(AddressType::Liquid, false);
-        self.set_address_type_enabled(AddressType::Lightning, false);
-        self.set_address_type_enabled(AddressType::Nostr, false);
+        for address_type in AddressType::ALL {
+            self.set_address_type_enabled(address_type, false);
+        }
     }
 
     /// Get a list of enabled address types
     pub fn get_enabled_address_types(&self) -> Vec<AddressType> {
-        let all_types = vec![
-            AddressType::P2PKH,
-            AddressType::P2SH,
-            AddressType::P2WPKH,
-            AddressType::P2TR,
-            AddressType::Liquid,
-            AddressType::Lightning,
-            AddressType::Nostr,
-        ];
-
-        all_types
+        AddressType::ALL
             .into_iter()
             .filter(|addr_type| self.is_address_type_enabled(addr_type))
             .collect()
@@ -129,6 +300,9 @@ impl UbaConfig {
 
     /// Set encryption key from a hex string
     ///
+    /// Surrounding whitespace (such as a trailing newline from a pasted key) and an
+    /// optional `0x` prefix are ignored.
+    ///
     /// # Arguments
     /// * `key_hex` - 64-character hex string representing 32 bytes
     ///
@@ -136,31 +310,13 @@ impl UbaConfig {
     /// * `Ok(())` if key was set successfully
     /// * `Err` if hex string is invalid or wrong length
     pub fn set_encryption_key_from_hex(&mut self, key_hex: &str) -> Result<(), crate::UbaError> {
-        if key_hex.len() != 64 {
-            return Err(crate::UbaError::InvalidEncryptionKey(
-                "Encryption key must be exactly 64 hex characters (32 bytes)".to_string(),
-            ));
-        }
-
-        let key_bytes = hex::decode(key_hex).map_err(|e| {
-            crate::UbaError::InvalidEncryptionKey(format!("Invalid hex string: {}", e))
-        })?;
-
-        if key_bytes.len() != 32 {
-            return Err(crate::UbaError::InvalidEncryptionKey(
-                "Encryption key must be exactly 32 bytes".to_string(),
-            ));
-        }
-
-        let mut key_array = [0u8; 32];
-        key_array.copy_from_slice(&key_bytes);
-        self.encryption_key = Some(key_array);
+        self.encryption_key = Some(EncryptionKey::from_hex(key_hex)?);
         Ok(())
     }
 
     /// Set encryption key from raw bytes
     pub fn set_encryption_key(&mut self, key: [u8; 32]) {
-        self.encryption_key = Some(key);
+        self.encryption_key = Some(EncryptionKey::new(key));
     }
 
     /// Generate a random encryption key
@@ -169,7 +325,7 @@ impl UbaConfig {
         let mut rng = rand::thread_rng();
         let mut key = [0u8; 32];
         rng.fill_bytes(&mut key);
-        self.encryption_key = Some(key);
+        self.encryption_key = Some(EncryptionKey::new(key));
         key
     }
 
@@ -179,33 +335,59 @@ impl UbaConfig {
     }
 
     /// Get encryption key as hex string (for display/storage)
+    ///
+    /// **Note**: the returned `String` is not zeroized on drop, so the key outlives
+    /// this config in memory. Prefer passing the key around as `EncryptionKey`.
     pub fn get_encryption_key_hex(&self) -> Option<String> {
-        self.encryption_key.map(hex::encode)
+        self.encryption_key.as_ref().map(EncryptionKey::to_hex)
     }
 
     /// Set custom relay URLs
     pub fn set_custom_relays(&mut self, relays: Vec<String>) {
-        self.custom_relays = Some(relays);
+        self.take_legacy_custom_relays();
+        self.relay_preset = RelayPreset::Custom(relays);
     }
 
     /// Add a custom relay URL
+    ///
+    /// Replaces a public preset with a custom list holding just this relay.
     pub fn add_custom_relay(&mut self, relay_url: String) {
-        match &mut self.custom_relays {
-            Some(relays) => relays.push(relay_url),
-            None => self.custom_relays = Some(vec![relay_url]),
+        self.take_legacy_custom_relays();
+        match &mut self.relay_preset {
+            RelayPreset::Custom(relays) => relays.push(relay_url),
+            preset => *preset = RelayPreset::Custom(vec![relay_url]),
         }
     }
 
-    /// Get relay URLs to use (custom or default)
+    /// Get relay URLs to use, as selected by `relay_preset`
+    ///
+    /// The deprecated `custom_relays` field still wins when it is set.
     pub fn get_relay_urls(&self) -> Vec<String> {
-        self.custom_relays
-            .clone()
-            .unwrap_or_else(default_public_relays)
+        #[allow(deprecated)]
+        match &self.custom_relays {
+            Some(relays) => relays.clone(),
+            None => self.relay_preset.relay_urls(),
+        }
     }
 
     /// Reset to use default public relays
     pub fn use_default_relays(&mut self) {
-        self.custom_relays = None;
+        self.take_legacy_custom_relays();
+        self.relay_preset = RelayPreset::Default;
+    }
+
+    /// Use the extended public relay list for higher availability
+    pub fn use_extended_relays(&mut self) {
+        self.take_legacy_custom_relays();
+        self.relay_preset = RelayPreset::Extended;
+    }
+
+    /// Move relays set through the deprecated `custom_relays` field into `relay_preset`
+    #[allow(deprecated)]
+    fn take_legacy_custom_relays(&mut self) {
+        if let Some(relays) = self.custom_relays.take() {
+            self.relay_preset = RelayPreset::Custom(relays);
+        }
     }
 
     /// Set retry configuration
@@ -213,6 +395,139 @@ impl UbaConfig {
         self.max_retry_attempts = max_attempts;
         self.retry_delay_ms = delay_ms;
     }
+
+    /// Throttle generate and retrieve calls made with this config
+    ///
+    /// Calls are counted under `identifier` in the shared `limiter`, so service
+    /// operators can hand each caller a config clone with its own identifier and
+    /// the same limiter. Calls over the limit fail with `UbaError::RateLimit`
+    /// before any relay is contacted.
+    pub fn set_rate_limiter(
+        &mut self,
+        limiter: Arc<Mutex<RateLimiter>>,
+        identifier: impl Into<String>,
+    ) {
+        self.rate_limiter = Some((limiter, identifier.into()));
+    }
+
+    /// Count a call against the configured rate limiter, if any
+    #[cfg(feature = "nostr")]
+    pub(crate) fn check_rate_limit(&self) -> Result<(), crate::UbaError> {
+        match &self.rate_limiter {
+            Some((limiter, identifier)) => limiter
+                .lock()
+                // A poisoned lock only means another caller panicked; the counts are still usable
+                .unwrap_or_else(|e| e.into_inner())
+                .is_allowed(identifier),
+            None => Ok(()),
+        }
+    }
+
+    /// Set the BIP39 passphrase used when deriving the seed from a mnemonic
+    pub fn set_bip39_passphrase(&mut self, passphrase: String) {
+        self.bip39_passphrase = Some(passphrase);
+    }
+
+    /// Get the BIP39 passphrase, falling back to the empty passphrase
+    pub fn get_bip39_passphrase(&self) -> &str {
+        self.bip39_passphrase.as_deref().unwrap_or("")
+    }
+
+    /// Force confidential (true) or explicit (false) Liquid addresses
+    pub fn set_liquid_confidential(&mut self, confidential: bool) {
+        self.liquid_confidential = Some(confidential);
+    }
+
+    /// Override the derivation path used for an address type
+    ///
+    /// Returns `UbaError::Config` if the path is not a valid BIP32 derivation path.
+    pub fn set_custom_derivation_path(
+        &mut self,
+        address_type: AddressType,
+        path: &str,
+    ) -> Result<(), crate::UbaError> {
+        parse_derivation_path(&address_type, path)?;
+        self.custom_derivation_paths
+            .insert(address_type, path.to_string());
+        Ok(())
+    }
+
+    /// Get the derivation path used for an address type (custom or standard)
+    ///
+    /// BOLT12 offers follow a custom Lightning path unless they have their own, so
+    /// offers stay signed by the advertised node keys.
+    pub fn get_derivation_path(&self, address_type: &AddressType) -> &str {
+        let custom = self.custom_derivation_paths.get(address_type).or_else(|| {
+            match address_type {
+                AddressType::LightningOffer => {
+                    self.custom_derivation_paths.get(&AddressType::Lightning)
+                }
+                _ => None,
+            }
+        });
+
+        custom
+            .map(String::as_str)
+            .unwrap_or_else(|| address_type.default_derivation_path())
+    }
+
+    /// Check that every custom derivation path parses as a BIP32 derivation path
+    pub fn validate_derivation_paths(&self) -> Result<(), crate::UbaError> {
+        for (address_type, path) in &self.custom_derivation_paths {
+            parse_derivation_path(address_type, path)?;
+        }
+        Ok(())
+    }
+
+    /// Get the address type order used for flattened output
+    pub fn get_address_type_order(&self) -> &[AddressType] {
+        self.address_type_order.as_deref().unwrap_or(&AddressType::ALL)
+    }
+
+    /// Add an extra query parameter to generated UBA strings
+    ///
+    /// The key must be non-empty and cannot be `label`, which is set per UBA.
+    pub fn set_uba_param(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<(), crate::UbaError> {
+        let key = key.into();
+        if key.is_empty() || key == "label" {
+            return Err(crate::UbaError::Config(format!(
+                "Invalid UBA parameter key '{}'",
+                key
+            )));
+        }
+        self.uba_params.insert(key, value.into());
+        Ok(())
+    }
+
+    /// Use custom elements address parameters for Liquid addresses
+    ///
+    /// `AddressParams` are referenced statically, so custom chains declare them as a
+    /// `static` item.
+    pub fn set_liquid_params(&mut self, params: &'static elements::AddressParams) {
+        self.liquid_params_override = Some(params);
+    }
+
+    /// Check whether Liquid addresses will be confidential for the configured network
+    pub fn is_liquid_confidential(&self) -> bool {
+        self.liquid_confidential
+            .unwrap_or(self.network == Network::Bitcoin)
+    }
+}
+
+fn parse_derivation_path(
+    address_type: &AddressType,
+    path: &str,
+) -> Result<bitcoin::bip32::DerivationPath, crate::UbaError> {
+    path.parse().map_err(|e| {
+        crate::UbaError::Config(format!(
+            "Invalid derivation path '{}' for {:?}: {}",
+            path, address_type, e
+        ))
+    })
 }
 
 impl Default for UbaConfig {
@@ -221,55 +536,257 @@ impl Default for UbaConfig {
             network: Network::Bitcoin,
             encrypt_data: false,
             encryption_key: None,
+            encrypt_metadata_only: false,
             relay_timeout: 10,
+            connect_timeout_ms: None,
+            publish_timeout_secs: None,
+            query_timeout_secs: None,
             max_addresses_per_type: 1,
             address_counts: HashMap::new(),
+            start_index: 0,
+            relay_preset: RelayPreset::Default,
+            #[allow(deprecated)]
             custom_relays: None,
+            auto_select_relays: None,
             address_filters: HashMap::new(), // Empty means all enabled by default
             max_retry_attempts: 3,
             retry_delay_ms: 500,
+            bip39_passphrase: None,
+            mnemonic_language: None,
+            liquid_confidential: None,
+            liquid_params_override: None,
+            taproot_merkle_root: None,
+            max_address_count: DEFAULT_MAX_ADDRESS_COUNT,
+            fixed_timestamp: None,
+            custom_derivation_paths: HashMap::new(),
+            max_event_size: DEFAULT_MAX_EVENT_SIZE,
+            retrieval_bounds: RetrievalBounds::default(),
+            validate_external_addresses: false,
+            strict_address_formats: false,
+            uba_params: BTreeMap::new(),
+            event_kind: DEFAULT_EVENT_KIND,
+            address_type_order: None,
+            include_fingerprint: false,
+            strip_metadata_on_publish: false,
+            strict_network: false,
+            sign_payload: false,
+            expected_signer: None,
+            rate_limiter: None,
         }
     }
 }
 
 /// Represents different types of Bitcoin addresses
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+///
+/// Variants are declared in the order of `AddressType::ALL`, which is also the order
+/// derived for `Ord`. Values serialize by variant name, but address maps are written
+/// in this order, so reordering changes the bytes (and event IDs) of new events.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum AddressType {
     /// Legacy P2PKH addresses (starts with 1)
     P2PKH,
-    /// SegWit P2SH-wrapped addresses (starts with 3)
-    P2SH,
+    /// P2WPKH wrapped in P2SH, i.e. nested SegWit (BIP49, starts with 3)
+    ///
+    /// Bare P2SH scripts such as multisig are not generated. Serialized as `"P2SH"`
+    /// so older readers keep understanding it; `"P2SHWPKH"` is accepted too.
+    #[serde(rename = "P2SH", alias = "P2SHWPKH")]
+    P2SHWPKH,
     /// Native SegWit addresses (starts with bc1)
     P2WPKH,
     /// Taproot addresses (starts with bc1p)
     P2TR,
-    /// Lightning Network invoice/address
-    Lightning,
     /// Liquid sidechain address
     Liquid,
+    /// Lightning Network invoice/address
+    Lightning,
+    /// Lightning Network static BOLT12 offer (starts with lno1)
+    LightningOffer,
     /// Nostr public key
     Nostr,
 }
 
 impl AddressType {
+    /// Former name of `AddressType::P2SHWPKH`
+    #[deprecated(note = "use AddressType::P2SHWPKH; bare P2SH is not supported")]
+    pub const P2SH: AddressType = AddressType::P2SHWPKH;
+
+    /// Every address type this build supports, in canonical order: Bitcoin L1
+    /// from oldest to newest script type, then Liquid, Lightning and Nostr
+    ///
+    /// The single list of types: methods that act on all types iterate this, so
+    /// a new variant only needs to be added here.
+    pub const ALL: [AddressType; 8] = [
+        AddressType::P2PKH,
+        AddressType::P2SHWPKH,
+        AddressType::P2WPKH,
+        AddressType::P2TR,
+        AddressType::Liquid,
+        AddressType::Lightning,
+        AddressType::LightningOffer,
+        AddressType::Nostr,
+    ];
+
+    /// The Bitcoin L1 address types, in canonical order
+    pub const BITCOIN_L1: [AddressType; 4] =
+        [AddressType::P2PKH, AddressType::P2SHWPKH, AddressType::P2WPKH, AddressType::P2TR];
+
+    /// Every address type this build supports, in canonical order
+    pub fn all() -> &'static [AddressType] {
+        &Self::ALL
+    }
+
+    /// Every supported address type with its `description`, in canonical order
+    ///
+    /// Intended for UIs and help text, so they don't hardcode the list of types.
+    pub fn catalog() -> Vec<(AddressType, &'static str)> {
+        Self::all()
+            .iter()
+            .map(|address_type| (address_type.clone(), address_type.description()))
+            .collect()
+    }
+
     /// Get a human-readable description of the address type
     pub fn description(&self) -> &'static str {
         match self {
             AddressType::P2PKH => "Legacy Bitcoin address (P2PKH)",
-            AddressType::P2SH => "SegWit-wrapped Bitcoin address (P2SH)",
+            AddressType::P2SHWPKH => "SegWit-wrapped Bitcoin address (P2SH)",
             AddressType::P2WPKH => "Native SegWit Bitcoin address (P2WPKH)",
             AddressType::P2TR => "Taproot Bitcoin address (P2TR)",
             AddressType::Lightning => "Lightning Network address/invoice",
+            AddressType::LightningOffer => "Lightning Network BOLT12 offer (lno format)",
             AddressType::Liquid => "Liquid sidechain address",
             AddressType::Nostr => "Nostr public key (npub format)",
         }
     }
+
+    /// Whether the type is generated when `UbaConfig::address_filters` has no
+    /// entry for it
+    ///
+    /// BOLT12 offers are opt-in, so default collections keep the same set of types.
+    pub fn enabled_by_default(&self) -> bool {
+        !matches!(self, AddressType::LightningOffer)
+    }
+
+    /// Get the user-facing kebab-case name of the address type
+    ///
+    /// This is the form accepted by `FromStr`, e.g. for `--types p2wpkh,p2tr`.
+    pub fn as_kebab_str(&self) -> &'static str {
+        match self {
+            AddressType::P2PKH => "p2pkh",
+            AddressType::P2SHWPKH => "p2sh",
+            AddressType::P2WPKH => "p2wpkh",
+            AddressType::P2TR => "p2tr",
+            AddressType::Lightning => "lightning",
+            AddressType::LightningOffer => "lightning-offer",
+            AddressType::Liquid => "liquid",
+            AddressType::Nostr => "nostr",
+        }
+    }
+
+    /// Get the BIP purpose number (44/49/84/86) for Bitcoin L1 address types
+    ///
+    /// Returns None for Liquid, Lightning and Nostr, which do not follow a BIP44-style
+    /// purpose scheme.
+    pub fn bip_purpose(&self) -> Option<u32> {
+        match self {
+            AddressType::P2PKH => Some(44),
+            AddressType::P2SHWPKH => Some(49),
+            AddressType::P2WPKH => Some(84),
+            AddressType::P2TR => Some(86),
+            AddressType::Lightning
+            | AddressType::LightningOffer
+            | AddressType::Liquid
+            | AddressType::Nostr => None,
+        }
+    }
+
+    /// Get the standard derivation path used for this address type
+    ///
+    /// Lightning node IDs and BOLT12 offers share the node key path, so an offer is
+    /// always signed by the node key at the same index.
+    pub fn default_derivation_path(&self) -> &'static str {
+        match self {
+            AddressType::P2PKH => "m/44'/0'/0'/0",
+            AddressType::P2SHWPKH => "m/49'/0'/0'/0",
+            AddressType::P2WPKH => "m/84'/0'/0'/0",
+            AddressType::P2TR => "m/86'/0'/0'/0",
+            // 1776 is the coin type for Liquid Network
+            AddressType::Liquid => "m/84'/1776'/0'/0",
+            // 1017 is used for Lightning node identity keys
+            AddressType::Lightning | AddressType::LightningOffer => "m/1017'/0'/0'",
+            // 1237 is a proposed coin type for Nostr (not officially assigned)
+            AddressType::Nostr => "m/44'/1237'/0'/0",
+        }
+    }
+}
+
+impl std::str::FromStr for AddressType {
+    type Err = crate::UbaError;
+
+    /// Parse a kebab-case name (case-insensitive), also accepting the serialized
+    /// variant names and common aliases such as "taproot", "segwit" or "bolt12"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "p2pkh" | "legacy" => Ok(AddressType::P2PKH),
+            "p2sh" | "p2shwpkh" | "p2sh-p2wpkh" | "nested-segwit" => Ok(AddressType::P2SHWPKH),
+            "p2wpkh" | "segwit" | "native-segwit" => Ok(AddressType::P2WPKH),
+            "p2tr" | "taproot" => Ok(AddressType::P2TR),
+            "lightning" | "ln" => Ok(AddressType::Lightning),
+            "lightning-offer" | "lightningoffer" | "bolt12" => Ok(AddressType::LightningOffer),
+            "liquid" => Ok(AddressType::Liquid),
+            "nostr" | "npub" => Ok(AddressType::Nostr),
+            other => Err(crate::UbaError::Config(format!(
+                "Unknown address type: {}",
+                other
+            ))),
+        }
+    }
+}
+
+impl TryFrom<u8> for AddressType {
+    type Error = crate::UbaError;
+
+    /// Convert a numeric code, as used by language bindings, into an address type
+    ///
+    /// Codes are positions in `AddressType::ALL`: 0 is P2PKH and 7 is Nostr.
+    fn try_from(code: u8) -> Result<Self, Self::Error> {
+        Self::ALL.get(code as usize).cloned().ok_or_else(|| {
+            crate::UbaError::Config(format!("Unknown address type code: {}", code))
+        })
+    }
+}
+
+impl From<AddressType> for u8 {
+    fn from(address_type: AddressType) -> u8 {
+        // ALL lists every variant in declaration order
+        address_type as u8
+    }
+}
+
+/// Convert a numeric network code, as used by language bindings, into a `Network`
+///
+/// 0 is mainnet, 1 testnet, 2 signet and 3 regtest. `Network` is defined by the
+/// `bitcoin` crate, so this is a function rather than a `TryFrom` impl.
+pub fn network_from_code(code: u8) -> Result<Network, crate::UbaError> {
+    match code {
+        0 => Ok(Network::Bitcoin),
+        1 => Ok(Network::Testnet),
+        2 => Ok(Network::Signet),
+        3 => Ok(Network::Regtest),
+        other => Err(crate::UbaError::Config(format!("Unknown network code: {}", other))),
+    }
 }
 
 /// Collection of Bitcoin addresses across different layers and types
+///
+/// Deserialization tolerates address types this build does not know, such as
+/// those added by newer publishers: their entries are moved to `unknown` instead
+/// of failing, so the known types remain usable.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "BitcoinAddressesData")]
 pub struct BitcoinAddresses {
     /// Mapping of address types to their corresponding addresses
+    #[serde(serialize_with = "serialize_sorted_addresses")]
     pub addresses: HashMap<AddressType, Vec<String>>,
     /// Optional metadata for the address collection
     pub metadata: Option<AddressMetadata>,
@@ -277,6 +794,78 @@ pub struct BitcoinAddresses {
     pub created_at: u64,
     /// Version of the address format for future compatibility
     pub version: u32,
+    /// Entries that could not be attributed to a known `AddressType`, keyed by
+    /// their type name, including types from newer publishers that this build
+    /// does not support. Not counted by `len` or returned by `iter_typed`.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub unknown: HashMap<String, Vec<String>>,
+    /// Optional free-form hints keyed by address, such as the asset id a Liquid
+    /// address is meant to receive (see `set_liquid_asset`). Clients that do not
+    /// use them can ignore them.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub annotations: HashMap<String, String>,
+    /// Publisher signature over the payload, see `sign_with_seed`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<PayloadSignature>,
+}
+
+/// BIP340 Schnorr signature over a `BitcoinAddresses` payload
+///
+/// Covers the addresses, `unknown` entries, annotations, `created_at` and
+/// `version`, but not the metadata, which can be stripped or encrypted on its own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayloadSignature {
+    /// Hex x-only public key of the signer (the seed's Nostr identity)
+    pub public_key: String,
+    /// Hex Schnorr signature over the SHA-256 of the canonical payload
+    pub signature: String,
+}
+
+/// Wire form of `BitcoinAddresses` with address types kept as plain strings
+#[derive(Deserialize)]
+struct BitcoinAddressesData {
+    addresses: HashMap<String, Vec<String>>,
+    metadata: Option<AddressMetadata>,
+    created_at: u64,
+    version: u32,
+    #[serde(default)]
+    unknown: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    annotations: HashMap<String, String>,
+    #[serde(default)]
+    signature: Option<PayloadSignature>,
+}
+
+impl From<BitcoinAddressesData> for BitcoinAddresses {
+    fn from(data: BitcoinAddressesData) -> Self {
+        use serde::de::value::{Error as ValueError, StrDeserializer};
+        use serde::de::IntoDeserializer;
+
+        let mut addresses = HashMap::new();
+        let mut unknown = data.unknown;
+        for (type_name, entries) in data.addresses {
+            let deserializer: StrDeserializer<'_, ValueError> =
+                type_name.as_str().into_deserializer();
+            match AddressType::deserialize(deserializer) {
+                Ok(address_type) => {
+                    addresses.insert(address_type, entries);
+                }
+                Err(_) => {
+                    unknown.entry(type_name).or_default().extend(entries);
+                }
+            }
+        }
+
+        Self {
+            addresses,
+            metadata: data.metadata,
+            created_at: data.created_at,
+            version: data.version,
+            unknown,
+            annotations: data.annotations,
+            signature: data.signature,
+        }
+    }
 }
 
 impl BitcoinAddresses {
@@ -291,7 +880,10 @@ impl BitcoinAddresses {
             addresses: HashMap::new(),
             metadata: None,
             created_at,
-            version: 1,
+            version: MAX_FORMAT_VERSION,
+            unknown: HashMap::new(),
+            annotations: HashMap::new(),
+            signature: None,
         }
     }
 
@@ -305,10 +897,55 @@ impl BitcoinAddresses {
             addresses: HashMap::new(),
             metadata: None,
             created_at,
-            version: 1,
+            version: MAX_FORMAT_VERSION,
+            unknown: HashMap::new(),
+            annotations: HashMap::new(),
+            signature: None,
         })
     }
 
+    /// Rebuild a typed collection from a flat address list, such as the output
+    /// of `retrieve`
+    ///
+    /// Each address is classified for `network` with `classify_address` and added
+    /// to its type in list order. Addresses that cannot be classified are kept in
+    /// the `"Unknown"` bucket of `unknown`; use `try_from_classified` to reject them
+    /// instead. No metadata is attached.
+    pub fn from_classified(addresses: &[String], network: bitcoin::Network) -> Self {
+        let mut collection = Self::new();
+        for address in addresses {
+            match crate::address::classify_address(address, network) {
+                Some(address_type) => collection.add_address(address_type, address.clone()),
+                None => collection
+                    .unknown
+                    .entry("Unknown".to_string())
+                    .or_default()
+                    .push(address.clone()),
+            }
+        }
+        collection
+    }
+
+    /// Rebuild a typed collection from a flat address list, failing on any
+    /// address that cannot be classified
+    ///
+    /// See `from_classified`. Returns `UbaError::InputValidation` naming every
+    /// unclassifiable address.
+    pub fn try_from_classified(
+        addresses: &[String],
+        network: bitcoin::Network,
+    ) -> Result<Self, crate::UbaError> {
+        let collection = Self::from_classified(addresses, network);
+        match collection.unknown.get("Unknown") {
+            Some(unclassified) => Err(crate::UbaError::InputValidation(format!(
+                "Cannot classify addresses for {}: {}",
+                network,
+                unclassified.join(", ")
+            ))),
+            None => Ok(collection),
+        }
+    }
+
     /// Add an address of a specific type
     pub fn add_address(&mut self, address_type: AddressType, address: String) {
         self.addresses
@@ -322,14 +959,76 @@ impl BitcoinAddresses {
         self.addresses.get(address_type)
     }
 
+    /// Get the address of a specific type at the given index
+    ///
+    /// Returns `None` if the type has no addresses or the index is out of range.
+    pub fn get_address(&self, address_type: &AddressType, index: usize) -> Option<&str> {
+        self.addresses
+            .get(address_type)
+            .and_then(|addresses| addresses.get(index))
+            .map(String::as_str)
+    }
+
+    /// Get the first address of a specific type
+    pub fn first(&self, address_type: &AddressType) -> Option<&str> {
+        self.get_address(address_type, 0)
+    }
+
     /// Get all addresses as a flat vector
+    ///
+    /// Types follow `AddressType::ALL` order and addresses keep their index
+    /// order within each type.
     pub fn get_all_addresses(&self) -> Vec<String> {
-        self.addresses
-            .values()
-            .flat_map(|addresses| addresses.iter().cloned())
+        self.get_all_addresses_in_order(&[])
+    }
+
+    /// Get all addresses as a flat vector with the given types first
+    ///
+    /// Types missing from `order` follow in canonical order.
+    pub fn get_all_addresses_in_order(&self, order: &[AddressType]) -> Vec<String> {
+        self.iter_typed_in_order(order)
+            .map(|(_, _, address)| address.to_string())
             .collect()
     }
 
+    /// Iterate over every address together with its type and index within that type
+    ///
+    /// Types are visited in `AddressType::ALL` order and indices ascend within
+    /// each type, so the output is reproducible regardless of the map's internal order.
+    pub fn iter_typed(&self) -> impl Iterator<Item = (AddressType, usize, &str)> + '_ {
+        self.iter_typed_in_order(&[])
+    }
+
+    /// Iterate over every address with its type and index, visiting the given types
+    /// first and the remaining types in canonical order
+    pub fn iter_typed_in_order(
+        &self,
+        order: &[AddressType],
+    ) -> impl Iterator<Item = (AddressType, usize, &str)> + '_ {
+        let mut type_order: Vec<AddressType> = Vec::new();
+        for address_type in order.iter().chain(AddressType::ALL.iter()) {
+            if !type_order.contains(address_type) {
+                type_order.push(address_type.clone());
+            }
+        }
+
+        type_order.into_iter().flat_map(move |address_type| {
+            self.addresses
+                .get(&address_type)
+                .into_iter()
+                .flatten()
+                .enumerate()
+                .map(move |(index, address)| (address_type.clone(), index, address.as_str()))
+        })
+    }
+
+    /// Iterate over each address type and its addresses, in `AddressType::ALL` order
+    ///
+    /// Only known types are visited; see `unknown` for the rest.
+    pub fn iter(&self) -> std::vec::IntoIter<(&AddressType, &Vec<String>)> {
+        self.into_iter()
+    }
+
     /// Check if the collection is empty
     pub fn is_empty(&self) -> bool {
         self.addresses.is_empty()
@@ -339,81 +1038,973 @@ impl BitcoinAddresses {
     pub fn len(&self) -> usize {
         self.addresses.values().map(|v| v.len()).sum()
     }
-}
 
-impl Default for BitcoinAddresses {
-    fn default() -> Self {
-        Self::new()
+    /// Get the number of addresses of each type present in the collection
+    ///
+    /// Types without addresses are omitted.
+    pub fn counts_by_type(&self) -> HashMap<AddressType, usize> {
+        self.addresses
+            .iter()
+            .filter(|(_, addresses)| !addresses.is_empty())
+            .map(|(address_type, addresses)| (address_type.clone(), addresses.len()))
+            .collect()
     }
-}
 
-/// Optional metadata for address collections
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct AddressMetadata {
-    /// User-defined label for the address collection
-    pub label: Option<String>,
-    /// Description of the wallet or purpose
-    pub description: Option<String>,
-    /// Extended public key used for derivation (if applicable)
-    pub xpub: Option<String>,
-    /// Derivation paths used for address generation
-    pub derivation_paths: Option<Vec<String>>,
-}
+    /// Estimate the size in bytes of the Nostr event that would carry this collection
+    ///
+    /// The estimate covers the full signed event JSON: the content (ChaCha20Poly1305
+    /// nonce and tag plus base64 expansion when a key is given), the UBA tags and the
+    /// fixed-size id, pubkey and signature fields. No networking or encryption is
+    /// performed; only the presence of the key matters. `event_kind` is the kind the
+    /// event is published under, normally `UbaConfig::event_kind`.
+    pub fn estimated_event_size(
+        &self,
+        encryption_key: Option<&[u8; 32]>,
+        event_kind: u16,
+    ) -> usize {
+        let json = serde_json::to_string(self).unwrap_or_default();
 
-/// Parsed UBA components
-#[derive(Debug, Clone)]
-pub struct ParsedUba {
-    /// The Nostr event ID that contains the address data
-    pub nostr_id: String,
-    /// Optional label extracted from the UBA
-    pub label: Option<String>,
-}
+        let content = match encryption_key {
+            // 12-byte nonce + ciphertext + 16-byte tag, base64 encoded with padding
+            Some(_) => "A".repeat((12 + json.len() + 16).div_ceil(3) * 4),
+            None => json,
+        };
 
-/// UBA generation request
-#[derive(Debug, Clone)]
-pub struct UbaGenerationRequest {
-    /// The seed phrase or private key material
-    pub seed: String,
-    /// Optional label for the UBA
-    pub label: Option<String>,
-    /// List of Nostr relay URLs
-    pub relay_urls: Vec<String>,
-    /// Configuration for the generation process
-    pub config: UbaConfig,
-}
+        let mut tags = vec![vec!["uba".to_string(), "bitcoin-addresses".to_string()]];
+        if encryption_key.is_some() {
+            tags.push(vec!["encrypted".to_string(), "true".to_string()]);
+            tags.push(vec!["scheme".to_string(), "chacha20poly1305".to_string()]);
+        }
+        if let Some(label) = self.metadata.as_ref().and_then(|m| m.label.as_ref()) {
+            tags.push(vec!["label".to_string(), label.clone()]);
+        }
+        tags.push(vec!["version".to_string(), self.version.to_string()]);
 
-/// UBA retrieval request
-#[derive(Debug, Clone)]
-pub struct UbaRetrievalRequest {
-    /// The UBA string to parse and retrieve
-    pub uba: String,
-    /// List of Nostr relay URLs to query
-    pub relay_urls: Vec<String>,
-    /// Configuration for the retrieval process
-    pub config: UbaConfig,
-}
+        let event = serde_json::json!({
+            "id": "0".repeat(64),
+            "pubkey": "0".repeat(64),
+            "created_at": u32::MAX,
+            "kind": event_kind,
+            "tags": tags,
+            "content": content,
+            "sig": "0".repeat(128),
+        });
 
-/// Get a curated list of reliable public Nostr relays
-///
-/// These relays are selected for reliability and geographical distribution.
-/// Users can override this list by setting custom_relays in UbaConfig.
-pub fn default_public_relays() -> Vec<String> {
-    vec![
-        // Reliable relays with good uptime and performance
-        "wss://relay.damus.io".to_string(), // Damus (Cloudflare)
-        "wss://nos.lol".to_string(),        // NOS (Hetzner)
-        "wss://relay.snort.social".to_string(), // Snort (Cloudflare)
-        "wss://nostr.wine".to_string(),     // Nostr Wine (Cloudflare)
-        "wss://relay.nostr.band".to_string(), // Nostr Band (Hetzner) - supports search
-        "wss://nostr.mutinywallet.com".to_string(), // Mutiny Wallet (Amazon)
-        "wss://relay.primal.net".to_string(), // Primal (Cloudflare)
-        "wss://relay.nostrati.com".to_string(), // Nostrati (Digital Ocean)
-        "wss://nostr.sethforprivacy.com".to_string(), // Seth for Privacy (Privacy-focused)
-        "wss://offchain.pub".to_string(),   // Offchain Pub (MULTACOM)
-        "wss://relay.nostrplebs.com".to_string(), // Nostr Plebs (Hetzner)
-        "wss://purplepag.es".to_string(),   // Purple Pages (Constant Company)
-    ]
-}
+        event.to_string().len()
+    }
+
+    /// Split the collection into parts whose events each stay under `max_event_size`
+    ///
+    /// `reserved` bytes are kept free in every part for extra event tags, and
+    /// `event_kind` is the kind the parts are published under. The first
+    /// part carries the metadata and any unknown address types; all parts keep
+    /// `created_at` and `version`. Address
+    /// types are visited in `AddressType::ALL` order and addresses keep their order. Sizes are
+    /// tracked incrementally with a conservative per-address cost, so parts may end
+    /// up slightly smaller than strictly necessary.
+    ///
+    /// Returns `UbaError::Config` if a single address cannot fit in an event.
+    pub fn split_by_event_size(
+        &self,
+        max_event_size: usize,
+        reserved: usize,
+        encrypted: bool,
+        event_kind: u16,
+    ) -> Result<Vec<BitcoinAddresses>, crate::UbaError> {
+        // Only the presence of a key matters for size estimation
+        let key = encrypted.then_some([0u8; 32]);
+        let limit = max_event_size.saturating_sub(reserved);
+
+        // Raw JSON bytes added to the content, converted to event bytes: quotes are
+        // escaped in plaintext content, and encrypted content grows by 4/3 (base64)
+        let event_bytes = |raw: usize| {
+            if encrypted {
+                raw.div_ceil(3) * 4 + 4
+            } else {
+                raw * 2
+            }
+        };
+
+        let mut entries: Vec<_> = self.addresses.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+
+        let new_part = |with_metadata: bool| BitcoinAddresses {
+            addresses: HashMap::new(),
+            metadata: if with_metadata { self.metadata.clone() } else { None },
+            created_at: self.created_at,
+            version: self.version,
+            unknown: if with_metadata { self.unknown.clone() } else { HashMap::new() },
+            annotations: if with_metadata { self.annotations.clone() } else { HashMap::new() },
+            // Verifiable again once the parts are recombined
+            signature: if with_metadata { self.signature.clone() } else { None },
+        };
+
+        let mut parts = Vec::new();
+        let mut part = new_part(true);
+        let mut size = part.estimated_event_size(key.as_ref(), event_kind);
+
+        for (address_type, addresses) in entries {
+            for address in addresses {
+                let type_name_len = format!("{:?}", address_type).len();
+                let mut cost = event_bytes(address.len() + 3);
+                if !part.addresses.contains_key(address_type) {
+                    cost += event_bytes(type_name_len + 6);
+                }
+
+                if size + cost > limit && !part.addresses.is_empty() {
+                    parts.push(std::mem::replace(&mut part, new_part(false)));
+                    size = part.estimated_event_size(key.as_ref(), event_kind);
+                    cost = event_bytes(address.len() + 3) + event_bytes(type_name_len + 6);
+                }
+
+                if size + cost > limit {
+                    return Err(crate::UbaError::Config(format!(
+                        "Address {} does not fit in an event of {} bytes",
+                        address, max_event_size
+                    )));
+                }
+
+                part.add_address(address_type.clone(), address.clone());
+                size += cost;
+            }
+        }
+
+        if !part.addresses.is_empty() || parts.is_empty() {
+            parts.push(part);
+        }
+
+        Ok(parts)
+    }
+
+    /// Find addresses that appear more than once in the collection
+    ///
+    /// Each entry lists a duplicated address together with the type of every
+    /// occurrence, in `AddressType::ALL` order, so an address repeated within one
+    /// type lists that type twice. Entries are sorted by address for stable output.
+    pub fn find_duplicates(&self) -> Vec<(String, Vec<AddressType>)> {
+        let mut occurrences: HashMap<&str, Vec<AddressType>> = HashMap::new();
+
+        let mut entries: Vec<_> = self.addresses.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        for (address_type, addresses) in entries {
+            for address in addresses {
+                occurrences
+                    .entry(address.as_str())
+                    .or_default()
+                    .push(address_type.clone());
+            }
+        }
+
+        let mut duplicates: Vec<(String, Vec<AddressType>)> = occurrences
+            .into_iter()
+            .filter(|(_, types)| types.len() > 1)
+            .map(|(address, types)| (address.to_string(), types))
+            .collect();
+        duplicates.sort_by(|a, b| a.0.cmp(&b.0));
+        duplicates
+    }
+
+    /// Export the Bitcoin L1 addresses as a newline-separated list
+    ///
+    /// This is what Electrum's "Import Bitcoin addresses" wallet creation (and
+    /// most other watch-only importers) accepts. Liquid, Lightning and Nostr
+    /// entries have no on-chain equivalent and are omitted.
+    pub fn to_address_list(&self) -> String {
+        self.bitcoin_l1_entries()
+            .map(|(_, _, address)| address)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Export the collection label in Electrum's label-export format
+    ///
+    /// Produces `{"<address>": "<label>", ...}` for every Bitcoin L1 address, as
+    /// imported through Electrum's Wallet > Labels > Import. Empty without a label.
+    pub fn to_electrum_labels_json(&self) -> serde_json::Value {
+        let labels: serde_json::Map<String, serde_json::Value> = match self.label() {
+            Some(label) => self
+                .bitcoin_l1_entries()
+                .map(|(_, _, address)| (address.to_string(), serde_json::json!(label)))
+                .collect(),
+            None => serde_json::Map::new(),
+        };
+        serde_json::Value::Object(labels)
+    }
+
+    /// Export the collection label as a BIP329 labels file, as imported by Sparrow
+    ///
+    /// One JSON record per line, `{"type": "addr", "ref": "<address>", "label":
+    /// "<label>"}`, for every Bitcoin L1 address. Empty without a label.
+    pub fn to_bip329_labels(&self) -> String {
+        let Some(label) = self.label() else {
+            return String::new();
+        };
+        self.bitcoin_l1_entries()
+            .map(|(_, _, address)| {
+                serde_json::json!({ "type": "addr", "ref": address, "label": label }).to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Export the Bitcoin L1 addresses as checksummed `addr()` output descriptors
+    ///
+    /// The descriptors can be imported into descriptor wallets such as Bitcoin
+    /// Core (`importdescriptors`) to watch the addresses.
+    pub fn to_addr_descriptors(&self) -> Vec<String> {
+        self.bitcoin_l1_entries()
+            .filter_map(|(_, _, address)| {
+                let descriptor = format!("addr({})", address);
+                let checksum = descriptor_checksum(&descriptor)?;
+                Some(format!("{}#{}", descriptor, checksum))
+            })
+            .collect()
+    }
+
+    /// Get the collection label from the metadata, if any
+    fn label(&self) -> Option<&str> {
+        self.metadata.as_ref()?.label.as_deref()
+    }
+
+    /// Iterate the Bitcoin L1 entries in canonical type order
+    fn bitcoin_l1_entries(&self) -> impl Iterator<Item = (AddressType, usize, &str)> + '_ {
+        self.iter_typed()
+            .filter(|(address_type, _, _)| AddressType::BITCOIN_L1.contains(address_type))
+    }
+
+    /// Merge another collection into this one
+    ///
+    /// Per-type address lists, including unknown address types, are unioned (keeping
+    /// this collection's order first and dropping duplicates), metadata fields prefer this collection's non-None values,
+    /// `created_at` becomes the newer timestamp and `version` the newer format version.
+    pub fn merge(&mut self, other: BitcoinAddresses) {
+        self.merge_with_strategy(other, MetadataMergeStrategy::PreferSelf);
+    }
+
+    /// Merge another collection into this one with an explicit metadata strategy
+    pub fn merge_with_strategy(&mut self, other: BitcoinAddresses, strategy: MetadataMergeStrategy) {
+        for (address_type, other_addresses) in other.addresses {
+            let addresses = self.addresses.entry(address_type).or_default();
+            for address in other_addresses {
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+
+        for (type_name, other_addresses) in other.unknown {
+            let addresses = self.unknown.entry(type_name).or_default();
+            for address in other_addresses {
+                if !addresses.contains(&address) {
+                    addresses.push(address);
+                }
+            }
+        }
+
+        self.metadata = match (self.metadata.take(), other.metadata) {
+            (Some(ours), Some(theirs)) => Some(match strategy {
+                MetadataMergeStrategy::PreferSelf => ours.or(theirs),
+                MetadataMergeStrategy::PreferOther => theirs.or(ours),
+            }),
+            (ours, theirs) => ours.or(theirs),
+        };
+
+        for (address, annotation) in other.annotations {
+            self.annotations.entry(address).or_insert(annotation);
+        }
+
+        self.created_at = self.created_at.max(other.created_at);
+        self.version = self.version.max(other.version);
+        // The merged payload no longer matches either signature
+        self.signature = None;
+    }
+
+    /// Add a human-readable Lightning Address (`name@domain`, LUD-16)
+    ///
+    /// It is stored with the node ids under `AddressType::Lightning`; use
+    /// `lightning_addresses` to get only the Lightning Addresses back. The address
+    /// is trimmed and lowercased, and `UbaError::InputValidation` is returned if it
+    /// is not a valid Lightning Address.
+    pub fn add_lightning_address(&mut self, address: &str) -> Result<(), crate::UbaError> {
+        let address = address.trim().to_ascii_lowercase();
+        crate::error::validation::validate_lightning_address(&address)?;
+        self.add_address(AddressType::Lightning, address);
+        Ok(())
+    }
+
+    /// Get the Lightning Addresses (`name@domain`) among the Lightning entries
+    pub fn lightning_addresses(&self) -> Vec<&str> {
+        self.get_addresses(&AddressType::Lightning)
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .filter(|address| crate::address::is_lightning_address(address))
+            .collect()
+    }
+
+    /// Attach a free-form annotation to an address, replacing any previous one
+    pub fn annotate(&mut self, address: impl Into<String>, annotation: impl Into<String>) {
+        self.annotations.insert(address.into(), annotation.into());
+    }
+
+    /// Get the annotation attached to an address, if any
+    pub fn annotation(&self, address: &str) -> Option<&str> {
+        self.annotations.get(address).map(String::as_str)
+    }
+
+    /// Tag a Liquid address with the asset id it is intended to receive
+    ///
+    /// Returns `UbaError::InputValidation` if `address` is not one of the
+    /// collection's Liquid addresses or `asset_id` is not a 64-character hex asset id.
+    pub fn set_liquid_asset(
+        &mut self,
+        address: &str,
+        asset_id: &str,
+    ) -> Result<(), crate::UbaError> {
+        let is_liquid = self
+            .get_addresses(&AddressType::Liquid)
+            .is_some_and(|addresses| addresses.iter().any(|a| a == address));
+        if !is_liquid {
+            return Err(crate::UbaError::InputValidation(format!(
+                "{} is not a Liquid address of this collection",
+                address
+            )));
+        }
+
+        let asset_id = asset_id
+            .trim()
+            .parse::<elements::AssetId>()
+            .map_err(|e| crate::UbaError::InputValidation(format!("Invalid asset id: {}", e)))?;
+        self.annotate(address, asset_id.to_string());
+        Ok(())
+    }
+
+    /// Sign the payload with the Nostr identity key of `seed`
+    ///
+    /// The identity key honors the BIP39 passphrase in `config`, like the keys
+    /// that author the seed's events. Readers can then check with
+    /// `verify_signature` that the addresses were published by the seed holder
+    /// and not altered in transit. Any later change to the addresses,
+    /// `created_at` or `version` invalidates the signature.
+    pub fn sign_with_seed(&mut self, seed: &str, config: &UbaConfig) -> Result<(), crate::UbaError> {
+        use bitcoin::secp256k1::{Keypair, Secp256k1};
+
+        let secp = Secp256k1::new();
+        let secret_key = crate::address::identity_secret_key(seed, config)?;
+        let keypair = Keypair::from_secret_key(&secp, &secret_key);
+        let signature = secp.sign_schnorr_no_aux_rand(&self.signing_message()?, &keypair);
+
+        self.signature = Some(PayloadSignature {
+            public_key: keypair.x_only_public_key().0.to_string(),
+            signature: signature.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Verify the payload signature, optionally requiring a specific signer
+    ///
+    /// `expected_signer` is a hex x-only public key, such as the hex form of the
+    /// publisher's Nostr public key. Returns `UbaError::InvalidSignature` if the
+    /// signature is missing, malformed, made by another key or does not match the
+    /// payload.
+    pub fn verify_signature(&self, expected_signer: Option<&str>) -> Result<(), crate::UbaError> {
+        use crate::UbaError;
+        use bitcoin::secp256k1::{schnorr, Secp256k1, XOnlyPublicKey};
+        use std::str::FromStr;
+
+        let payload_signature = self
+            .signature
+            .as_ref()
+            .ok_or_else(|| UbaError::InvalidSignature("payload is not signed".to_string()))?;
+
+        if let Some(expected) = expected_signer {
+            if !payload_signature.public_key.eq_ignore_ascii_case(expected.trim()) {
+                return Err(UbaError::InvalidSignature(format!(
+                    "signed by {}, expected {}",
+                    payload_signature.public_key, expected
+                )));
+            }
+        }
+
+        let public_key = XOnlyPublicKey::from_str(&payload_signature.public_key)
+            .map_err(|e| UbaError::InvalidSignature(format!("invalid public key: {}", e)))?;
+        let signature = schnorr::Signature::from_str(&payload_signature.signature)
+            .map_err(|e| UbaError::InvalidSignature(format!("invalid signature: {}", e)))?;
+
+        Secp256k1::verification_only()
+            .verify_schnorr(&signature, &self.signing_message()?, &public_key)
+            .map_err(|_| {
+                UbaError::InvalidSignature("signature does not match the payload".to_string())
+            })
+    }
+
+    /// SHA-256 of the canonical JSON of the payload without metadata and signature
+    fn signing_message(&self) -> Result<bitcoin::secp256k1::Message, crate::UbaError> {
+        use bitcoin::hashes::{sha256, Hash};
+
+        let payload = BitcoinAddresses {
+            metadata: None,
+            signature: None,
+            ..self.clone()
+        };
+        let digest = sha256::Hash::hash(payload.to_canonical_json()?.as_bytes());
+        Ok(bitcoin::secp256k1::Message::from_digest(digest.to_byte_array()))
+    }
+
+    /// Serialize to compact JSON with every object's keys sorted
+    ///
+    /// Unlike `serde_json::to_string`, the output does not depend on `HashMap`
+    /// iteration order, so equal collections always produce the same bytes and can
+    /// be hashed or signed reproducibly. Address lists keep their order.
+    pub fn to_canonical_json(&self) -> Result<String, crate::UbaError> {
+        Ok(serde_json::to_string(&canonicalize_json(serde_json::to_value(self)?))?)
+    }
+
+    /// Compare this collection (the old state) against another (the new state)
+    ///
+    /// Address types follow their declaration order and addresses keep the order of
+    /// the collection they come from, so the diff is stable across runs.
+    pub fn diff(&self, other: &Self) -> AddressDiff {
+        let mut address_types: Vec<&AddressType> =
+            self.addresses.keys().chain(other.addresses.keys()).collect();
+        address_types.sort_by_key(|address_type| (*address_type).clone() as u8);
+        address_types.dedup();
+
+        let empty = Vec::new();
+        let by_type = address_types
+            .into_iter()
+            .map(|address_type| {
+                let old = self.addresses.get(address_type).unwrap_or(&empty);
+                let new = other.addresses.get(address_type).unwrap_or(&empty);
+
+                let type_diff = AddressTypeDiff {
+                    added: new.iter().filter(|a| !old.contains(a)).cloned().collect(),
+                    removed: old.iter().filter(|a| !new.contains(a)).cloned().collect(),
+                    unchanged: old.iter().filter(|a| new.contains(a)).cloned().collect(),
+                };
+                (address_type.clone(), type_diff)
+            })
+            .collect();
+
+        AddressDiff {
+            by_type,
+            metadata_changes: metadata_changes(self.metadata.as_ref(), other.metadata.as_ref()),
+        }
+    }
+}
+
+impl IntoIterator for BitcoinAddresses {
+    type Item = (AddressType, Vec<String>);
+    type IntoIter = std::vec::IntoIter<(AddressType, Vec<String>)>;
+
+    /// Consume the collection into its address types and addresses, in
+    /// `AddressType::ALL` order
+    fn into_iter(self) -> Self::IntoIter {
+        let mut entries: Vec<_> = self.addresses.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a BitcoinAddresses {
+    type Item = (&'a AddressType, &'a Vec<String>);
+    type IntoIter = std::vec::IntoIter<(&'a AddressType, &'a Vec<String>)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let mut entries: Vec<_> = self.addresses.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries.into_iter()
+    }
+}
+
+/// Changes to the addresses of a single type between two collections
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AddressTypeDiff {
+    /// Addresses present only in the new collection
+    pub added: Vec<String>,
+    /// Addresses present only in the old collection
+    pub removed: Vec<String>,
+    /// Addresses present in both collections
+    pub unchanged: Vec<String>,
+}
+
+impl AddressTypeDiff {
+    /// Check if any address was added or removed
+    pub fn has_changes(&self) -> bool {
+        !self.added.is_empty() || !self.removed.is_empty()
+    }
+}
+
+/// A change to a single metadata field between two collections
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetadataChange {
+    /// Name of the changed field (e.g. "label")
+    pub field: String,
+    /// Value in the old collection
+    pub old: Option<String>,
+    /// Value in the new collection
+    pub new: Option<String>,
+}
+
+/// Differences between two `BitcoinAddresses` collections
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AddressDiff {
+    /// Per-type changes, in `AddressType` declaration order
+    pub by_type: Vec<(AddressType, AddressTypeDiff)>,
+    /// Changed metadata fields, in declaration order
+    pub metadata_changes: Vec<MetadataChange>,
+}
+
+impl AddressDiff {
+    /// Get the changes for a specific address type
+    pub fn get(&self, address_type: &AddressType) -> Option<&AddressTypeDiff> {
+        self.by_type
+            .iter()
+            .find(|(t, _)| t == address_type)
+            .map(|(_, diff)| diff)
+    }
+
+    /// Check if the two collections differ at all
+    pub fn has_changes(&self) -> bool {
+        !self.metadata_changes.is_empty() || self.by_type.iter().any(|(_, d)| d.has_changes())
+    }
+}
+
+/// Compare metadata field by field
+fn metadata_changes(
+    old: Option<&AddressMetadata>,
+    new: Option<&AddressMetadata>,
+) -> Vec<MetadataChange> {
+    let fields = |metadata: Option<&AddressMetadata>| -> [(&'static str, Option<String>); 5] {
+        [
+            ("label", metadata.and_then(|m| m.label.clone())),
+            ("description", metadata.and_then(|m| m.description.clone())),
+            ("xpub", metadata.and_then(|m| m.xpub.clone())),
+            (
+                "derivation_paths",
+                metadata.and_then(|m| m.derivation_paths.as_ref().map(|p| p.join(","))),
+            ),
+            ("fingerprint", metadata.and_then(|m| m.fingerprint.clone())),
+        ]
+    };
+
+    fields(old)
+        .into_iter()
+        .zip(fields(new))
+        .filter(|((_, old_value), (_, new_value))| old_value != new_value)
+        .map(|((field, old_value), (_, new_value))| MetadataChange {
+            field: field.to_string(),
+            old: old_value,
+            new: new_value,
+        })
+        .collect()
+}
+
+/// Serialize the address map with entries in `AddressType::ALL` order
+///
+/// `HashMap` iteration order differs between instances, so without sorting the same
+/// addresses could serialize to different bytes and produce different event IDs.
+fn serialize_sorted_addresses<S: Serializer>(
+    addresses: &HashMap<AddressType, Vec<String>>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    let mut entries: Vec<_> = addresses.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    serializer.collect_map(entries)
+}
+
+/// Rebuild a JSON value with the keys of every object in sorted order
+fn canonicalize_json(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let sorted: BTreeMap<String, serde_json::Value> = map
+                .into_iter()
+                .map(|(key, value)| (key, canonicalize_json(value)))
+                .collect();
+            serde_json::Value::Object(sorted.into_iter().collect())
+        }
+        serde_json::Value::Array(items) => {
+            serde_json::Value::Array(items.into_iter().map(canonicalize_json).collect())
+        }
+        other => other,
+    }
+}
+
+/// Compute the BIP380 output descriptor checksum
+///
+/// Returns None if the descriptor contains a character outside the descriptor
+/// character set.
+fn descriptor_checksum(descriptor: &str) -> Option<String> {
+    const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+    const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+    fn polymod(c: u64, value: u64) -> u64 {
+        let c0 = c >> 35;
+        let mut c = ((c & 0x7_ffff_ffff) << 5) ^ value;
+        if c0 & 1 != 0 {
+            c ^= 0xf5_dee5_1989;
+        }
+        if c0 & 2 != 0 {
+            c ^= 0xa9_fdca_3312;
+        }
+        if c0 & 4 != 0 {
+            c ^= 0x1b_ab10_e32d;
+        }
+        if c0 & 8 != 0 {
+            c ^= 0x37_06b1_677a;
+        }
+        if c0 & 16 != 0 {
+            c ^= 0x64_4d62_6ffd;
+        }
+        c
+    }
+
+    let mut c = 1u64;
+    let mut class = 0u64;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = INPUT_CHARSET.find(ch)? as u64;
+        c = polymod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = polymod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = polymod(c, class);
+    }
+    for _ in 0..8 {
+        c = polymod(c, 0);
+    }
+    c ^= 1;
+
+    Some(
+        (0..8)
+            .map(|j| CHECKSUM_CHARSET[((c >> (5 * (7 - j))) & 31) as usize] as char)
+            .collect(),
+    )
+}
+
+impl Default for BitcoinAddresses {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resumable position for paged address generation
+///
+/// Records the next derivation index for each address type, so
+/// `AddressGenerator::generate_next_page` can continue where the previous page
+/// stopped. The cursor serializes to JSON for persisting between sessions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressCursor {
+    next_indices: BTreeMap<AddressType, u32>,
+}
+
+impl AddressCursor {
+    /// Create a cursor starting at index 0 for every type
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the index the next page starts at for an address type
+    pub fn next_index(&self, address_type: &AddressType) -> u32 {
+        self.next_indices.get(address_type).copied().unwrap_or(0)
+    }
+
+    /// Move an address type's next index forward by `count`
+    ///
+    /// Saturates instead of wrapping, so a cursor past the derivation index range
+    /// makes the next page fail rather than silently restart at a low index.
+    pub fn advance(&mut self, address_type: AddressType, count: usize) {
+        let count = u32::try_from(count).unwrap_or(u32::MAX);
+        let next = self.next_indices.entry(address_type).or_insert(0);
+        *next = next.saturating_add(count);
+    }
+}
+
+/// Optional metadata for address collections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressMetadata {
+    /// User-defined label for the address collection
+    pub label: Option<String>,
+    /// Description of the wallet or purpose
+    pub description: Option<String>,
+    /// Extended public key used for derivation (if applicable)
+    pub xpub: Option<String>,
+    /// Derivation paths used for address generation
+    pub derivation_paths: Option<Vec<String>>,
+    /// Fingerprint of the master key the derivation paths start from, as 8 hex
+    /// characters. Only set when `UbaConfig::include_fingerprint` is enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+impl AddressMetadata {
+    /// Fill every None field of this metadata from `fallback`
+    fn or(self, fallback: AddressMetadata) -> AddressMetadata {
+        AddressMetadata {
+            label: self.label.or(fallback.label),
+            description: self.description.or(fallback.description),
+            xpub: self.xpub.or(fallback.xpub),
+            derivation_paths: self.derivation_paths.or(fallback.derivation_paths),
+            fingerprint: self.fingerprint.or(fallback.fingerprint),
+        }
+    }
+}
+
+/// How metadata fields are resolved when merging two collections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetadataMergeStrategy {
+    /// Keep the receiving collection's fields, filling None fields from the other
+    #[default]
+    PreferSelf,
+    /// Take the other collection's fields, filling None fields from the receiver
+    PreferOther,
+}
+
+/// Parsed UBA components
+#[derive(Debug, Clone)]
+pub struct ParsedUba {
+    /// The Nostr event ID that contains the address data
+    pub nostr_id: String,
+    /// Optional label extracted from the UBA
+    pub label: Option<String>,
+    /// All query parameters of the UBA, including the label, URL-decoded. When a
+    /// key repeats, the first occurrence wins.
+    pub params: HashMap<String, String>,
+}
+
+/// A validated Unified Bitcoin Address
+///
+/// `Uba` wraps the Nostr event ID, the optional label and any additional query
+/// parameters, and keeps its canonical string form alongside them. It parses with
+/// `FromStr`, prints with `Display`, and implements `AsRef<str>`, so it can be passed
+/// anywhere a UBA string is accepted.
+///
+/// # Example
+/// ```rust
+/// use uba::Uba;
+///
+/// let uba: Uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef&label=my-wallet".parse()?;
+/// assert_eq!(uba.label(), Some("my-wallet"));
+/// assert_eq!(uba.to_string().parse::<Uba>()?, uba);
+/// # Ok::<(), uba::UbaError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Uba {
+    nostr_id: String,
+    label: Option<String>,
+    params: Vec<(String, String)>,
+    canonical: String,
+}
+
+impl Uba {
+    /// Create a UBA pointing to the given Nostr event ID (64 hex characters)
+    pub fn new(nostr_id: &str) -> Result<Self, crate::UbaError> {
+        format!("UBA:{}", nostr_id).parse()
+    }
+
+    /// Attach a label to the UBA, replacing any existing one
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self.canonical = self.to_canonical_string();
+        self
+    }
+
+    /// Attach an additional query parameter to the UBA
+    pub fn with_param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.params.push((key.into(), value.into()));
+        self.canonical = self.to_canonical_string();
+        self
+    }
+
+    /// Get the Nostr event ID (hex) holding the address data
+    pub fn nostr_id(&self) -> &str {
+        &self.nostr_id
+    }
+
+    /// Get the label, if any
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Get the query parameters other than the label, in order of appearance
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// Get the canonical UBA string
+    pub fn as_str(&self) -> &str {
+        &self.canonical
+    }
+
+    fn to_canonical_string(&self) -> String {
+        let mut uba = format!("UBA:{}", self.nostr_id);
+        if let Some(label) = &self.label {
+            uba.push_str(&format!("&label={}", urlencoding::encode(label)));
+        }
+        for (key, value) in &self.params {
+            uba.push_str(&format!(
+                "&{}={}",
+                urlencoding::encode(key),
+                urlencoding::encode(value)
+            ));
+        }
+        uba
+    }
+}
+
+impl std::str::FromStr for Uba {
+    type Err = crate::UbaError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parsed = crate::parse::parse_uba(s)?;
+        let params = crate::parse::parse_extra_params(s)?;
+
+        let mut uba = Uba {
+            nostr_id: parsed.nostr_id.to_lowercase(),
+            label: parsed.label,
+            params,
+            canonical: String::new(),
+        };
+        uba.canonical = uba.to_canonical_string();
+        Ok(uba)
+    }
+}
+
+impl std::fmt::Display for Uba {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.canonical)
+    }
+}
+
+impl AsRef<str> for Uba {
+    fn as_ref(&self) -> &str {
+        &self.canonical
+    }
+}
+
+impl From<Uba> for ParsedUba {
+    fn from(uba: Uba) -> Self {
+        let mut params = HashMap::new();
+        if let Some(label) = &uba.label {
+            params.insert("label".to_string(), label.clone());
+        }
+        for (key, value) in uba.params {
+            params.entry(key).or_insert(value);
+        }
+
+        ParsedUba {
+            nostr_id: uba.nostr_id,
+            label: uba.label,
+            params,
+        }
+    }
+}
+
+/// Result of generating and publishing a UBA
+#[derive(Debug, Clone)]
+pub struct GenerateOutcome {
+    /// The UBA string pointing to the published event
+    pub uba: String,
+    /// The Nostr event ID (hex) holding the address data
+    pub event_id: String,
+    /// The address collection that was published
+    pub addresses: BitcoinAddresses,
+    /// URLs of the relays that accepted the event
+    pub confirmed_relays: Vec<String>,
+}
+
+/// Result of an end-to-end `self_test` round trip
+#[derive(Debug, Clone)]
+pub struct SelfTestReport {
+    /// The Nostr event ID (hex) of the throwaway event
+    pub event_id: String,
+    /// Time taken to publish the event
+    pub publish_time: std::time::Duration,
+    /// Time taken to retrieve the event back
+    pub retrieve_time: std::time::Duration,
+    /// URLs of the relays that accepted the event
+    pub confirmed_relays: Vec<String>,
+    /// URLs of the relays that did not accept the event
+    pub failed_relays: Vec<String>,
+    /// Whether a deletion request for the event was accepted by any relay
+    pub deleted: bool,
+}
+
+/// A UBA event as stored on the relays, for diagnosing events that fail to decode
+///
+/// Returned by `NostrClient::fetch_raw_event`; nothing in it has been deserialized.
+#[derive(Debug, Clone)]
+pub struct RawUbaEvent {
+    /// The Nostr event ID (hex)
+    pub event_id: String,
+    /// Public key (hex) of the event author
+    pub author: String,
+    /// Event `created_at` in Unix seconds
+    pub created_at: u64,
+    /// Event kind
+    pub kind: u16,
+    /// All event tags
+    pub tags: Vec<Vec<String>>,
+    /// Event content exactly as published
+    pub content: String,
+    /// Decrypted content, if the event is encrypted and could be decrypted. For
+    /// metadata-only encryption this is the decrypted metadata block.
+    pub decrypted_content: Option<String>,
+    /// Why decryption failed, if it was attempted and failed
+    pub decryption_error: Option<String>,
+}
+
+/// UBA generation request, e.g. one entry of a `generate_batch`
+#[derive(Debug, Clone)]
+pub struct UbaGenerationRequest {
+    /// The seed phrase or private key material
+    pub seed: String,
+    /// Optional label for the UBA
+    pub label: Option<String>,
+    /// List of Nostr relay URLs; empty uses the relays of `config`
+    pub relay_urls: Vec<String>,
+    /// Configuration for the generation process
+    pub config: UbaConfig,
+}
+
+/// UBA retrieval request
+#[derive(Debug, Clone)]
+pub struct UbaRetrievalRequest {
+    /// The UBA string to parse and retrieve
+    pub uba: String,
+    /// List of Nostr relay URLs to query
+    pub relay_urls: Vec<String>,
+    /// Configuration for the retrieval process
+    pub config: UbaConfig,
+}
+
+/// Get a curated list of reliable public Nostr relays
+///
+/// These relays are selected for reliability and geographical distribution.
+/// Users can override this list through `UbaConfig::relay_preset`.
+pub fn default_public_relays() -> Vec<String> {
+    vec![
+        // Reliable relays with good uptime and performance
+        "wss://relay.damus.io".to_string(), // Damus (Cloudflare)
+        "wss://nos.lol".to_string(),        // NOS (Hetzner)
+        "wss://relay.snort.social".to_string(), // Snort (Cloudflare)
+        "wss://nostr.wine".to_string(),     // Nostr Wine (Cloudflare)
+        "wss://relay.nostr.band".to_string(), // Nostr Band (Hetzner) - supports search
+        "wss://nostr.mutinywallet.com".to_string(), // Mutiny Wallet (Amazon)
+        "wss://relay.primal.net".to_string(), // Primal (Cloudflare)
+        "wss://relay.nostrati.com".to_string(), // Nostrati (Digital Ocean)
+        "wss://nostr.sethforprivacy.com".to_string(), // Seth for Privacy (Privacy-focused)
+        "wss://offchain.pub".to_string(),   // Offchain Pub (MULTACOM)
+        "wss://relay.nostrplebs.com".to_string(), // Nostr Plebs (Hetzner)
+        "wss://purplepag.es".to_string(),   // Purple Pages (Constant Company)
+    ]
+}
 
 /// Extended public relay list for high-availability scenarios
 ///
@@ -445,12 +2036,15 @@ mod tests {
         
         // All address types should be enabled by default
         assert!(config.is_address_type_enabled(&AddressType::P2PKH));
-        assert!(config.is_address_type_enabled(&AddressType::P2SH));
+        assert!(config.is_address_type_enabled(&AddressType::P2SHWPKH));
         assert!(config.is_address_type_enabled(&AddressType::P2WPKH));
         assert!(config.is_address_type_enabled(&AddressType::P2TR));
         assert!(config.is_address_type_enabled(&AddressType::Liquid));
         assert!(config.is_address_type_enabled(&AddressType::Lightning));
         assert!(config.is_address_type_enabled(&AddressType::Nostr));
+
+        // BOLT12 offers are opt-in
+        assert!(!config.is_address_type_enabled(&AddressType::LightningOffer));
     }
 
     #[test]
@@ -474,7 +2068,7 @@ mod tests {
         // Disable all Bitcoin L1
         config.disable_bitcoin_l1();
         assert!(!config.is_address_type_enabled(&AddressType::P2PKH));
-        assert!(!config.is_address_type_enabled(&AddressType::P2SH));
+        assert!(!config.is_address_type_enabled(&AddressType::P2SHWPKH));
         assert!(!config.is_address_type_enabled(&AddressType::P2WPKH));
         assert!(!config.is_address_type_enabled(&AddressType::P2TR));
         // L2 should still be enabled
@@ -484,7 +2078,7 @@ mod tests {
         // Re-enable Bitcoin L1
         config.enable_bitcoin_l1();
         assert!(config.is_address_type_enabled(&AddressType::P2PKH));
-        assert!(config.is_address_type_enabled(&AddressType::P2SH));
+        assert!(config.is_address_type_enabled(&AddressType::P2SHWPKH));
         assert!(config.is_address_type_enabled(&AddressType::P2WPKH));
         assert!(config.is_address_type_enabled(&AddressType::P2TR));
     }
@@ -529,6 +2123,551 @@ mod tests {
         assert!(enabled.contains(&AddressType::P2PKH));
     }
 
+    #[test]
+    fn test_address_type_string_names() {
+        // Every type roundtrips through its kebab-case name
+        for address_type in AddressType::ALL {
+            let parsed: AddressType = address_type.as_kebab_str().parse().unwrap();
+            assert_eq!(parsed, address_type);
+        }
+
+        // Serialized names and the P2SHWPKH alias parse as well
+        for address_type in AddressType::ALL {
+            let json = serde_json::to_string(&address_type).unwrap();
+            let parsed: AddressType = json.trim_matches('"').parse().unwrap();
+            assert_eq!(parsed, address_type);
+        }
+        assert_eq!("P2SHWPKH".parse::<AddressType>().unwrap(), AddressType::P2SHWPKH);
+
+        assert_eq!("Taproot".parse::<AddressType>().unwrap(), AddressType::P2TR);
+        assert_eq!(" P2WPKH ".parse::<AddressType>().unwrap(), AddressType::P2WPKH);
+        assert!(matches!(
+            "p2wsh".parse::<AddressType>(),
+            Err(crate::UbaError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_relay_presets() {
+        let mut config = UbaConfig::default();
+        assert_eq!(config.get_relay_urls(), default_public_relays());
+
+        config.use_extended_relays();
+        assert_eq!(config.get_relay_urls(), extended_public_relays());
+        assert!(config.get_relay_urls().len() > default_public_relays().len());
+
+        config.add_custom_relay("wss://relay.example.com".to_string());
+        assert_eq!(config.get_relay_urls(), vec!["wss://relay.example.com".to_string()]);
+        config.add_custom_relay("wss://relay2.example.com".to_string());
+        assert_eq!(config.get_relay_urls().len(), 2);
+
+        config.use_default_relays();
+        assert_eq!(config.relay_preset, RelayPreset::Default);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_legacy_custom_relays() {
+        let mut config = UbaConfig {
+            custom_relays: Some(vec!["wss://legacy.example.com".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(config.get_relay_urls(), vec!["wss://legacy.example.com".to_string()]);
+
+        // Adding a relay extends the legacy list, now held by the preset
+        config.add_custom_relay("wss://relay.example.com".to_string());
+        assert!(config.custom_relays.is_none());
+        assert_eq!(
+            config.relay_preset,
+            RelayPreset::Custom(vec![
+                "wss://legacy.example.com".to_string(),
+                "wss://relay.example.com".to_string(),
+            ])
+        );
+
+        config.custom_relays = Some(vec!["wss://legacy.example.com".to_string()]);
+        config.use_default_relays();
+        assert_eq!(config.get_relay_urls(), default_public_relays());
+    }
+
+    #[test]
+    fn test_address_type_catalog() {
+        let catalog = AddressType::catalog();
+        assert_eq!(catalog.len(), AddressType::all().len());
+        assert_eq!(catalog[0], (AddressType::P2PKH, "Legacy Bitcoin address (P2PKH)"));
+
+        // Every type is listed once, with its own description
+        let types: std::collections::HashSet<&AddressType> =
+            catalog.iter().map(|(address_type, _)| address_type).collect();
+        assert_eq!(types.len(), AddressType::ALL.len());
+        for (address_type, description) in &catalog {
+            assert_eq!(*description, address_type.description());
+        }
+        let descriptions: std::collections::HashSet<&str> =
+            catalog.iter().map(|(_, description)| *description).collect();
+        assert_eq!(descriptions.len(), AddressType::ALL.len());
+    }
+
+    #[test]
+    fn test_address_type_bip_purpose() {
+        assert_eq!(AddressType::P2PKH.bip_purpose(), Some(44));
+        assert_eq!(AddressType::P2SHWPKH.bip_purpose(), Some(49));
+        assert_eq!(AddressType::P2WPKH.bip_purpose(), Some(84));
+        assert_eq!(AddressType::P2TR.bip_purpose(), Some(86));
+        assert_eq!(AddressType::Liquid.bip_purpose(), None);
+        assert_eq!(AddressType::Nostr.bip_purpose(), None);
+    }
+
+    #[test]
+    fn test_validate_address_counts() {
+        let mut config = UbaConfig::default();
+        assert!(config.validate_address_counts().is_ok());
+
+        config.set_address_count(AddressType::P2WPKH, DEFAULT_MAX_ADDRESS_COUNT);
+        assert!(config.validate_address_counts().is_ok());
+
+        config.set_address_count(AddressType::P2WPKH, DEFAULT_MAX_ADDRESS_COUNT + 1);
+        assert!(matches!(
+            config.validate_address_counts(),
+            Err(crate::UbaError::Config(_))
+        ));
+
+        // Disabled types are not generated, so their counts are not checked
+        config.set_address_type_enabled(AddressType::P2WPKH, false);
+        assert!(config.validate_address_counts().is_ok());
+
+        // Raising the limit cannot bypass the derivation index range
+        config.max_address_count = usize::MAX;
+        config.set_address_count(AddressType::P2TR, usize::MAX);
+        assert!(matches!(
+            config.validate_address_counts(),
+            Err(crate::UbaError::Config(_))
+        ));
+
+        // The range is counted from start_index
+        config.set_address_count(AddressType::P2TR, 10);
+        config.start_index = (1 << 31) - 10;
+        assert!(config.validate_address_counts().is_ok());
+        config.start_index += 1;
+        assert!(matches!(
+            config.validate_address_counts(),
+            Err(crate::UbaError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_bitcoin_addresses_diff() {
+        let mut old = BitcoinAddresses::new();
+        old.add_address(AddressType::P2WPKH, "bc1qold".to_string());
+        old.add_address(AddressType::P2WPKH, "bc1qkept".to_string());
+        old.add_address(AddressType::Lightning, "02node".to_string());
+        old.metadata = Some(AddressMetadata {
+            label: Some("old-label".to_string()),
+            description: None,
+            xpub: None,
+            derivation_paths: None,
+            fingerprint: None,
+        });
+
+        let mut new = BitcoinAddresses::new();
+        new.add_address(AddressType::P2WPKH, "bc1qkept".to_string());
+        new.add_address(AddressType::P2WPKH, "bc1qnew".to_string());
+        new.add_address(AddressType::P2TR, "bc1ptaproot".to_string());
+        new.metadata = Some(AddressMetadata {
+            label: Some("new-label".to_string()),
+            description: None,
+            xpub: None,
+            derivation_paths: None,
+            fingerprint: None,
+        });
+
+        let diff = old.diff(&new);
+        assert!(diff.has_changes());
+
+        // Types follow declaration order for reproducible output
+        let types: Vec<AddressType> = diff.by_type.iter().map(|(t, _)| t.clone()).collect();
+        assert_eq!(types, vec![AddressType::P2WPKH, AddressType::P2TR, AddressType::Lightning]);
+
+        let p2wpkh = diff.get(&AddressType::P2WPKH).unwrap();
+        assert_eq!(p2wpkh.added, vec!["bc1qnew".to_string()]);
+        assert_eq!(p2wpkh.removed, vec!["bc1qold".to_string()]);
+        assert_eq!(p2wpkh.unchanged, vec!["bc1qkept".to_string()]);
+
+        assert_eq!(diff.get(&AddressType::P2TR).unwrap().added, vec!["bc1ptaproot".to_string()]);
+        assert_eq!(diff.get(&AddressType::Lightning).unwrap().removed, vec!["02node".to_string()]);
+
+        assert_eq!(
+            diff.metadata_changes,
+            vec![MetadataChange {
+                field: "label".to_string(),
+                old: Some("old-label".to_string()),
+                new: Some("new-label".to_string()),
+            }]
+        );
+
+        // A collection compared with itself has no changes
+        assert!(!new.diff(&new).has_changes());
+    }
+
+    #[test]
+    fn test_split_by_event_size() {
+        let mut addresses = BitcoinAddresses::new();
+        for i in 0..200 {
+            addresses.add_address(AddressType::P2WPKH, format!("bc1qaddress{:04}", i));
+            addresses.add_address(AddressType::P2TR, format!("bc1paddress{:04}", i));
+        }
+        addresses.metadata = Some(AddressMetadata {
+            label: Some("big".to_string()),
+            description: None,
+            xpub: None,
+            derivation_paths: None,
+            fingerprint: None,
+        });
+
+        for encrypted in [false, true] {
+            let key = encrypted.then_some([0u8; 32]);
+            let parts = addresses
+                .split_by_event_size(4_000, 200, encrypted, DEFAULT_EVENT_KIND)
+                .unwrap();
+            assert!(parts.len() > 1);
+
+            // Every part fits, only the first carries metadata, and nothing is lost
+            for (i, part) in parts.iter().enumerate() {
+                assert!(part.estimated_event_size(key.as_ref(), DEFAULT_EVENT_KIND) + 200 <= 4_000);
+                assert_eq!(part.metadata.is_some(), i == 0);
+            }
+            let total: usize = parts.iter().map(|p| p.len()).sum();
+            assert_eq!(total, addresses.len());
+        }
+
+        // A collection that fits stays whole
+        let parts = addresses
+            .split_by_event_size(1_000_000, 200, false, DEFAULT_EVENT_KIND)
+            .unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].addresses, addresses.addresses);
+
+        // A limit too small for any address is rejected
+        assert!(matches!(
+            addresses.split_by_event_size(100, 0, false, DEFAULT_EVENT_KIND),
+            Err(crate::UbaError::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_address_by_index() {
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qfirst".to_string());
+        addresses.add_address(AddressType::P2WPKH, "bc1qsecond".to_string());
+
+        assert_eq!(addresses.first(&AddressType::P2WPKH), Some("bc1qfirst"));
+        assert_eq!(addresses.get_address(&AddressType::P2WPKH, 1), Some("bc1qsecond"));
+        assert_eq!(addresses.get_address(&AddressType::P2WPKH, 2), None);
+        assert_eq!(addresses.first(&AddressType::Lightning), None);
+    }
+
+    #[test]
+    fn test_iter_typed_order() {
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::Nostr, "npub1first".to_string());
+        addresses.add_address(AddressType::P2WPKH, "bc1qfirst".to_string());
+        addresses.add_address(AddressType::P2WPKH, "bc1qsecond".to_string());
+        addresses.add_address(AddressType::P2PKH, "1first".to_string());
+
+        let triples: Vec<_> = addresses.iter_typed().collect();
+        assert_eq!(
+            triples,
+            vec![
+                (AddressType::P2PKH, 0, "1first"),
+                (AddressType::P2WPKH, 0, "bc1qfirst"),
+                (AddressType::P2WPKH, 1, "bc1qsecond"),
+                (AddressType::Nostr, 0, "npub1first"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_classified_rebuilds_generated_collection() {
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let generated = crate::AddressGenerator::new(UbaConfig::default())
+            .generate_addresses(seed, None)
+            .unwrap();
+
+        let mut flat = generated.get_all_addresses();
+        let rebuilt = BitcoinAddresses::from_classified(&flat, bitcoin::Network::Bitcoin);
+        assert_eq!(rebuilt.addresses, generated.addresses);
+        assert!(rebuilt.unknown.is_empty());
+
+        // Testnet addresses and junk do not classify on mainnet
+        flat.push("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx".to_string());
+        flat.push("not-an-address".to_string());
+        let rebuilt = BitcoinAddresses::from_classified(&flat, bitcoin::Network::Bitcoin);
+        assert_eq!(rebuilt.addresses, generated.addresses);
+        assert_eq!(rebuilt.unknown["Unknown"].len(), 2);
+
+        assert!(matches!(
+            BitcoinAddresses::try_from_classified(&flat, bitcoin::Network::Bitcoin),
+            Err(crate::UbaError::InputValidation(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_address_types_are_kept_aside() {
+        let json = r#"{
+            "addresses": {
+                "P2WPKH": ["bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"],
+                "SilentPayment": ["sp1qexample"]
+            },
+            "metadata": null,
+            "created_at": 1700000000,
+            "version": 1
+        }"#;
+
+        let addresses: BitcoinAddresses = serde_json::from_str(json).unwrap();
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(
+            addresses.first(&AddressType::P2WPKH),
+            Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+        );
+        assert_eq!(addresses.unknown["SilentPayment"], vec!["sp1qexample".to_string()]);
+
+        // Unknown entries survive a round trip
+        let reparsed: BitcoinAddresses =
+            serde_json::from_str(&serde_json::to_string(&addresses).unwrap()).unwrap();
+        assert_eq!(reparsed.addresses, addresses.addresses);
+        assert_eq!(reparsed.unknown, addresses.unknown);
+
+        // Merging unions unknown entries like known ones
+        let mut other = BitcoinAddresses::new();
+        other.unknown.insert(
+            "SilentPayment".to_string(),
+            vec!["sp1qexample".to_string(), "sp1qother".to_string()],
+        );
+        other.unknown.insert("Ark".to_string(), vec!["ark1example".to_string()]);
+        let mut merged = addresses.clone();
+        merged.merge(other);
+        assert_eq!(
+            merged.unknown["SilentPayment"],
+            vec!["sp1qexample".to_string(), "sp1qother".to_string()]
+        );
+        assert_eq!(merged.unknown["Ark"], vec!["ark1example".to_string()]);
+
+        // Splitting keeps unknown entries in the first part only
+        let mut big = addresses.clone();
+        for i in 0..200 {
+            big.add_address(AddressType::P2WPKH, format!("bc1qaddress{:04}", i));
+        }
+        let parts = big.split_by_event_size(4_000, 200, false, DEFAULT_EVENT_KIND).unwrap();
+        assert!(parts.len() > 1);
+        assert_eq!(parts[0].unknown, addresses.unknown);
+        assert!(parts[1..].iter().all(|part| part.unknown.is_empty()));
+    }
+
+    #[test]
+    fn test_wallet_import_exports() {
+        let mut addresses = BitcoinAddresses::new();
+        addresses.metadata = Some(AddressMetadata {
+            label: Some("savings".to_string()),
+            description: None,
+            xpub: None,
+            derivation_paths: None,
+            fingerprint: None,
+        });
+        addresses.add_address(AddressType::P2WPKH, "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string());
+        addresses.add_address(AddressType::P2SHWPKH, "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy".to_string());
+        addresses.add_address(AddressType::Lightning, "02abc".to_string());
+        addresses.add_address(AddressType::Nostr, "npub1abc".to_string());
+
+        assert_eq!(
+            addresses.to_address_list(),
+            "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy\nbc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq"
+        );
+
+        let labels = addresses.to_electrum_labels_json();
+        assert_eq!(labels.as_object().unwrap().len(), 2);
+        assert_eq!(labels["3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"], "savings");
+
+        let bip329: Vec<serde_json::Value> = addresses
+            .to_bip329_labels()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(bip329.len(), 2);
+        assert_eq!(
+            bip329[1],
+            serde_json::json!({
+                "type": "addr",
+                "ref": "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq",
+                "label": "savings"
+            })
+        );
+
+        let descriptors = addresses.to_addr_descriptors();
+        assert_eq!(descriptors.len(), 2);
+        assert!(descriptors[1].starts_with("addr(bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq)#"));
+
+        // Without a label there is nothing to label
+        addresses.metadata = None;
+        assert_eq!(addresses.to_electrum_labels_json(), serde_json::json!({}));
+        assert!(addresses.to_bip329_labels().is_empty());
+    }
+
+    #[test]
+    fn test_descriptor_checksum() {
+        // Test vector from BIP380
+        assert_eq!(descriptor_checksum("raw(deadbeef)").as_deref(), Some("89f8spxm"));
+        assert_eq!(descriptor_checksum("addr(\u{e9})"), None);
+    }
+
+    #[test]
+    fn test_address_order_is_stable() {
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::Nostr, "npub1first".to_string());
+        addresses.add_address(AddressType::Liquid, "lq1first".to_string());
+        addresses.add_address(AddressType::P2TR, "bc1pfirst".to_string());
+        addresses.add_address(AddressType::P2TR, "bc1psecond".to_string());
+        addresses.add_address(AddressType::P2PKH, "1first".to_string());
+
+        let expected = vec!["1first", "bc1pfirst", "bc1psecond", "lq1first", "npub1first"];
+        for _ in 0..10 {
+            assert_eq!(addresses.get_all_addresses(), expected);
+        }
+
+        // Overridden types come first, the rest keep the canonical order
+        assert_eq!(
+            addresses.get_all_addresses_in_order(&[AddressType::Nostr, AddressType::P2TR]),
+            vec!["npub1first", "bc1pfirst", "bc1psecond", "1first", "lq1first"]
+        );
+    }
+
+    #[test]
+    fn test_all_lists_every_variant_once() {
+        // Exhaustive on purpose: a new variant fails to compile here until it is
+        // given a position, and then fails the assertions until it is in ALL
+        fn position(address_type: &AddressType) -> usize {
+            match address_type {
+                AddressType::P2PKH => 0,
+                AddressType::P2SHWPKH => 1,
+                AddressType::P2WPKH => 2,
+                AddressType::P2TR => 3,
+                AddressType::Liquid => 4,
+                AddressType::Lightning => 5,
+                AddressType::LightningOffer => 6,
+                AddressType::Nostr => 7,
+            }
+        }
+        for (i, address_type) in AddressType::ALL.iter().enumerate() {
+            assert_eq!(position(address_type), i);
+        }
+
+        let mut config = UbaConfig::default();
+        config.disable_all_address_types();
+        assert!(config.get_enabled_address_types().is_empty());
+        config.enable_all_address_types();
+        assert_eq!(config.get_enabled_address_types(), AddressType::ALL.to_vec());
+        config.set_all_counts(3);
+        assert!(AddressType::ALL.iter().all(|t| config.get_address_count(t) == 3));
+    }
+
+    #[test]
+    fn test_address_type_ordering() {
+        let mut sorted = AddressType::ALL.to_vec();
+        sorted.reverse();
+        sorted.sort();
+        assert_eq!(sorted, AddressType::ALL.to_vec());
+        assert!(AddressType::P2PKH < AddressType::P2TR);
+        assert!(AddressType::Liquid < AddressType::Lightning);
+
+        // Serialization still uses the variant names
+        assert_eq!(serde_json::to_string(&AddressType::Liquid).unwrap(), "\"Liquid\"");
+
+        let map: std::collections::BTreeMap<AddressType, usize> = [
+            (AddressType::Nostr, 2),
+            (AddressType::P2WPKH, 1),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(map.keys().next(), Some(&AddressType::P2WPKH));
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qaaa".to_string());
+        addresses.add_address(AddressType::P2WPKH, "bc1qbbb".to_string());
+        assert!(addresses.find_duplicates().is_empty());
+
+        // Repeated within a type and across types
+        addresses.add_address(AddressType::P2WPKH, "bc1qaaa".to_string());
+        addresses.add_address(AddressType::P2TR, "bc1qbbb".to_string());
+
+        assert_eq!(
+            addresses.find_duplicates(),
+            vec![
+                (
+                    "bc1qaaa".to_string(),
+                    vec![AddressType::P2WPKH, AddressType::P2WPKH]
+                ),
+                (
+                    "bc1qbbb".to_string(),
+                    vec![AddressType::P2WPKH, AddressType::P2TR]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bitcoin_addresses_merge() {
+        let mut l1 = BitcoinAddresses::new();
+        l1.add_address(AddressType::P2WPKH, "bc1qfirst".to_string());
+        l1.add_address(AddressType::P2WPKH, "bc1qshared".to_string());
+        l1.created_at = 100;
+        l1.metadata = Some(AddressMetadata {
+            label: Some("l1".to_string()),
+            description: None,
+            xpub: None,
+            derivation_paths: None,
+            fingerprint: None,
+        });
+
+        let mut l2 = BitcoinAddresses::new();
+        // Overlapping type with one duplicate address
+        l2.add_address(AddressType::P2WPKH, "bc1qshared".to_string());
+        l2.add_address(AddressType::P2WPKH, "bc1qsecond".to_string());
+        // Disjoint types
+        l2.add_address(AddressType::Lightning, "02node".to_string());
+        l2.add_address(AddressType::Liquid, "lq1liquid".to_string());
+        l2.created_at = 200;
+        l2.metadata = Some(AddressMetadata {
+            label: Some("l2".to_string()),
+            description: Some("lightning and liquid".to_string()),
+            xpub: None,
+            derivation_paths: None,
+            fingerprint: None,
+        });
+
+        let mut merged = l1.clone();
+        merged.merge(l2.clone());
+
+        assert_eq!(
+            merged.get_addresses(&AddressType::P2WPKH).unwrap(),
+            &vec!["bc1qfirst".to_string(), "bc1qshared".to_string(), "bc1qsecond".to_string()]
+        );
+        assert_eq!(merged.get_addresses(&AddressType::Lightning).unwrap(), &vec!["02node".to_string()]);
+        assert_eq!(merged.get_addresses(&AddressType::Liquid).unwrap(), &vec!["lq1liquid".to_string()]);
+        assert_eq!(merged.len(), 5);
+        assert_eq!(merged.created_at, 200);
+
+        // Non-None fields are preferred, the receiver wins on conflicts
+        let metadata = merged.metadata.unwrap();
+        assert_eq!(metadata.label, Some("l1".to_string()));
+        assert_eq!(metadata.description, Some("lightning and liquid".to_string()));
+
+        let mut merged = l1;
+        merged.merge_with_strategy(l2, MetadataMergeStrategy::PreferOther);
+        assert_eq!(merged.metadata.unwrap().label, Some("l2".to_string()));
+    }
+
     #[test]
     fn test_address_filtering_with_counts() {
         let mut config = UbaConfig::default();
@@ -548,4 +2687,201 @@ mod tests {
         let enabled = config.get_enabled_address_types();
         assert!(!enabled.contains(&AddressType::Lightning));
     }
+
+    #[test]
+    fn test_set_encryption_key_from_hex_normalizes_input() {
+        let key_hex = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+
+        let mut config = UbaConfig::default();
+        config.set_encryption_key_from_hex(&format!("0x{}", key_hex)).unwrap();
+        assert_eq!(config.get_encryption_key_hex().as_deref(), Some(key_hex));
+
+        let mut config = UbaConfig::default();
+        config.set_encryption_key_from_hex(&format!("  {}\n", key_hex)).unwrap();
+        assert_eq!(config.get_encryption_key_hex().as_deref(), Some(key_hex));
+
+        // Wrong length and bad characters are reported separately
+        let err = config.set_encryption_key_from_hex(&key_hex[..62]).unwrap_err();
+        assert!(err.to_string().contains("got 62"));
+        let err = config
+            .set_encryption_key_from_hex(&format!("{}zz", &key_hex[..62]))
+            .unwrap_err();
+        assert!(err.to_string().contains("non-hex"));
+    }
+
+    #[test]
+    fn test_payload_signature() {
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2WPKH, "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu".to_string());
+        addresses.add_address(
+            AddressType::P2TR,
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr".to_string(),
+        );
+        addresses.metadata = Some(AddressMetadata {
+            label: Some("signed".to_string()),
+            description: None,
+            xpub: None,
+            derivation_paths: None,
+            fingerprint: None,
+        });
+
+        assert!(matches!(addresses.verify_signature(None), Err(crate::UbaError::InvalidSignature(_))));
+        addresses.sign_with_seed(seed, &UbaConfig::default()).unwrap();
+        let signer = addresses.signature.as_ref().unwrap().public_key.clone();
+        addresses.verify_signature(Some(&signer)).unwrap();
+
+        // Survives a JSON round trip and metadata changes
+        let mut decoded: BitcoinAddresses =
+            serde_json::from_str(&serde_json::to_string(&addresses).unwrap()).unwrap();
+        decoded.metadata = None;
+        decoded.verify_signature(Some(&signer)).unwrap();
+
+        // Signed by a different seed
+        let other = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        let mut foreign = addresses.clone();
+        foreign.sign_with_seed(other, &UbaConfig::default()).unwrap();
+        foreign.verify_signature(None).unwrap();
+        assert!(foreign.verify_signature(Some(&signer)).is_err());
+
+        // Tampered addresses or timestamp
+        let mut tampered = addresses.clone();
+        tampered.add_address(AddressType::P2PKH, "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA".to_string());
+        assert!(tampered.verify_signature(None).is_err());
+        let mut tampered = addresses.clone();
+        tampered.created_at += 1;
+        assert!(tampered.verify_signature(None).is_err());
+    }
+
+    #[test]
+    fn test_canonical_json_is_byte_identical() {
+        let build = |types: &[AddressType]| {
+            let mut addresses = BitcoinAddresses::new();
+            addresses.created_at = 1_700_000_000;
+            for address_type in types {
+                addresses.add_address(address_type.clone(), format!("{:?}-0", address_type));
+                addresses.add_address(address_type.clone(), format!("{:?}-1", address_type));
+            }
+            addresses.unknown.insert("Zeta".to_string(), vec!["z".to_string()]);
+            addresses.unknown.insert("Alpha".to_string(), vec!["a".to_string()]);
+            addresses
+        };
+
+        let mut reversed = AddressType::ALL.to_vec();
+        reversed.reverse();
+        let first = build(&AddressType::ALL).to_canonical_json().unwrap();
+        let second = build(&reversed).to_canonical_json().unwrap();
+        assert_eq!(first, second);
+
+        assert!(first.starts_with(r#"{"addresses":{"Lightning":["Lightning-0","Lightning-1"]"#));
+        assert!(first.ends_with(r#""unknown":{"Alpha":["a"],"Zeta":["z"]},"version":1}"#));
+    }
+
+    #[test]
+    fn test_liquid_asset_annotation() {
+        // L-BTC asset id on Liquid mainnet
+        let lbtc = "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d";
+        let liquid = "lq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn";
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::Liquid, liquid.to_string());
+
+        addresses.set_liquid_asset(liquid, lbtc).unwrap();
+        assert_eq!(addresses.annotation(liquid), Some(lbtc));
+        assert!(addresses.set_liquid_asset(liquid, "not-an-asset").is_err());
+        assert!(addresses.set_liquid_asset("bc1qnotliquid", lbtc).is_err());
+
+        let json = serde_json::to_string(&addresses).unwrap();
+        let decoded: BitcoinAddresses = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.annotation(liquid), Some(lbtc));
+
+        // Omitted entirely when there are none
+        let plain = serde_json::to_string(&BitcoinAddresses::new()).unwrap();
+        assert!(!plain.contains("annotations"));
+    }
+
+    #[test]
+    fn test_numeric_codes() {
+        for (code, address_type) in AddressType::ALL.iter().enumerate() {
+            let code = code as u8;
+            assert_eq!(AddressType::try_from(code).unwrap(), *address_type);
+            assert_eq!(u8::from(address_type.clone()), code);
+        }
+        assert!(AddressType::try_from(AddressType::ALL.len() as u8).is_err());
+
+        assert_eq!(network_from_code(0).unwrap(), Network::Bitcoin);
+        assert_eq!(network_from_code(3).unwrap(), Network::Regtest);
+        assert!(network_from_code(4).is_err());
+    }
+
+    #[test]
+    fn test_counts_by_type() {
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::P2PKH, "1a".to_string());
+        for i in 0..3 {
+            addresses.add_address(AddressType::P2WPKH, format!("bc1q{}", i));
+        }
+        addresses.addresses.insert(AddressType::P2TR, Vec::new());
+
+        let counts = addresses.counts_by_type();
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts[&AddressType::P2PKH], 1);
+        assert_eq!(counts[&AddressType::P2WPKH], 3);
+        assert_eq!(counts.values().sum::<usize>(), addresses.len());
+    }
+
+    #[test]
+    fn test_lightning_address_round_trip() {
+        let node_id = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::Lightning, node_id.to_string());
+        addresses.add_lightning_address(" Satoshi@GetAlby.com ").unwrap();
+        assert!(addresses.add_lightning_address("not an address").is_err());
+
+        let json = serde_json::to_string(&addresses).unwrap();
+        let decoded: BitcoinAddresses = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            decoded.get_addresses(&AddressType::Lightning).unwrap(),
+            &vec![node_id.to_string(), "satoshi@getalby.com".to_string()]
+        );
+        assert_eq!(decoded.lightning_addresses(), vec!["satoshi@getalby.com"]);
+    }
+
+    #[test]
+    fn test_into_iterator() {
+        let mut addresses = BitcoinAddresses::new();
+        addresses.add_address(AddressType::Nostr, "npub1".to_string());
+        addresses.add_address(AddressType::P2TR, "bc1p".to_string());
+        addresses.add_address(AddressType::P2PKH, "1a".to_string());
+        addresses.add_address(AddressType::P2PKH, "1b".to_string());
+
+        let borrowed: Vec<_> = addresses.iter().map(|(t, list)| (t.clone(), list.len())).collect();
+        assert_eq!(
+            borrowed,
+            vec![(AddressType::P2PKH, 2), (AddressType::P2TR, 1), (AddressType::Nostr, 1)]
+        );
+
+        let mut visited = 0;
+        for (_, list) in &addresses {
+            visited += list.len();
+        }
+        assert_eq!(visited, addresses.len());
+
+        let owned: Vec<(AddressType, Vec<String>)> = addresses.into_iter().collect();
+        assert_eq!(owned[0], (AddressType::P2PKH, vec!["1a".to_string(), "1b".to_string()]));
+        assert_eq!(owned.len(), 3);
+    }
+
+    #[test]
+    fn test_p2shwpkh_wire_name() {
+        assert_eq!(serde_json::to_string(&AddressType::P2SHWPKH).unwrap(), r#""P2SH""#);
+        for name in [r#""P2SH""#, r#""P2SHWPKH""#] {
+            assert_eq!(serde_json::from_str::<AddressType>(name).unwrap(), AddressType::P2SHWPKH);
+        }
+
+        // Stored collections written before the rename still load
+        let legacy = r#"{"addresses":{"P2SH":["3a"]},"metadata":null,"created_at":0,"version":1}"#;
+        let addresses: BitcoinAddresses = serde_json::from_str(legacy).unwrap();
+        assert_eq!(addresses.first(&AddressType::P2SHWPKH), Some("3a"));
+        assert!(addresses.unknown.is_empty());
+    }
 }
@@ -1,10 +1,22 @@
 //! Main UBA functionality - generate and retrieve functions
 
 use crate::address::AddressGenerator;
+use crate::cache::{CacheMode, UbaCache};
 use crate::error::{Result, UbaError};
-use crate::nostr_client::{generate_nostr_keys_from_seed, NostrClient};
-use crate::types::{BitcoinAddresses, ParsedUba, UbaConfig};
-
+use crate::nostr_client::{generate_nostr_keys_from_seed_with_config, NostrClient};
+use crate::parse::{validate_label, validate_nostr_id};
+use crate::transport::RelayTransport;
+use crate::types::{
+    AddressType, BitcoinAddresses, GenerateOutcome, SelfTestReport, Uba, UbaConfig,
+    UbaGenerationRequest,
+};
+
+pub use crate::parse::{format_uba, parse_uba};
+
+use nostr::{JsonUtil, Keys};
+use std::str::FromStr;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 use url::Url;
 
 /// Generate a UBA string from a seed and store address data on Nostr relays
@@ -43,6 +55,47 @@ pub async fn generate_with_config(
     relay_urls: &[String],
     config: UbaConfig,
 ) -> Result<String> {
+    generate_full_with_config(seed, label, relay_urls, config)
+        .await
+        .map(|outcome| outcome.uba)
+}
+
+/// Generate a UBA string using an existing Nostr client
+///
+/// This is the building block behind `generate_with_config`. It lets callers supply
+/// their own client, for example one built on a custom `RelayTransport`.
+pub async fn generate_with_client<T: RelayTransport>(
+    seed: &str,
+    label: Option<&str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+    nostr_client: &NostrClient<T>,
+) -> Result<String> {
+    generate_full_with_client(seed, label, relay_urls, config, nostr_client)
+        .await
+        .map(|outcome| outcome.uba)
+}
+
+/// Generate a UBA and return the full outcome instead of just the UBA string
+///
+/// The outcome carries the UBA string, the event ID, the generated addresses and
+/// the relays that accepted the event, so callers don't need to regenerate or
+/// retrieve the addresses they just published.
+pub async fn generate_full(
+    seed: &str,
+    label: Option<&str>,
+    relay_urls: &[String],
+) -> Result<GenerateOutcome> {
+    generate_full_with_config(seed, label, relay_urls, UbaConfig::default()).await
+}
+
+/// Generate a UBA with custom configuration and return the full outcome
+pub async fn generate_full_with_config(
+    seed: &str,
+    label: Option<&str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+) -> Result<GenerateOutcome> {
     // Use relay URLs from config if provided, otherwise use passed URLs
     let final_relay_urls = if relay_urls.is_empty() {
         config.get_relay_urls()
@@ -56,33 +109,421 @@ pub async fn generate_with_config(
         validate_label(label)?;
     }
 
+    // Generate deterministic Nostr keys from the seed
+    let nostr_keys = generate_nostr_keys_from_seed_with_config(seed, &config)?;
+    let nostr_client = NostrClient::from_config(nostr_keys, &config);
+
+    let final_relay_urls = select_relays(final_relay_urls, &config, &nostr_client).await?;
+    generate_full_with_client(seed, label, &final_relay_urls, config, &nostr_client).await
+}
+
+/// Generate a UBA using an existing Nostr client and return the full outcome
+pub async fn generate_full_with_client<T: RelayTransport>(
+    seed: &str,
+    label: Option<&str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+    nostr_client: &NostrClient<T>,
+) -> Result<GenerateOutcome> {
+    config.check_rate_limit()?;
+
+    // Validate inputs
+    validate_relay_urls(relay_urls)?;
+    if let Some(label) = label {
+        validate_label(label)?;
+    }
+
     // Generate Bitcoin addresses from the seed
     let address_generator = AddressGenerator::new(config.clone());
     let addresses = address_generator.generate_addresses(seed, label.map(String::from))?;
 
-    // Generate deterministic Nostr keys from the seed
-    let nostr_keys = generate_nostr_keys_from_seed(seed)?;
-    let nostr_client = NostrClient::with_keys(nostr_keys, config.relay_timeout);
-
-    // Connect to Nostr relays
-    nostr_client.connect_to_relays(&final_relay_urls).await?;
-
     // Publish the addresses to Nostr with encryption if enabled
-    let event_id = nostr_client
-        .publish_addresses_with_encryption(&addresses, config.encryption_key.as_ref())
+    let output = nostr_client
+        .with_connection(relay_urls, |client| {
+            client.publish_addresses_detailed(&addresses, config.encryption_key.as_deref())
+        })
         .await?;
 
-    // Disconnect from relays
-    nostr_client.disconnect().await;
-
     // Format the UBA string
-    let uba = if let Some(label) = label {
-        format!("UBA:{}&label={}", event_id, label)
+    let event_id = output.event_id.to_hex();
+    let uba = build_uba(&event_id, label, &config)?;
+
+    Ok(GenerateOutcome {
+        uba: uba.to_string(),
+        event_id,
+        addresses,
+        confirmed_relays: output.confirmed_relays,
+    })
+}
+
+/// Generate a UBA that can be aborted through a cancellation token
+///
+/// Cancelling the token stops connecting, publishing and any retry loop at the next
+/// await point, disconnects from the relays and returns `UbaError::Cancelled`.
+/// Address derivation itself is synchronous and is only checked for cancellation
+/// before it starts.
+///
+/// # Example
+/// ```rust,no_run
+/// use uba::{generate_cancellable, CancellationToken, UbaConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+///     let token = CancellationToken::new();
+///
+///     // e.g. hand a clone to the UI's cancel button
+///     let cancel = token.clone();
+///     tokio::spawn(async move {
+///         tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+///         cancel.cancel();
+///     });
+///
+///     let uba = generate_cancellable(seed, None, &[], UbaConfig::default(), token).await?;
+///     println!("Generated UBA: {}", uba);
+///     Ok(())
+/// }
+/// ```
+pub async fn generate_cancellable(
+    seed: &str,
+    label: Option<&str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+    cancellation_token: CancellationToken,
+) -> Result<String> {
+    let final_relay_urls = if relay_urls.is_empty() {
+        config.get_relay_urls()
     } else {
-        format!("UBA:{}", event_id)
+        relay_urls.to_vec()
     };
 
-    Ok(uba)
+    let nostr_keys = generate_nostr_keys_from_seed_with_config(seed, &config)?;
+    let nostr_client = NostrClient::from_config(nostr_keys, &config);
+
+    let final_relay_urls = select_relays(final_relay_urls, &config, &nostr_client).await?;
+    generate_cancellable_with_client(
+        seed,
+        label,
+        &final_relay_urls,
+        config,
+        &nostr_client,
+        cancellation_token,
+    )
+    .await
+}
+
+/// Generate a cancellable UBA using an existing Nostr client
+pub async fn generate_cancellable_with_client<T: RelayTransport>(
+    seed: &str,
+    label: Option<&str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+    nostr_client: &NostrClient<T>,
+    cancellation_token: CancellationToken,
+) -> Result<String> {
+    if cancellation_token.is_cancelled() {
+        return Err(UbaError::Cancelled);
+    }
+
+    tokio::select! {
+        // Prefer cancellation when both are ready, so a cancelled call never publishes
+        biased;
+        _ = cancellation_token.cancelled() => {
+            nostr_client.disconnect().await;
+            Err(UbaError::Cancelled)
+        }
+        outcome = generate_full_with_client(seed, label, relay_urls, config, nostr_client) => {
+            outcome.map(|outcome| outcome.uba)
+        }
+    }
+}
+
+/// Generate a UBA whose addresses are split across linked events
+///
+/// Use this when the generated collection may exceed what relays accept in a single
+/// event. The addresses are split into parts that each fit `config.max_event_size`,
+/// every part is published as its own event, and the returned UBA points to a
+/// manifest event listing them. `retrieve` reassembles the parts transparently.
+///
+/// # Example
+/// ```rust,no_run
+/// use uba::{generate_sharded, UbaConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut config = UbaConfig::default();
+///     config.max_addresses_per_type = 1000;
+///     config.max_event_size = 32 * 1024;
+///
+///     let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+///     let relays = vec!["wss://relay.example.com".to_string()];
+///     let uba = generate_sharded(seed, Some("large-wallet"), &relays, config).await?;
+///     println!("Generated UBA: {}", uba);
+///     Ok(())
+/// }
+/// ```
+pub async fn generate_sharded(
+    seed: &str,
+    label: Option<&str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+) -> Result<String> {
+    let final_relay_urls = if relay_urls.is_empty() {
+        config.get_relay_urls()
+    } else {
+        relay_urls.to_vec()
+    };
+
+    let nostr_keys = generate_nostr_keys_from_seed_with_config(seed, &config)?;
+    let nostr_client = NostrClient::from_config(nostr_keys, &config);
+
+    let final_relay_urls = select_relays(final_relay_urls, &config, &nostr_client).await?;
+    generate_sharded_with_client(seed, label, &final_relay_urls, config, &nostr_client).await
+}
+
+/// Generate a sharded UBA using an existing Nostr client
+pub async fn generate_sharded_with_client<T: RelayTransport>(
+    seed: &str,
+    label: Option<&str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+    nostr_client: &NostrClient<T>,
+) -> Result<String> {
+    config.check_rate_limit()?;
+
+    // Validate inputs
+    validate_relay_urls(relay_urls)?;
+    if let Some(label) = label {
+        validate_label(label)?;
+    }
+
+    // Generate Bitcoin addresses from the seed
+    let address_generator = AddressGenerator::new(config.clone());
+    let addresses = address_generator.generate_addresses(seed, label.map(String::from))?;
+
+    // Connect, publish all parts and the manifest, then disconnect
+    let output = nostr_client
+        .with_connection(relay_urls, |client| {
+            client.publish_addresses_sharded(
+                &addresses,
+                config.encryption_key.as_deref(),
+                config.max_event_size,
+            )
+        })
+        .await?;
+
+    // The UBA references the manifest event
+    let uba = build_uba(&output.event_id.to_hex(), label, &config)?;
+
+    Ok(uba.to_string())
+}
+
+/// Export the Nostr identity a seed's UBA collection advertises, as `(nsec, npub)`
+///
+/// This is the key pair behind the first `AddressType::Nostr` entry, derived at
+/// `m/44'/1237'/0'/0/0`, so it can be imported into a regular Nostr client. It is
+/// not the key that signs UBA events.
+///
+/// # Security
+/// **The returned nsec is a secret key.** Anyone who obtains it can post and read
+/// encrypted messages as this identity, and it is as sensitive as the seed's other
+/// derived keys. Never log, publish or transmit it; only hand it to software you
+/// trust with the seed.
+///
+/// # Example
+/// ```rust
+/// use uba::nostr_keys_from_seed;
+///
+/// let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+/// let (nsec, npub) = nostr_keys_from_seed(seed).unwrap();
+/// assert!(nsec.starts_with("nsec1"));
+/// assert!(npub.starts_with("npub1"));
+/// ```
+pub fn nostr_keys_from_seed(seed: &str) -> Result<(String, String)> {
+    nostr_keys_from_seed_with_config(seed, &UbaConfig::default())
+}
+
+/// Export the Nostr identity a seed's UBA collection advertises, with custom configuration
+///
+/// The BIP39 passphrase and mnemonic language in `config` select the wallet the
+/// identity is derived from, as they do for the collection itself. See
+/// `nostr_keys_from_seed` for the security notes.
+pub fn nostr_keys_from_seed_with_config(
+    seed: &str,
+    config: &UbaConfig,
+) -> Result<(String, String)> {
+    AddressGenerator::new(config.clone()).nostr_keys(seed)
+}
+
+/// Generate UBAs for many seeds, connecting to each relay set only once
+///
+/// Each request carries its own seed, label, relays and config. Requests that
+/// share a relay set are published over a single connection, every event signed
+/// with the deterministic keys of its own seed. Failures of individual entries,
+/// including rate limiting and unreachable relays, are reported in the returned vector, in request
+/// order; they never abort the rest of the batch.
+///
+/// # Example
+/// ```rust,no_run
+/// use uba::{generate_batch, UbaConfig, UbaGenerationRequest};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let relays = vec!["wss://relay.example.com".to_string()];
+///     let requests = vec![
+///         UbaGenerationRequest {
+///             seed: "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about".to_string(),
+///             label: Some("alice".to_string()),
+///             relay_urls: relays.clone(),
+///             config: UbaConfig::default(),
+///         },
+///         UbaGenerationRequest {
+///             seed: "legal winner thank year wave sausage worth useful legal winner thank yellow".to_string(),
+///             label: None,
+///             relay_urls: relays,
+///             config: UbaConfig::default(),
+///         },
+///     ];
+///
+///     for result in generate_batch(requests).await {
+///         match result {
+///             Ok(uba) => println!("Generated UBA: {}", uba),
+///             Err(e) => println!("Failed: {}", e),
+///         }
+///     }
+/// }
+/// ```
+pub async fn generate_batch(requests: Vec<UbaGenerationRequest>) -> Vec<Result<String>> {
+    // The shared client only holds the connections; each entry signs with its own
+    // seed's keys and applies its own config
+    let config = requests
+        .first()
+        .map(|request| request.config.clone())
+        .unwrap_or_default();
+    let nostr_client = NostrClient::from_config(Keys::generate(), &config);
+
+    generate_batch_with_client(requests, &nostr_client).await
+}
+
+/// Generate UBAs for many seeds using an existing Nostr client
+///
+/// The client connects to each relay set; entries are published through
+/// `NostrClient::with_signer`, so the client's own keys are never used or changed.
+pub async fn generate_batch_with_client<T: RelayTransport + Clone>(
+    requests: Vec<UbaGenerationRequest>,
+    nostr_client: &NostrClient<T>,
+) -> Vec<Result<String>> {
+    let mut results: Vec<Option<Result<String>>> = requests.iter().map(|_| None).collect();
+
+    // Group the entries by relay set, keeping the order in which sets first appear
+    let mut groups: Vec<(Vec<String>, Vec<usize>)> = Vec::new();
+    for (index, request) in requests.iter().enumerate() {
+        let relay_urls = if request.relay_urls.is_empty() {
+            request.config.get_relay_urls()
+        } else {
+            request.relay_urls.clone()
+        };
+        if let Err(e) = validate_relay_urls(&relay_urls) {
+            results[index] = Some(Err(e));
+            continue;
+        }
+        match groups.iter_mut().find(|(urls, _)| *urls == relay_urls) {
+            Some((_, indices)) => indices.push(index),
+            None => groups.push((relay_urls, vec![index])),
+        }
+    }
+
+    let requests = &requests;
+    for (relay_urls, indices) in groups {
+        let group = &indices;
+        let published = nostr_client
+            .with_connection(&relay_urls, |client| async move {
+                let mut published = Vec::with_capacity(group.len());
+                for &index in group {
+                    published.push((index, generate_batch_entry(&requests[index], client).await));
+                }
+                Ok::<_, UbaError>(published)
+            })
+            .await;
+
+        match published {
+            Ok(published) => {
+                for (index, result) in published {
+                    results[index] = Some(result);
+                }
+            }
+            // No relay of the set was reachable, which fails every entry using it
+            Err(e) => {
+                for index in indices {
+                    results[index] = Some(Err(UbaError::NostrRelay(e.to_string())));
+                }
+            }
+        }
+    }
+
+    // Every entry was either rejected up front or published in its group
+    results.into_iter().flatten().collect()
+}
+
+/// Generate and publish one batch entry over an already connected client
+async fn generate_batch_entry<T: RelayTransport + Clone>(
+    request: &UbaGenerationRequest,
+    nostr_client: &NostrClient<T>,
+) -> Result<String> {
+    let config = &request.config;
+    config.check_rate_limit()?;
+    if let Some(label) = &request.label {
+        validate_label(label)?;
+    }
+
+    let addresses = AddressGenerator::new(config.clone())
+        .generate_addresses(&request.seed, request.label.clone())?;
+
+    let nostr_keys = generate_nostr_keys_from_seed_with_config(&request.seed, config)?;
+    let mut signer = nostr_client.with_signer(nostr_keys);
+    signer.apply_config(config);
+    let output = signer
+        .publish_addresses_detailed(&addresses, config.encryption_key.as_deref())
+        .await?;
+
+    let uba = build_uba(&output.event_id.to_hex(), request.label.as_deref(), config)?;
+    Ok(uba.to_string())
+}
+
+/// Preview what `generate_with_config` would publish, without touching any relay
+///
+/// Returns the generated addresses together with the signed event serialized as
+/// JSON. The event carries the computed event ID and the content exactly as it
+/// would be stored, encrypted if an encryption key is configured.
+///
+/// # Example
+/// ```rust
+/// use uba::{generate_dry_run, UbaConfig};
+///
+/// let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+/// let (addresses, event_json) = generate_dry_run(seed, Some("preview"), UbaConfig::default())?;
+/// println!("{} addresses, event: {}", addresses.len(), event_json);
+/// # Ok::<(), uba::UbaError>(())
+/// ```
+pub fn generate_dry_run(
+    seed: &str,
+    label: Option<&str>,
+    config: UbaConfig,
+) -> Result<(BitcoinAddresses, String)> {
+    if let Some(label) = label {
+        validate_label(label)?;
+    }
+
+    // Generate Bitcoin addresses from the seed
+    let address_generator = AddressGenerator::new(config.clone());
+    let addresses = address_generator.generate_addresses(seed, label.map(String::from))?;
+
+    // Build the event with the same deterministic keys generate would use
+    let nostr_keys = generate_nostr_keys_from_seed_with_config(seed, &config)?;
+    let nostr_client = NostrClient::from_config(nostr_keys, &config);
+    let event = nostr_client.build_addresses_event(&addresses, config.encryption_key.as_deref())?;
+
+    Ok((addresses, event.as_json()))
 }
 
 /// Retrieve Bitcoin addresses from a UBA string
@@ -108,17 +549,19 @@ pub async fn generate_with_config(
 ///     Ok(())
 /// }
 /// ```
-pub async fn retrieve(uba: &str, relay_urls: &[String]) -> Result<Vec<String>> {
+pub async fn retrieve(uba: impl AsRef<str>, relay_urls: &[String]) -> Result<Vec<String>> {
     let config = UbaConfig::default();
     retrieve_with_config(uba, relay_urls, config).await
 }
 
 /// Retrieve Bitcoin addresses with custom configuration
 pub async fn retrieve_with_config(
-    uba: &str,
+    uba: impl AsRef<str>,
     relay_urls: &[String],
     config: UbaConfig,
 ) -> Result<Vec<String>> {
+    config.check_rate_limit()?;
+
     // Use relay URLs from config if provided, otherwise use passed URLs
     let final_relay_urls = if relay_urls.is_empty() {
         config.get_relay_urls()
@@ -130,38 +573,42 @@ pub async fn retrieve_with_config(
     validate_relay_urls(&final_relay_urls)?;
 
     // Parse the UBA string
-    let parsed_uba = parse_uba(uba)?;
+    let parsed_uba = parse_uba(uba.as_ref())?;
 
     // Create Nostr client (we don't need specific keys for reading)
-    let nostr_client = NostrClient::new(config.relay_timeout)?;
-
-    // Connect to Nostr relays
-    nostr_client.connect_to_relays(&final_relay_urls).await?;
+    let nostr_client = NostrClient::from_config(Keys::generate(), &config);
 
     // Retrieve the addresses from Nostr with decryption if needed
+    let final_relay_urls = select_relays(final_relay_urls, &config, &nostr_client).await?;
     let addresses = nostr_client
-        .retrieve_addresses_with_decryption(&parsed_uba.nostr_id, config.encryption_key.as_ref())
+        .with_connection(&final_relay_urls, |client| {
+            client.retrieve_addresses_with_decryption(
+                &parsed_uba.nostr_id,
+                config.encryption_key.as_deref(),
+            )
+        })
         .await?;
 
-    // Disconnect from relays
-    nostr_client.disconnect().await;
+    if let Some(signer) = &config.expected_signer {
+        addresses.verify_signature(Some(signer))?;
+    }
 
     // Return all addresses as a flat vector
-    Ok(addresses.get_all_addresses())
+    Ok(addresses.get_all_addresses_in_order(config.get_address_type_order()))
 }
 
 /// Retrieve the full BitcoinAddresses structure from a UBA string
 ///
 /// This function returns the complete address collection with metadata,
 /// allowing access to addresses grouped by type.
-pub async fn retrieve_full(uba: &str, relay_urls: &[String]) -> Result<BitcoinAddresses> {
+pub async fn retrieve_full(uba: impl AsRef<str>, relay_urls: &[String]) -> Result<BitcoinAddresses> {
     let config = UbaConfig::default();
     retrieve_full_with_config(uba, relay_urls, config).await
 }
 
 /// Retrieve the full BitcoinAddresses structure with custom configuration
 pub async fn retrieve_full_with_config(
-    uba: &str,
+    uba: impl AsRef<str>,
     relay_urls: &[String],
     config: UbaConfig,
 ) -> Result<BitcoinAddresses> {
@@ -175,116 +622,846 @@ pub async fn retrieve_full_with_config(
     // Validate inputs
     validate_relay_urls(&final_relay_urls)?;
 
-    // Parse the UBA string
-    let parsed_uba = parse_uba(uba)?;
-
     // Create Nostr client
-    let nostr_client = NostrClient::new(config.relay_timeout)?;
+    let nostr_client = NostrClient::from_config(Keys::generate(), &config);
+
+    let final_relay_urls = select_relays(final_relay_urls, &config, &nostr_client).await?;
+    retrieve_full_with_client(uba, &final_relay_urls, config, &nostr_client).await
+}
 
-    // Connect to Nostr relays
-    nostr_client.connect_to_relays(&final_relay_urls).await?;
+/// Retrieve the full BitcoinAddresses structure using an existing Nostr client
+///
+/// This is the building block behind `retrieve_full_with_config`. It lets callers
+/// supply their own client, for example one built on a custom `RelayTransport`.
+pub async fn retrieve_full_with_client<T: RelayTransport>(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+    nostr_client: &NostrClient<T>,
+) -> Result<BitcoinAddresses> {
+    config.check_rate_limit()?;
+
+    // Validate inputs
+    validate_relay_urls(relay_urls)?;
+
+    // Parse the UBA string
+    let parsed_uba = parse_uba(uba.as_ref())?;
 
     // Retrieve the addresses from Nostr with decryption if needed
     let addresses = nostr_client
-        .retrieve_addresses_with_decryption(&parsed_uba.nostr_id, config.encryption_key.as_ref())
+        .with_connection(relay_urls, |client| {
+            client.retrieve_addresses_with_decryption(
+                &parsed_uba.nostr_id,
+                config.encryption_key.as_deref(),
+            )
+        })
         .await?;
 
-    // Disconnect from relays
-    nostr_client.disconnect().await;
+    if let Some(signer) = &config.expected_signer {
+        addresses.verify_signature(Some(signer))?;
+    }
 
     Ok(addresses)
 }
 
-/// Parse a UBA string into its components
+/// Retrieve many UBAs, such as an address book, over a single relay connection
 ///
-/// # Arguments
-/// * `uba` - UBA string to parse
+/// Relays are connected once and all events are requested with one combined
+/// query. The outer `Result` only fails for batch-wide problems (invalid relay
+/// URLs, no relay reachable, a failed query); each UBA that cannot be parsed, is
+/// missing (`UbaError::NoteNotFound`) or fails to decrypt is reported in its own
+/// entry, in input order.
 ///
-/// # Returns
-/// A `ParsedUba` struct containing the Nostr ID and optional label
+/// # Example
+/// ```rust,no_run
+/// use uba::{retrieve_many, UbaConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let ubas = [
+///         "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef",
+///         "UBA:abcdef1234567890abcdef1234567890abcdef1234567890abcdef1234567890",
+///     ];
+///     let relays = vec!["wss://relay.damus.io".to_string()];
+///
+///     let results = retrieve_many(&ubas, &relays, UbaConfig::default()).await?;
+///     for (uba, result) in ubas.iter().zip(results) {
+///         match result {
+///             Ok(addresses) => println!("{}: {} addresses", uba, addresses.len()),
+///             Err(e) => println!("{}: {}", uba, e),
+///         }
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn retrieve_many(
+    ubas: &[&str],
+    relay_urls: &[String],
+    config: UbaConfig,
+) -> Result<Vec<Result<BitcoinAddresses>>> {
+    // Use relay URLs from config if provided, otherwise use passed URLs
+    let final_relay_urls = if relay_urls.is_empty() {
+        config.get_relay_urls()
+    } else {
+        relay_urls.to_vec()
+    };
+
+    validate_relay_urls(&final_relay_urls)?;
+
+    let nostr_client = NostrClient::from_config(Keys::generate(), &config);
+
+    let final_relay_urls = select_relays(final_relay_urls, &config, &nostr_client).await?;
+    retrieve_many_with_client(ubas, &final_relay_urls, config, &nostr_client).await
+}
+
+/// Retrieve many UBAs using an existing Nostr client
+///
+/// See `retrieve_many`.
+pub async fn retrieve_many_with_client<T: RelayTransport>(
+    ubas: &[&str],
+    relay_urls: &[String],
+    config: UbaConfig,
+    nostr_client: &NostrClient<T>,
+) -> Result<Vec<Result<BitcoinAddresses>>> {
+    config.check_rate_limit()?;
+    validate_relay_urls(relay_urls)?;
+
+    let parsed: Vec<Result<String>> = ubas
+        .iter()
+        .map(|uba| parse_uba(uba).map(|parsed| parsed.nostr_id))
+        .collect();
+    let event_ids: Vec<String> = parsed
+        .iter()
+        .filter_map(|id| id.as_ref().ok())
+        .cloned()
+        .collect();
+
+    let mut retrieved = nostr_client
+        .with_connection(relay_urls, |client| {
+            client.retrieve_many_with_decryption(&event_ids, config.encryption_key.as_deref())
+        })
+        .await?
+        .into_iter();
+
+    Ok(parsed
+        .into_iter()
+        .map(|id| {
+            // There is one retrieval result per UBA that parsed, in the same order;
+            // a missing one is reported like an event no relay has
+            let addresses = id.and_then(|nostr_id| {
+                retrieved
+                    .next()
+                    .unwrap_or_else(|| Err(UbaError::NoteNotFound(nostr_id)))
+            })?;
+            if let Some(signer) = &config.expected_signer {
+                addresses.verify_signature(Some(signer))?;
+            }
+            Ok(addresses)
+        })
+        .collect())
+}
+
+/// Retrieve the full BitcoinAddresses structure, rejecting it if any Bitcoin L1
+/// address belongs to a network other than `expected_network`
+///
+/// Protects wallets from treating testnet addresses published under a UBA as
+/// mainnet payment destinations (or the reverse). Liquid, Lightning and Nostr
+/// entries are not checked.
 ///
 /// # Example
-/// ```rust
-/// use uba::parse_uba;
+/// ```rust,no_run
+/// use uba::{retrieve_full_checked, Network, UbaConfig};
 ///
-/// let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef&label=my-wallet";
-/// let parsed = parse_uba(uba)?;
-/// println!("Nostr ID: {}", parsed.nostr_id);
-/// println!("Label: {:?}", parsed.label);
-/// # Ok::<(), uba::UbaError>(())
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+///     let relays = vec!["wss://relay.damus.io".to_string()];
+///
+///     let addresses =
+///         retrieve_full_checked(uba, &relays, Network::Bitcoin, UbaConfig::default()).await?;
+///     println!("{} mainnet addresses", addresses.len());
+///     Ok(())
+/// }
 /// ```
-pub fn parse_uba(uba: &str) -> Result<ParsedUba> {
-    // Check if it starts with "UBA:"
-    if !uba.starts_with("UBA:") {
-        return Err(UbaError::InvalidUbaFormat(
-            "UBA string must start with 'UBA:'".to_string(),
+pub async fn retrieve_full_checked(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    expected_network: bitcoin::Network,
+    config: UbaConfig,
+) -> Result<BitcoinAddresses> {
+    let addresses = retrieve_full_with_config(uba, relay_urls, config).await?;
+    check_addresses_network(&addresses, expected_network)?;
+    Ok(addresses)
+}
+
+/// Retrieve and network-check addresses using an existing Nostr client
+///
+/// See `retrieve_full_checked`.
+pub async fn retrieve_full_checked_with_client<T: RelayTransport>(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    expected_network: bitcoin::Network,
+    config: UbaConfig,
+    nostr_client: &NostrClient<T>,
+) -> Result<BitcoinAddresses> {
+    let addresses = retrieve_full_with_client(uba, relay_urls, config, nostr_client).await?;
+    check_addresses_network(&addresses, expected_network)?;
+    Ok(addresses)
+}
+
+/// Narrow the relays to the fastest-connecting ones if `auto_select_relays` is set
+///
+/// Fails with `UbaError::NostrRelay` if none of the candidates can be reached
+/// within the connect timeout.
+async fn select_relays<T: RelayTransport>(
+    relay_urls: Vec<String>,
+    config: &UbaConfig,
+    nostr_client: &NostrClient<T>,
+) -> Result<Vec<String>> {
+    let Some(n) = config.auto_select_relays else {
+        return Ok(relay_urls);
+    };
+    if n == 0 {
+        return Err(UbaError::Config(
+            "auto_select_relays must select at least one relay".to_string(),
         ));
     }
 
-    // Remove the "UBA:" prefix
-    let content = &uba[4..];
-
-    // Check for label parameter
-    if let Some(query_start) = content.find('&') {
-        let nostr_id = content[..query_start].to_string();
-        let query_string = &content[query_start + 1..];
+    let timeout = config.connect_timeout();
+    let selected = nostr_client.select_fastest(&relay_urls, n, timeout).await;
+    if selected.is_empty() {
+        return Err(UbaError::NostrRelay(format!(
+            "None of the {} candidate relays connected within {} ms",
+            relay_urls.len(),
+            timeout.as_millis()
+        )));
+    }
+    Ok(selected)
+}
 
-        // Parse query parameters
-        let label = parse_query_params(query_string)?;
+/// Check that every Bitcoin L1 address in the collection is valid for `network`
+fn check_addresses_network(addresses: &BitcoinAddresses, network: bitcoin::Network) -> Result<()> {
+    let mismatched: Vec<String> = addresses
+        .iter_typed()
+        .filter(|(address_type, _, _)| {
+            matches!(
+                address_type,
+                AddressType::P2PKH | AddressType::P2SHWPKH | AddressType::P2WPKH | AddressType::P2TR
+            )
+        })
+        .filter(|(_, _, address)| {
+            bitcoin::Address::from_str(address)
+                .map(|parsed| !parsed.is_valid_for_network(network))
+                .unwrap_or(true)
+        })
+        .map(|(address_type, index, address)| format!("{:?}[{}] {}", address_type, index, address))
+        .collect();
 
-        // Validate the Nostr ID format (should be 64 hex characters)
-        validate_nostr_id(&nostr_id)?;
+    if mismatched.is_empty() {
+        Ok(())
+    } else {
+        Err(UbaError::Config(format!(
+            "Addresses do not belong to network {}: {}",
+            network,
+            mismatched.join(", ")
+        )))
+    }
+}
 
-        Ok(ParsedUba { nostr_id, label })
+/// Check whether a seed reproduces the addresses published under a UBA
+///
+/// Retrieves the UBA and regenerates addresses locally from `seed` with `config`.
+/// Returns `true` if the UBA event was published with the seed's Nostr keys and
+/// every retrieved address is among the locally generated addresses of the same
+/// type; an empty collection never matches. The local set may be larger, so the
+/// configured address counts only need to be at least as large as those used when
+/// the UBA was generated; the network, derivation paths and BIP39 passphrase must
+/// match.
+///
+/// # Example
+/// ```rust,no_run
+/// use uba::{verify_seed_matches, UbaConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+///     let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+///     let relays = vec!["wss://relay.damus.io".to_string()];
+///
+///     if verify_seed_matches(seed, uba, &relays, UbaConfig::default()).await? {
+///         println!("The seed controls this UBA");
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn verify_seed_matches(
+    seed: &str,
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+) -> Result<bool> {
+    let final_relay_urls = if relay_urls.is_empty() {
+        config.get_relay_urls()
     } else {
-        // No query parameters, just the Nostr ID
-        validate_nostr_id(content)?;
+        relay_urls.to_vec()
+    };
+    validate_relay_urls(&final_relay_urls)?;
+
+    let nostr_client = NostrClient::from_config(Keys::generate(), &config);
 
-        Ok(ParsedUba {
-            nostr_id: content.to_string(),
-            label: None,
+    let final_relay_urls = select_relays(final_relay_urls, &config, &nostr_client).await?;
+    verify_seed_matches_with_client(seed, uba, &final_relay_urls, config, &nostr_client).await
+}
+
+/// Check whether a seed reproduces a UBA's addresses using an existing Nostr client
+///
+/// See `verify_seed_matches`.
+pub async fn verify_seed_matches_with_client<T: RelayTransport>(
+    seed: &str,
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+    nostr_client: &NostrClient<T>,
+) -> Result<bool> {
+    // Derive locally first so a bad seed fails without touching the relays
+    let generator = AddressGenerator::new(config.clone());
+    let local = generator.generate_addresses(seed, None)?;
+    let author = generate_nostr_keys_from_seed_with_config(seed, &config)?.public_key();
+
+    config.check_rate_limit()?;
+    validate_relay_urls(relay_urls)?;
+    let parsed_uba = parse_uba(uba.as_ref())?;
+    let nostr_id = &parsed_uba.nostr_id;
+    let encryption_key = config.encryption_key.as_deref();
+
+    // Anyone can republish the seed's public addresses; only its keys can author.
+    // The author check and the decode share one relay session.
+    let retrieved = nostr_client
+        .with_connection(relay_urls, |client| async move {
+            let event = client.fetch_event(nostr_id).await?;
+            if event.pubkey != author {
+                return Ok(None);
+            }
+            client.decode_event(&event, encryption_key).await.map(Some)
         })
+        .await?;
+    let Some(retrieved) = retrieved else {
+        return Ok(false);
+    };
+
+    if let Some(signer) = &config.expected_signer {
+        retrieved.verify_signature(Some(signer))?;
+    }
+    if retrieved.is_empty() {
+        return Ok(false);
+    }
+
+    let matches = retrieved.iter_typed().all(|(address_type, _, address)| {
+        local
+            .get_addresses(&address_type)
+            .is_some_and(|generated| generated.iter().any(|a| a == address))
+    });
+    Ok(matches)
+}
+
+/// Retrieve addresses from a primary relay set, falling back to a backup set
+///
+/// The fallback set is only queried when the primary set could not be reached,
+/// timed out or does not have the event; other errors (such as a wrong encryption
+/// key) are returned immediately. An empty `fallback_relays` uses the configured or
+/// default public relays.
+///
+/// When both sets fail, `UbaError::NoteNotFound` is only returned if neither set
+/// had the event; if either failed with a network error, that error is returned
+/// instead, since the event may still exist.
+///
+/// # Example
+/// ```rust,no_run
+/// use uba::{retrieve_with_fallback, UbaConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+///     let private_relays = vec!["wss://relay.mycompany.com".to_string()];
+///
+///     // Fall back to the default public relays
+///     let addresses = retrieve_with_fallback(uba, &private_relays, &[], UbaConfig::default()).await?;
+///     println!("Found {} addresses", addresses.len());
+///     Ok(())
+/// }
+/// ```
+pub async fn retrieve_with_fallback(
+    uba: impl AsRef<str>,
+    primary_relays: &[String],
+    fallback_relays: &[String],
+    config: UbaConfig,
+) -> Result<Vec<String>> {
+    let order = config.get_address_type_order().to_vec();
+    retrieve_full_with_fallback(uba, primary_relays, fallback_relays, config)
+        .await
+        .map(|addresses| addresses.get_all_addresses_in_order(&order))
+}
+
+/// Retrieve the full address collection with fallback relays
+///
+/// See `retrieve_with_fallback` for when the fallback set is used.
+pub async fn retrieve_full_with_fallback(
+    uba: impl AsRef<str>,
+    primary_relays: &[String],
+    fallback_relays: &[String],
+    config: UbaConfig,
+) -> Result<BitcoinAddresses> {
+    let fallback_relays = if fallback_relays.is_empty() {
+        config.get_relay_urls()
+    } else {
+        fallback_relays.to_vec()
+    };
+
+    let primary_client = NostrClient::from_config(Keys::generate(), &config);
+    let fallback_client = NostrClient::from_config(Keys::generate(), &config);
+
+    retrieve_full_with_fallback_clients(
+        uba,
+        (primary_relays, &primary_client),
+        (&fallback_relays, &fallback_client),
+        config,
+    )
+    .await
+}
+
+/// Retrieve the full address collection with fallback relays, using existing clients
+/// for the primary and fallback relay sets
+pub async fn retrieve_full_with_fallback_clients<P: RelayTransport, F: RelayTransport>(
+    uba: impl AsRef<str>,
+    primary: (&[String], &NostrClient<P>),
+    fallback: (&[String], &NostrClient<F>),
+    config: UbaConfig,
+) -> Result<BitcoinAddresses> {
+    let uba = uba.as_ref();
+    let (primary_relays, primary_client) = primary;
+    let (fallback_relays, fallback_client) = fallback;
+
+    let primary_error =
+        match retrieve_full_with_client(uba, primary_relays, config.clone(), primary_client).await
+        {
+            Ok(addresses) => return Ok(addresses),
+            Err(error) if is_fallback_error(&error) => error,
+            Err(error) => return Err(error),
+        };
+
+    // Both attempts disconnect from their relays whatever the outcome
+    match retrieve_full_with_client(uba, fallback_relays, config, fallback_client).await {
+        Ok(addresses) => Ok(addresses),
+        // Only "not found anywhere" if the primary set also answered that it had no event
+        Err(UbaError::NoteNotFound(id)) => match primary_error {
+            UbaError::NoteNotFound(_) => Err(UbaError::NoteNotFound(id)),
+            network_error => Err(network_error),
+        },
+        Err(error) => Err(error),
     }
 }
 
-/// Parse query parameters from UBA string
-fn parse_query_params(query_string: &str) -> Result<Option<String>> {
-    let pairs: Vec<&str> = query_string.split('&').collect();
+/// Check whether a retrieval error means another relay set might still succeed
+fn is_fallback_error(error: &UbaError) -> bool {
+    matches!(
+        error,
+        UbaError::NoteNotFound(_)
+            | UbaError::Timeout
+            | UbaError::NostrRelay(_)
+            | UbaError::Network(_)
+            | UbaError::RetryExhausted(_)
+    )
+}
 
-    for pair in pairs {
-        if let Some(eq_pos) = pair.find('=') {
-            let key = &pair[..eq_pos];
-            let value = &pair[eq_pos + 1..];
+/// Check that relays can resolve a UBA, without downloading its addresses
+///
+/// Connects to the relays and asks for the UBA's event ID with `limit(1)`. Returns
+/// `Ok(true)` if any relay has it and `Ok(false)` if none does; the payload is
+/// neither decrypted nor parsed, so this works for encrypted UBAs without the key.
+/// `timeout` bounds connecting and the query. An empty `relay_urls` uses the
+/// default public relays.
+///
+/// # Example
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use uba::verify_resolvable;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+///     if verify_resolvable(uba, &[], Duration::from_secs(5)).await? {
+///         println!("Relays have this UBA");
+///     }
+///     Ok(())
+/// }
+/// ```
+pub async fn verify_resolvable(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    timeout: Duration,
+) -> Result<bool> {
+    verify_resolvable_with_config(uba, relay_urls, timeout, UbaConfig::default()).await
+}
 
-            if key == "label" {
-                // URL decode the value if needed
-                let decoded = urlencoding::decode(value).map_err(|_| {
-                    UbaError::InvalidUbaFormat("Invalid URL encoding in label".to_string())
-                })?;
-                return Ok(Some(decoded.to_string()));
+/// Check that relays can resolve a UBA published with custom configuration
+///
+/// Queries for `config.event_kind`, so UBAs published under a custom kind are
+/// found. `timeout` still bounds connecting and the query; an empty `relay_urls` uses the
+/// relays of `config`.
+pub async fn verify_resolvable_with_config(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    timeout: Duration,
+    config: UbaConfig,
+) -> Result<bool> {
+    let final_relay_urls = if relay_urls.is_empty() {
+        config.get_relay_urls()
+    } else {
+        relay_urls.to_vec()
+    };
+
+    let mut nostr_client = NostrClient::from_config(Keys::generate(), &config);
+    nostr_client.set_timeout(timeout);
+    nostr_client.set_connect_timeout(timeout);
+    nostr_client.set_query_timeout(Some(timeout));
+
+    verify_resolvable_with_client(uba, &final_relay_urls, &nostr_client).await
+}
+
+/// Check that relays can resolve a UBA using an existing Nostr client
+pub async fn verify_resolvable_with_client<T: RelayTransport>(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    nostr_client: &NostrClient<T>,
+) -> Result<bool> {
+    // Validate inputs
+    validate_relay_urls(relay_urls)?;
+    let nostr_event_id = resolve_nostr_id(uba.as_ref())?;
+
+    nostr_client
+        .with_connection(relay_urls, |client| client.event_exists(&nostr_event_id))
+        .await
+}
+
+/// Run an end-to-end round trip against relays and report how it went
+///
+/// Generates addresses from a fresh random seed, publishes them under a throwaway
+/// Nostr key, retrieves the event back, checks the addresses match and then asks
+/// the relays to delete it (NIP-09). No user seed or key is involved, which makes
+/// this suitable for health checks. An empty `relay_urls` uses the default public
+/// relays.
+///
+/// Returns an error if publishing or retrieval fails or the retrieved addresses
+/// differ; deletion is best effort and reported in `SelfTestReport::deleted`.
+///
+/// # Example
+/// ```rust,no_run
+/// use uba::self_test;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let relays = vec!["wss://relay.damus.io".to_string()];
+///     let report = self_test(&relays).await?;
+///     println!(
+///         "Round trip OK on {:?} ({:?} publish, {:?} retrieve)",
+///         report.confirmed_relays, report.publish_time, report.retrieve_time
+///     );
+///     Ok(())
+/// }
+/// ```
+pub async fn self_test(relay_urls: &[String]) -> Result<SelfTestReport> {
+    self_test_with_config(relay_urls, UbaConfig::default()).await
+}
+
+/// Run the `self_test` round trip with custom configuration
+///
+/// The throwaway event is published and queried with `config.event_kind` and the
+/// configured timeouts, so this checks the relays the way the application uses
+/// them. An empty `relay_urls` uses the relays of `config`.
+pub async fn self_test_with_config(
+    relay_urls: &[String],
+    config: UbaConfig,
+) -> Result<SelfTestReport> {
+    let final_relay_urls = if relay_urls.is_empty() {
+        config.get_relay_urls()
+    } else {
+        relay_urls.to_vec()
+    };
+
+    let nostr_client = NostrClient::from_config(Keys::generate(), &config);
+
+    self_test_with_client(&final_relay_urls, &nostr_client).await
+}
+
+/// Run the `self_test` round trip using an existing Nostr client
+///
+/// The client's keys sign the throwaway and deletion events, so pass a client with
+/// disposable keys.
+pub async fn self_test_with_client<T: RelayTransport>(
+    relay_urls: &[String],
+    nostr_client: &NostrClient<T>,
+) -> Result<SelfTestReport> {
+    validate_relay_urls(relay_urls)?;
+
+    // A fresh random seed, so the test never touches real wallet material
+    let mut entropy = zeroize::Zeroizing::new([0u8; 16]);
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, entropy.as_mut());
+    let mnemonic = bip39::Mnemonic::from_entropy(entropy.as_ref())?;
+    let seed = zeroize::Zeroizing::new(mnemonic.to_string());
+
+    let mut config = UbaConfig::default();
+    config.disable_all_address_types();
+    config.set_address_type_enabled(AddressType::P2WPKH, true);
+    let addresses = AddressGenerator::new(config).generate_addresses(seed.as_str(), None)?;
+
+    nostr_client
+        .with_connection(relay_urls, |client| self_test_round_trip(&addresses, relay_urls, client))
+        .await
+}
+
+/// Publish, retrieve, compare and delete for `self_test_with_client`
+async fn self_test_round_trip<T: RelayTransport>(
+    addresses: &BitcoinAddresses,
+    relay_urls: &[String],
+    nostr_client: &NostrClient<T>,
+) -> Result<SelfTestReport> {
+    let started = std::time::Instant::now();
+    let output = nostr_client.publish_addresses_detailed(addresses, None).await?;
+    let publish_time = started.elapsed();
+    let event_id = output.event_id.to_hex();
+
+    let started = std::time::Instant::now();
+    let retrieved = nostr_client.retrieve_addresses(&event_id).await;
+    let retrieve_time = started.elapsed();
+
+    // Clean up before judging the result, so a failed check leaves nothing behind
+    let deleted = nostr_client.delete_event(&event_id).await.is_ok();
+
+    if retrieved?.addresses != addresses.addresses {
+        return Err(UbaError::NostrRelay(format!(
+            "Event {} came back with different addresses",
+            event_id
+        )));
+    }
+
+    let failed_relays = relay_urls
+        .iter()
+        .filter(|url| !output.confirmed_relays.iter().any(|confirmed| same_relay(confirmed, url)))
+        .cloned()
+        .collect();
+
+    Ok(SelfTestReport {
+        event_id,
+        publish_time,
+        retrieve_time,
+        confirmed_relays: output.confirmed_relays,
+        failed_relays,
+        deleted,
+    })
+}
+
+/// Compare relay URLs after normalization, since relays report `wss://host/` for
+/// a configured `wss://host`
+fn same_relay(a: &str, b: &str) -> bool {
+    match (Url::parse(a), Url::parse(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Retrieve a UBA together with all updates that replaced it
+///
+/// The result is ordered from the original collection to the most recent update.
+/// The scan for updates is bounded by `config.retrieval_bounds`.
+pub async fn retrieve_history_with_config(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+) -> Result<Vec<BitcoinAddresses>> {
+    // Use relay URLs from config if provided, otherwise use passed URLs
+    let final_relay_urls = if relay_urls.is_empty() {
+        config.get_relay_urls()
+    } else {
+        relay_urls.to_vec()
+    };
+
+    validate_relay_urls(&final_relay_urls)?;
+
+    let nostr_client = NostrClient::from_config(Keys::generate(), &config);
+
+    let final_relay_urls = select_relays(final_relay_urls, &config, &nostr_client).await?;
+    retrieve_history_with_client(uba, &final_relay_urls, config, &nostr_client).await
+}
+
+/// Retrieve a UBA and its updates using an existing Nostr client
+pub async fn retrieve_history_with_client<T: RelayTransport>(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+    nostr_client: &NostrClient<T>,
+) -> Result<Vec<BitcoinAddresses>> {
+    validate_relay_urls(relay_urls)?;
+    let parsed_uba = parse_uba(uba.as_ref())?;
+
+    nostr_client
+        .with_connection(relay_urls, |client| {
+            client.retrieve_history(
+                &parsed_uba.nostr_id,
+                config.encryption_key.as_deref(),
+                &config.retrieval_bounds,
+            )
+        })
+        .await
+}
+
+/// Check whether a UBA has been updated since a given time
+///
+/// Returns true when an update replacing the UBA's event (directly or through
+/// earlier updates) was created after `since_created_at`, a Unix timestamp in
+/// seconds. The updates are not decoded, so apps caching a UBA can poll cheaply
+/// and only do a full retrieve when something changed.
+pub async fn has_update(
+    uba: impl AsRef<str>,
+    since_created_at: u64,
+    relay_urls: &[String],
+) -> Result<bool> {
+    has_update_with_config(uba, since_created_at, relay_urls, UbaConfig::default()).await
+}
+
+/// Check whether a UBA has been updated since a given time, with custom configuration
+pub async fn has_update_with_config(
+    uba: impl AsRef<str>,
+    since_created_at: u64,
+    relay_urls: &[String],
+    config: UbaConfig,
+) -> Result<bool> {
+    // Use relay URLs from config if provided, otherwise use passed URLs
+    let final_relay_urls = if relay_urls.is_empty() {
+        config.get_relay_urls()
+    } else {
+        relay_urls.to_vec()
+    };
+
+    validate_relay_urls(&final_relay_urls)?;
+
+    let nostr_client = NostrClient::from_config(Keys::generate(), &config);
+
+    let final_relay_urls = select_relays(final_relay_urls, &config, &nostr_client).await?;
+    has_update_with_client(uba, since_created_at, &final_relay_urls, &nostr_client).await
+}
+
+/// Check whether a UBA has been updated since a given time using an existing Nostr client
+pub async fn has_update_with_client<T: RelayTransport>(
+    uba: impl AsRef<str>,
+    since_created_at: u64,
+    relay_urls: &[String],
+    nostr_client: &NostrClient<T>,
+) -> Result<bool> {
+    validate_relay_urls(relay_urls)?;
+    let parsed_uba = parse_uba(uba.as_ref())?;
+
+    nostr_client
+        .with_connection(relay_urls, |client| {
+            client.has_update(&parsed_uba.nostr_id, since_created_at)
+        })
+        .await
+}
+
+/// Retrieve the full BitcoinAddresses structure, using a local filesystem cache
+///
+/// The cache in `cache_dir` is checked first and relays are only queried on a miss,
+/// after which the result is written back. Entries expire after the default TTL.
+pub async fn retrieve_full_cached(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    cache_dir: impl AsRef<std::path::Path>,
+) -> Result<BitcoinAddresses> {
+    let cache = UbaCache::new(cache_dir);
+    retrieve_full_cached_with_config(uba, relay_urls, UbaConfig::default(), &cache, CacheMode::Default)
+        .await
+}
+
+/// Retrieve the full BitcoinAddresses structure with a cache and custom configuration
+///
+/// `mode` controls whether the cache is used, refreshed or bypassed.
+pub async fn retrieve_full_cached_with_config(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+    cache: &UbaCache,
+    mode: CacheMode,
+) -> Result<BitcoinAddresses> {
+    // Use relay URLs from config if provided, otherwise use passed URLs
+    let final_relay_urls = if relay_urls.is_empty() {
+        config.get_relay_urls()
+    } else {
+        relay_urls.to_vec()
+    };
+
+    // Create Nostr client
+    let nostr_client = NostrClient::from_config(Keys::generate(), &config);
+
+    retrieve_full_cached_with_client(uba, &final_relay_urls, config, &nostr_client, cache, mode)
+        .await
+}
+
+/// Retrieve the full BitcoinAddresses structure with a cache and an existing Nostr client
+pub async fn retrieve_full_cached_with_client<T: RelayTransport>(
+    uba: impl AsRef<str>,
+    relay_urls: &[String],
+    config: UbaConfig,
+    nostr_client: &NostrClient<T>,
+    cache: &UbaCache,
+    mode: CacheMode,
+) -> Result<BitcoinAddresses> {
+    // Parse first so the cache is only ever keyed by a validated Nostr ID
+    let parsed_uba = parse_uba(uba.as_ref())?;
+    // Entries are bound to the key they were decrypted with, so a caller with a
+    // different key or none never receives plaintext it could not fetch itself
+    let encryption_key = config.encryption_key.clone();
+
+    if mode == CacheMode::Default {
+        if let Some(addresses) = cache.get(&parsed_uba.nostr_id, encryption_key.as_deref())? {
+            // Cache hits count against the rate limit and are held to the expected
+            // signer like relay lookups
+            config.check_rate_limit()?;
+            if let Some(signer) = &config.expected_signer {
+                addresses.verify_signature(Some(signer))?;
             }
+            return Ok(addresses);
         }
     }
 
-    Ok(None)
+    let addresses = retrieve_full_with_client(uba, relay_urls, config, nostr_client).await?;
+
+    if mode != CacheMode::Bypass {
+        cache.put(&parsed_uba.nostr_id, &addresses, encryption_key.as_deref())?;
+    }
+
+    Ok(addresses)
 }
 
-/// Validate a Nostr event ID format
-fn validate_nostr_id(nostr_id: &str) -> Result<()> {
-    if nostr_id.len() != 64 {
-        return Err(UbaError::InvalidUbaFormat(
-            "Nostr ID must be 64 characters long".to_string(),
-        ));
+/// Format the UBA for a newly published event with its label and the extra
+/// parameters from `config.uba_params`, in key order
+fn build_uba(event_id: &str, label: Option<&str>, config: &UbaConfig) -> Result<Uba> {
+    let mut uba = Uba::new(event_id)?;
+    if let Some(label) = label {
+        uba = uba.with_label(label);
     }
-
-    // Check if it's valid hex
-    if !nostr_id.chars().all(|c| c.is_ascii_hexdigit()) {
-        return Err(UbaError::InvalidUbaFormat(
-            "Nostr ID must be hexadecimal".to_string(),
-        ));
+    for (key, value) in &config.uba_params {
+        uba = uba.with_param(key.as_str(), value.as_str());
     }
+    Ok(uba)
+}
 
-    Ok(())
+/// Resolve the Nostr event ID from either a bare event ID or a UBA string
+fn resolve_nostr_id(uba_or_event_id: &str) -> Result<String> {
+    if uba_or_event_id.starts_with("UBA:") {
+        Ok(parse_uba(uba_or_event_id)?.nostr_id)
+    } else {
+        validate_nostr_id(uba_or_event_id)?;
+        Ok(uba_or_event_id.to_string())
+    }
 }
 
 /// Validate relay URLs
@@ -310,36 +1487,13 @@ fn validate_relay_urls(relay_urls: &[String]) -> Result<()> {
     Ok(())
 }
 
-/// Validate label format
-fn validate_label(label: &str) -> Result<()> {
-    if label.is_empty() {
-        return Err(UbaError::InvalidLabel("Label cannot be empty".to_string()));
-    }
-
-    if label.len() > 100 {
-        return Err(UbaError::InvalidLabel(
-            "Label cannot exceed 100 characters".to_string(),
-        ));
-    }
-
-    // Check for invalid characters that might cause issues in URLs
-    // Allow only alphanumeric characters, hyphens, and underscores
-    if !label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
-        return Err(UbaError::InvalidLabel(
-            "Label can only contain alphanumeric characters, hyphens, and underscores".to_string(),
-        ));
-    }
-
-    Ok(())
-}
-
 /// Update Bitcoin addresses for an existing UBA by creating a new Nostr event
 ///
 /// Since Nostr events are immutable, this function creates a new event that replaces
 /// the original one. The new event will reference the original event ID.
 ///
 /// # Arguments
-/// * `nostr_event_id` - The Nostr event ID to update (hex format)
+/// * `uba_or_event_id` - The UBA string or Nostr event ID (hex format) to update
 /// * `seed` - BIP39 mnemonic phrase or hex-encoded private key for generating new addresses
 /// * `relay_urls` - List of Nostr relay URLs where the update will be published
 /// * `config` - Configuration including address filtering and encryption settings
@@ -367,7 +1521,7 @@ fn validate_label(label: &str) -> Result<()> {
 /// }
 /// ```
 pub async fn update_uba(
-    nostr_event_id: &str,
+    uba_or_event_id: impl AsRef<str>,
     seed: &str,
     relay_urls: &[String],
     config: UbaConfig,
@@ -381,36 +1535,41 @@ pub async fn update_uba(
 
     // Validate inputs
     validate_relay_urls(&final_relay_urls)?;
-    validate_nostr_id(nostr_event_id)?;
+    let nostr_event_id = resolve_nostr_id(uba_or_event_id.as_ref())?;
 
     // Generate new Bitcoin addresses from the seed with current config
     let address_generator = AddressGenerator::new(config.clone());
     let mut updated_addresses = address_generator.generate_addresses(seed, None)?;
 
     // Update the timestamp to reflect this is an update
-    updated_addresses.created_at = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs();
+    updated_addresses.created_at = match config.fixed_timestamp {
+        Some(timestamp) => timestamp,
+        None => std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_secs(),
+    };
+    // The timestamp is signed, so sign again after changing it
+    if config.sign_payload {
+        updated_addresses.sign_with_seed(seed, &config)?;
+    }
 
     // Generate deterministic Nostr keys from the seed
-    let nostr_keys = generate_nostr_keys_from_seed(seed)?;
-    let nostr_client = NostrClient::with_keys(nostr_keys, config.relay_timeout);
-
-    // Connect to Nostr relays
-    nostr_client.connect_to_relays(&final_relay_urls).await?;
+    let nostr_keys = generate_nostr_keys_from_seed_with_config(seed, &config)?;
+    let nostr_client = NostrClient::from_config(nostr_keys, &config);
 
     // Update the addresses on Nostr with encryption if enabled
     let new_event_id = nostr_client
-        .update_addresses(nostr_event_id, &updated_addresses, config.encryption_key.as_ref())
+        .with_connection(&final_relay_urls, |client| {
+            client.update_addresses(
+                &nostr_event_id,
+                &updated_addresses,
+                config.encryption_key.as_deref(),
+            )
+        })
         .await?;
 
-    // Disconnect from relays
-    nostr_client.disconnect().await;
-
     // Return the new UBA string pointing to the updated event
-    let new_uba = format!("UBA:{}", new_event_id);
-    Ok(new_uba)
+    Ok(Uba::new(&new_event_id)?.to_string())
 }
 
 /// Update Bitcoin addresses with custom address data
@@ -419,7 +1578,7 @@ pub async fn update_uba(
 /// generating new addresses from a seed.
 ///
 /// # Arguments
-/// * `nostr_event_id` - The Nostr event ID to update (hex format)
+/// * `uba_or_event_id` - The UBA string or Nostr event ID (hex format) to update
 /// * `updated_addresses` - The new address data to publish
 /// * `relay_urls` - List of Nostr relay URLs where the update will be published
 /// * `config` - Configuration including encryption settings
@@ -427,7 +1586,7 @@ pub async fn update_uba(
 /// # Returns
 /// A new UBA string pointing to the updated event
 pub async fn update_uba_with_addresses(
-    nostr_event_id: &str,
+    uba_or_event_id: impl AsRef<str>,
     updated_addresses: BitcoinAddresses,
     relay_urls: &[String],
     config: UbaConfig,
@@ -441,7 +1600,7 @@ pub async fn update_uba_with_addresses(
 
     // Validate inputs first (before network operations)
     validate_relay_urls(&final_relay_urls)?;
-    validate_nostr_id(nostr_event_id)?;
+    let nostr_event_id = resolve_nostr_id(uba_or_event_id.as_ref())?;
     
     // Validate the address data early
     if updated_addresses.is_empty() {
@@ -470,74 +1629,193 @@ pub async fn update_uba_with_addresses(
         }
     }
 
-    // Create Nostr client (we need keys for publishing, but they don't need to be deterministic for updates)
-    let nostr_client = NostrClient::new(config.relay_timeout)?;
+    // Optionally check that every address parses for the configured network
+    if config.validate_external_addresses {
+        AddressGenerator::new(config.clone()).validate_addresses(&updated_addresses)?;
+    }
 
-    // Connect to Nostr relays
-    nostr_client.connect_to_relays(&final_relay_urls).await?;
+    // Create Nostr client (we need keys for publishing, but they don't need to be deterministic for updates)
+    let nostr_client = NostrClient::from_config(Keys::generate(), &config);
 
     // Update the addresses on Nostr with encryption if enabled
     let new_event_id = nostr_client
-        .update_addresses(nostr_event_id, &updated_addresses, config.encryption_key.as_ref())
+        .with_connection(&final_relay_urls, |client| {
+            client.update_addresses(
+                &nostr_event_id,
+                &updated_addresses,
+                config.encryption_key.as_deref(),
+            )
+        })
         .await?;
 
-    // Disconnect from relays
-    nostr_client.disconnect().await;
-
     // Return the new UBA string pointing to the updated event
-    let new_uba = format!("UBA:{}", new_event_id);
-    Ok(new_uba)
+    Ok(Uba::new(&new_event_id)?.to_string())
+}
+
+/// Update a UBA with addresses given as `BitcoinAddresses` JSON
+///
+/// Convenience wrapper around `update_uba_with_addresses` for callers that hold
+/// the collection in serialized form, such as bindings for other languages.
+/// Malformed JSON fails with `UbaError::Json` before any relay is contacted.
+pub async fn update_addresses_from_data(
+    uba_or_event_id: impl AsRef<str>,
+    addresses_json: &str,
+    relay_urls: &[String],
+    config: UbaConfig,
+) -> Result<String> {
+    let updated_addresses: BitcoinAddresses = serde_json::from_str(addresses_json)?;
+    update_uba_with_addresses(uba_or_event_id, updated_addresses, relay_urls, config).await
+}
+
+/// Re-encrypt a UBA's stored addresses under a new key
+///
+/// Retrieves the UBA, decrypts it with `old_key` and publishes a replacing event
+/// encrypted with `new_key`. The addresses themselves are not regenerated. Fails with
+/// `UbaError::Encryption` without publishing anything if `old_key` cannot decrypt the
+/// stored data. A payload signature is dropped, since the new timestamp invalidates
+/// it; use `update_uba` to publish a re-signed collection.
+///
+/// The replacement is signed with the Nostr identity derived from `seed`, so it
+/// belongs to the same author as the original and shows up in `retrieve_history`
+/// and `has_update`.
+///
+/// # Arguments
+/// * `uba_or_event_id` - The UBA string or Nostr event ID (hex format) to re-encrypt
+/// * `seed` - The BIP39 mnemonic the UBA was generated from
+/// * `old_key` - The key the stored data is currently encrypted with
+/// * `new_key` - The key to encrypt the replacement with
+/// * `relay_urls` - List of Nostr relay URLs where the update will be published
+/// * `config` - Configuration used to derive the Nostr identity from the seed
+///
+/// # Returns
+/// A new UBA string pointing to the re-encrypted event
+///
+/// # Example
+/// ```rust,no_run
+/// use uba::{derive_encryption_key, reencrypt_uba, UbaConfig};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+///     let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+///     let old_key = derive_encryption_key("leaked passphrase", None);
+///     let new_key = derive_encryption_key("new passphrase", None);
+///     let relays = vec!["wss://relay.example.com".to_string()];
+///
+///     let new_uba =
+///         reencrypt_uba(uba, seed, &old_key, &new_key, &relays, UbaConfig::default()).await?;
+///     println!("Re-encrypted UBA: {}", new_uba);
+///     Ok(())
+/// }
+/// ```
+pub async fn reencrypt_uba(
+    uba_or_event_id: impl AsRef<str>,
+    seed: &str,
+    old_key: &[u8; 32],
+    new_key: &[u8; 32],
+    relay_urls: &[String],
+    config: UbaConfig,
+) -> Result<String> {
+    // Use relay URLs from config if provided, otherwise use passed URLs
+    let final_relay_urls = if relay_urls.is_empty() {
+        config.get_relay_urls()
+    } else {
+        relay_urls.to_vec()
+    };
+
+    // Sign the replacement with the same identity that published the original
+    let nostr_keys = generate_nostr_keys_from_seed_with_config(seed, &config)?;
+    let nostr_client = NostrClient::from_config(nostr_keys, &config);
+
+    reencrypt_uba_with_client(
+        uba_or_event_id,
+        old_key,
+        new_key,
+        &final_relay_urls,
+        &nostr_client,
+    )
+    .await
+}
+
+/// Re-encrypt a UBA's stored addresses using an existing Nostr client
+///
+/// The replacement is signed with the client's keys, which must be the original
+/// author's for readers to follow it as an update.
+pub async fn reencrypt_uba_with_client<T: RelayTransport>(
+    uba_or_event_id: impl AsRef<str>,
+    old_key: &[u8; 32],
+    new_key: &[u8; 32],
+    relay_urls: &[String],
+    nostr_client: &NostrClient<T>,
+) -> Result<String> {
+    // Validate inputs
+    validate_relay_urls(relay_urls)?;
+    let nostr_event_id = resolve_nostr_id(uba_or_event_id.as_ref())?;
+
+    // Decrypt with the old key and publish the replacement under the new key
+    let new_event_id = nostr_client
+        .with_connection(relay_urls, |client| {
+            client.reencrypt_addresses(&nostr_event_id, old_key, new_key)
+        })
+        .await?;
+
+    Ok(Uba::new(&new_event_id)?.to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::address::AddressGenerator;
+    use crate::nostr_client::generate_nostr_keys_from_seed;
     use crate::types::AddressType;
 
     #[test]
-    fn test_parse_uba_without_label() {
-        let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
-        let result = parse_uba(uba);
+    fn test_nostr_keys_from_seed_match_collection() {
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let (nsec, npub) = nostr_keys_from_seed(seed).unwrap();
 
-        assert!(result.is_ok());
-        let parsed = result.unwrap();
-        assert_eq!(
-            parsed.nostr_id,
-            "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef"
-        );
-        assert_eq!(parsed.label, None);
-    }
+        let addresses = AddressGenerator::new(UbaConfig::default())
+            .generate_addresses(seed, None)
+            .unwrap();
+        assert_eq!(addresses.first(&AddressType::Nostr), Some(npub.as_str()));
 
-    #[test]
-    fn test_parse_uba_with_label() {
-        let uba =
-            "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef&label=my-wallet";
-        let result = parse_uba(uba);
+        let keys = nostr::Keys::parse(&nsec).unwrap();
+        assert_eq!(nostr::ToBech32::to_bech32(&keys.public_key()).unwrap(), npub);
 
-        assert!(result.is_ok());
-        let parsed = result.unwrap();
-        assert_eq!(
-            parsed.nostr_id,
-            "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef"
-        );
-        assert_eq!(parsed.label, Some("my-wallet".to_string()));
+        // A BIP39 passphrase selects a different wallet and so a different identity
+        let mut config = UbaConfig::default();
+        config.set_bip39_passphrase("TREZOR".to_string());
+        let (_, passphrase_npub) = nostr_keys_from_seed_with_config(seed, &config).unwrap();
+        assert_ne!(passphrase_npub, npub);
+        let addresses = AddressGenerator::new(config).generate_addresses(seed, None).unwrap();
+        assert_eq!(addresses.first(&AddressType::Nostr), Some(passphrase_npub.as_str()));
     }
 
     #[test]
-    fn test_parse_uba_invalid_format() {
-        let uba = "INVALID:1234567890abcdef";
-        let result = parse_uba(uba);
+    fn test_build_uba_sorts_extra_params() {
+        let nostr_id = "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+        let mut config = UbaConfig::default();
+        config.set_uba_param("net", "testnet").unwrap();
+        config.set_uba_param("app", "my app").unwrap();
+        assert!(config.set_uba_param("label", "x").is_err());
 
-        assert!(result.is_err());
+        let uba = build_uba(nostr_id, Some("x"), &config).unwrap();
+        assert_eq!(
+            uba.to_string(),
+            format!("UBA:{}&label=x&app=my%20app&net=testnet", nostr_id)
+        );
     }
 
     #[test]
-    fn test_parse_uba_invalid_nostr_id() {
-        let uba = "UBA:invalidhex";
-        let result = parse_uba(uba);
+    fn test_resolve_nostr_id() {
+        let nostr_id = "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
 
-        assert!(result.is_err());
+        assert_eq!(resolve_nostr_id(nostr_id).unwrap(), nostr_id);
+        assert_eq!(
+            resolve_nostr_id(&format!("UBA:{}&label=wallet", nostr_id)).unwrap(),
+            nostr_id
+        );
+        assert!(resolve_nostr_id("invalid_event_id").is_err());
     }
 
     #[test]
@@ -555,21 +1833,6 @@ mod tests {
         assert!(validate_relay_urls(&empty_urls).is_err());
     }
 
-    #[test]
-    fn test_validate_label() {
-        // Valid labels
-        assert!(validate_label("my-wallet").is_ok());
-        assert!(validate_label("wallet123").is_ok());
-        assert!(validate_label("a").is_ok());
-
-        // Invalid labels
-        assert!(validate_label("").is_err());
-        assert!(validate_label("a".repeat(101).as_str()).is_err()); // Too long
-        assert!(validate_label("my wallet").is_err()); // Contains space
-        assert!(validate_label("my@wallet").is_err()); // Contains @
-        assert!(validate_label("my/wallet").is_err()); // Contains /
-    }
-
     #[test]
     fn test_update_uba_validation_invalid_event_id() {
         let rt = tokio::runtime::Runtime::new().unwrap();
@@ -619,6 +1882,20 @@ mod tests {
         });
     }
 
+    #[tokio::test]
+    async fn test_update_addresses_from_data_validation() {
+        let event_id = "1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+        let relays = vec!["wss://relay.example.com".to_string()];
+
+        let result =
+            update_addresses_from_data(event_id, "{not json", &relays, UbaConfig::default()).await;
+        assert!(matches!(result, Err(UbaError::Json(_))));
+
+        let empty = serde_json::to_string(&BitcoinAddresses::new()).unwrap();
+        let result = update_addresses_from_data(event_id, &empty, &relays, UbaConfig::default()).await;
+        assert!(matches!(result, Err(UbaError::UpdateValidation(_))));
+    }
+
     #[test]
     fn test_update_uba_with_filtering_configuration() {
         // Test that the update function respects address filtering
@@ -656,7 +1933,7 @@ mod tests {
 
         // Should only have Bitcoin L1 addresses
         assert!(addresses.addresses.contains_key(&AddressType::P2PKH));
-        assert!(addresses.addresses.contains_key(&AddressType::P2SH));
+        assert!(addresses.addresses.contains_key(&AddressType::P2SHWPKH));
         assert!(addresses.addresses.contains_key(&AddressType::P2WPKH));
         assert!(addresses.addresses.contains_key(&AddressType::P2TR));
 
@@ -666,6 +1943,909 @@ mod tests {
         assert!(!addresses.addresses.contains_key(&AddressType::Nostr));
     }
 
+    #[tokio::test]
+    async fn test_generate_and_retrieve_with_mock_relay() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let config = UbaConfig::default();
+        let relay = MockRelay::new();
+
+        let publisher = relay.client(generate_nostr_keys_from_seed(seed).unwrap(), config.relay_timeout);
+        let uba = generate_with_client(seed, Some("mock-wallet"), &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+        assert!(uba.ends_with("&label=mock-wallet"));
+        assert_eq!(relay.len(), 1);
+
+        // Labels with spaces are URL-encoded and decode back to the original
+        let spaced = generate_with_client(seed, Some("My Wallet"), &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+        assert!(spaced.ends_with("&label=My%20Wallet"));
+        assert_eq!(parse_uba(&spaced).unwrap().label.as_deref(), Some("My Wallet"));
+
+        let reader = relay.client(nostr::Keys::generate(), config.relay_timeout);
+        let retrieved = retrieve_full_with_client(&uba, &relays, config.clone(), &reader)
+            .await
+            .unwrap();
+
+        // A typed UBA is accepted wherever a UBA string is
+        let typed: Uba = uba.parse().unwrap();
+        let retrieved_typed = retrieve_full_with_client(&typed, &relays, config.clone(), &reader)
+            .await
+            .unwrap();
+        assert_eq!(retrieved_typed.addresses, retrieved.addresses);
+
+        let expected = AddressGenerator::new(config)
+            .generate_addresses(seed, Some("mock-wallet".to_string()))
+            .unwrap();
+        assert_eq!(retrieved.addresses, expected.addresses);
+        assert_eq!(
+            retrieved.metadata.and_then(|m| m.label),
+            Some("mock-wallet".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_full_checked_rejects_other_network() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let config = UbaConfig {
+            network: bitcoin::Network::Testnet,
+            ..Default::default()
+        };
+        let relay = MockRelay::new();
+
+        let publisher = relay.client(generate_nostr_keys_from_seed(seed).unwrap(), config.relay_timeout);
+        let uba = generate_with_client(seed, None, &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+
+        let reader = relay.client(nostr::Keys::generate(), config.relay_timeout);
+        let addresses = retrieve_full_checked_with_client(
+            &uba,
+            &relays,
+            bitcoin::Network::Testnet,
+            config.clone(),
+            &reader,
+        )
+        .await
+        .unwrap();
+        assert!(!addresses.is_empty());
+
+        let result = retrieve_full_checked_with_client(
+            &uba,
+            &relays,
+            bitcoin::Network::Bitcoin,
+            config,
+            &reader,
+        )
+        .await;
+        assert!(matches!(result, Err(UbaError::Config(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_rejects_calls_over_the_limit() {
+        use crate::error::validation::RateLimiter;
+        use crate::testing::MockRelay;
+        use std::sync::{Arc, Mutex};
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let limiter = Arc::new(Mutex::new(RateLimiter::new(3, Duration::from_secs(60))));
+        let mut config = UbaConfig::default();
+        config.set_rate_limiter(limiter.clone(), "client-a");
+        let relay = MockRelay::new();
+        let client = relay.client(generate_nostr_keys_from_seed(seed).unwrap(), config.relay_timeout);
+
+        let uba = generate_with_client(seed, None, &relays, config.clone(), &client)
+            .await
+            .unwrap();
+        retrieve_full_with_client(&uba, &relays, config.clone(), &client).await.unwrap();
+        retrieve_full_with_client(&uba, &relays, config.clone(), &client).await.unwrap();
+
+        // The fourth call in the window is rejected
+        let result = retrieve_full_with_client(&uba, &relays, config, &client).await;
+        assert!(matches!(result, Err(UbaError::RateLimit(_))));
+
+        // Other identifiers have their own budget
+        let mut other = UbaConfig::default();
+        other.set_rate_limiter(limiter, "client-b");
+        assert!(retrieve_full_with_client(&uba, &relays, other, &client).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_generate_batch_respects_rate_limit() {
+        use crate::error::validation::RateLimiter;
+        use crate::testing::MockRelay;
+        use std::sync::{Arc, Mutex};
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let mut config = UbaConfig::default();
+        let limiter = Arc::new(Mutex::new(RateLimiter::new(2, Duration::from_secs(60))));
+        config.set_rate_limiter(limiter, "service");
+        let relay = MockRelay::new();
+        let client = relay.client(nostr::Keys::generate(), config.relay_timeout);
+
+        let requests = (0..3)
+            .map(|i| UbaGenerationRequest {
+                seed: seed.to_string(),
+                label: Some(format!("user-{}", i)),
+                relay_urls: relays.clone(),
+                config: config.clone(),
+            })
+            .collect();
+        let results = generate_batch_with_client(requests, &client).await;
+
+        assert!(results[0].is_ok());
+        assert!(results[1].is_ok());
+        assert!(matches!(results[2], Err(UbaError::RateLimit(_))));
+    }
+
+    #[tokio::test]
+    async fn test_select_relays_honors_auto_select() {
+        use crate::testing::MockRelay;
+
+        let relay = MockRelay::new();
+        relay.set_latency("wss://slow.relay", Duration::from_millis(200));
+        relay.set_unreachable("wss://down.relay");
+        let client = relay.client(nostr::Keys::generate(), 10);
+        let candidates = vec![
+            "wss://slow.relay".to_string(),
+            "wss://fast.relay".to_string(),
+            "wss://down.relay".to_string(),
+        ];
+
+        let mut config = UbaConfig::default();
+        let all = select_relays(candidates.clone(), &config, &client).await.unwrap();
+        assert_eq!(all, candidates);
+
+        config.auto_select_relays = Some(1);
+        let selected = select_relays(candidates, &config, &client).await.unwrap();
+        assert_eq!(selected, vec!["wss://fast.relay".to_string()]);
+
+        let down = vec!["wss://down.relay".to_string()];
+        assert!(matches!(
+            select_relays(down.clone(), &config, &client).await,
+            Err(UbaError::NostrRelay(_))
+        ));
+
+        config.auto_select_relays = Some(0);
+        assert!(matches!(
+            select_relays(down, &config, &client).await,
+            Err(UbaError::Config(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_verify_seed_matches() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let other_seed = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let config = UbaConfig::default();
+        let relay = MockRelay::new();
+
+        let publisher = relay.client(generate_nostr_keys_from_seed(seed).unwrap(), config.relay_timeout);
+        let uba = generate_with_client(seed, None, &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+
+        // The author check and the decode share a single relay session
+        let reader = relay.client(nostr::Keys::generate(), config.relay_timeout);
+        let disconnects = relay.disconnect_count();
+        assert!(verify_seed_matches_with_client(seed, &uba, &relays, config.clone(), &reader)
+            .await
+            .unwrap());
+        assert_eq!(relay.disconnect_count(), disconnects + 1);
+
+        // A missing event still ends the session
+        let missing = Uba::new(&"ab".repeat(32)).unwrap().to_string();
+        let result = verify_seed_matches_with_client(seed, &missing, &relays, config.clone(), &reader).await;
+        assert!(matches!(result, Err(UbaError::NoteNotFound(_))));
+        assert_eq!(relay.disconnect_count(), disconnects + 2);
+
+        // A larger local set still contains every published address
+        let mut superset = config.clone();
+        superset.set_address_count(AddressType::P2WPKH, 20);
+        assert!(verify_seed_matches_with_client(seed, &uba, &relays, superset, &reader)
+            .await
+            .unwrap());
+
+        assert!(!verify_seed_matches_with_client(other_seed, &uba, &relays, config.clone(), &reader)
+            .await
+            .unwrap());
+
+        // The seed's addresses republished under someone else's keys do not match
+        let copier = relay.client(nostr::Keys::generate(), config.relay_timeout);
+        let copy = generate_with_client(seed, None, &relays, config.clone(), &copier)
+            .await
+            .unwrap();
+        assert!(!verify_seed_matches_with_client(seed, &copy, &relays, config, &reader)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_disconnects_after_failed_publish() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let config = UbaConfig::default();
+        let relay = MockRelay::new();
+        relay.set_max_event_size(100);
+
+        let client = relay.client(generate_nostr_keys_from_seed(seed).unwrap(), config.relay_timeout);
+        let err = generate_with_client(seed, None, &relays, config.clone(), &client)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UbaError::EventTooLarge { .. }));
+        assert_eq!(relay.disconnect_count(), 1);
+
+        let missing = format!("UBA:{}", "0".repeat(64));
+        assert!(retrieve_full_with_client(&missing, &relays, config, &client).await.is_err());
+        assert_eq!(relay.disconnect_count(), 2);
+
+        // Already disconnected, so dropping the client does not disconnect again
+        drop(client);
+        assert_eq!(relay.disconnect_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_many_with_mock_relay() {
+        use crate::testing::MockRelay;
+
+        let alice = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let bob = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let config = UbaConfig::default();
+        let relay = MockRelay::new();
+
+        let mut ubas = Vec::new();
+        for seed in [alice, bob] {
+            let publisher = relay.client(generate_nostr_keys_from_seed(seed).unwrap(), config.relay_timeout);
+            ubas.push(
+                generate_with_client(seed, None, &relays, config.clone(), &publisher)
+                    .await
+                    .unwrap(),
+            );
+        }
+        let missing = format!("UBA:{}", "0".repeat(64));
+        let requested = [ubas[1].as_str(), missing.as_str(), "not a uba", ubas[0].as_str()];
+
+        let reader = relay.client(nostr::Keys::generate(), config.relay_timeout);
+        let disconnects = relay.disconnect_count();
+        let results = retrieve_many_with_client(&requested, &relays, config.clone(), &reader)
+            .await
+            .unwrap();
+        assert_eq!(relay.disconnect_count(), disconnects + 1);
+
+        assert_eq!(results.len(), 4);
+        for (index, seed) in [(3, alice), (0, bob)] {
+            let expected = AddressGenerator::new(config.clone()).generate_addresses(seed, None).unwrap();
+            assert_eq!(results[index].as_ref().unwrap().addresses, expected.addresses);
+        }
+        assert!(matches!(results[1], Err(UbaError::NoteNotFound(_))));
+        assert!(results[2].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_signed_payload_round_trip() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let keys = generate_nostr_keys_from_seed(seed).unwrap();
+        let signer = keys.public_key().to_hex();
+
+        let mut config = UbaConfig {
+            sign_payload: true,
+            ..Default::default()
+        };
+        let publisher = relay.client(keys, config.relay_timeout);
+        let signed = generate_with_client(seed, None, &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+
+        config.sign_payload = false;
+        let unsigned = generate_with_client(seed, Some("unsigned"), &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+
+        let reader_config = UbaConfig {
+            expected_signer: Some(signer),
+            ..Default::default()
+        };
+        let reader = relay.client(nostr::Keys::generate(), reader_config.relay_timeout);
+        let addresses = retrieve_full_with_client(&signed, &relays, reader_config.clone(), &reader)
+            .await
+            .unwrap();
+        assert!(addresses.signature.is_some());
+
+        let err = retrieve_full_with_client(&unsigned, &relays, reader_config.clone(), &reader)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UbaError::InvalidSignature(_)));
+
+        // An unsigned collection cached earlier is rejected as well
+        let cache_dir = std::env::temp_dir().join(format!("uba-cache-test-{}", uuid::Uuid::new_v4()));
+        let cache = UbaCache::new(&cache_dir);
+        retrieve_full_cached_with_client(&unsigned, &relays, UbaConfig::default(), &reader, &cache, CacheMode::Default)
+            .await
+            .unwrap();
+        let err = retrieve_full_cached_with_client(&unsigned, &relays, reader_config, &reader, &cache, CacheMode::Default)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UbaError::InvalidSignature(_)));
+
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bip39_passphrase_changes_identity() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+
+        // An empty passphrase keeps the identity of a seed without one
+        let mut empty = UbaConfig::default();
+        empty.set_bip39_passphrase(String::new());
+        let default_keys = generate_nostr_keys_from_seed(seed).unwrap();
+        assert_eq!(
+            generate_nostr_keys_from_seed_with_config(seed, &empty).unwrap().public_key(),
+            default_keys.public_key()
+        );
+
+        let mut config = UbaConfig {
+            sign_payload: true,
+            ..Default::default()
+        };
+        config.set_bip39_passphrase("TREZOR".to_string());
+        let mut other = config.clone();
+        other.set_bip39_passphrase("other".to_string());
+        let keys = generate_nostr_keys_from_seed_with_config(seed, &config).unwrap();
+        assert_ne!(keys.public_key(), default_keys.public_key());
+        assert_ne!(
+            keys.public_key(),
+            generate_nostr_keys_from_seed_with_config(seed, &other).unwrap().public_key()
+        );
+
+        // Events and payload signatures both come from the passphrase identity
+        let publisher = relay.client(keys.clone(), config.relay_timeout);
+        let uba = generate_with_client(seed, None, &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+        let addresses = retrieve_full_with_client(&uba, &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+        addresses
+            .verify_signature(Some(&keys.public_key().to_hex()))
+            .unwrap();
+        assert!(verify_seed_matches_with_client(seed, &uba, &relays, config, &publisher)
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_self_test_round_trip_with_mock_relay() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let client = relay.client(nostr::Keys::generate(), 10);
+
+        let report = self_test_with_client(&relays, &client).await.unwrap();
+        assert_eq!(report.confirmed_relays, relays);
+        assert!(report.failed_relays.is_empty());
+        assert!(report.deleted);
+
+        // The throwaway event is followed by a NIP-09 deletion of it
+        let events = relay.events();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].id.to_hex(), report.event_id);
+        assert_eq!(events[1].kind, nostr::Kind::EventDeletion);
+    }
+
+    #[test]
+    fn test_same_relay_ignores_trailing_slash() {
+        assert!(same_relay("wss://relay.damus.io", "wss://relay.damus.io/"));
+        assert!(!same_relay("wss://relay.damus.io", "wss://nos.lol"));
+    }
+
+    #[tokio::test]
+    async fn test_generate_full_with_mock_relay() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let config = UbaConfig::default();
+        let relay = MockRelay::new();
+
+        let publisher = relay.client(generate_nostr_keys_from_seed(seed).unwrap(), config.relay_timeout);
+        let outcome = generate_full_with_client(seed, None, &relays, config, &publisher)
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.uba, format!("UBA:{}", outcome.event_id));
+        assert_eq!(outcome.confirmed_relays, relays);
+        assert!(!outcome.addresses.is_empty());
+        assert_eq!(relay.events()[0].id.to_hex(), outcome.event_id);
+    }
+
+    #[tokio::test]
+    async fn test_generate_sharded_with_mock_relay() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let config = UbaConfig {
+            max_addresses_per_type: 20,
+            max_event_size: 4_000,
+            ..Default::default()
+        };
+        let relay = MockRelay::new();
+        let client = relay.client(generate_nostr_keys_from_seed(seed).unwrap(), config.relay_timeout);
+
+        let uba = generate_sharded_with_client(seed, Some("big"), &relays, config.clone(), &client)
+            .await
+            .unwrap();
+        assert!(relay.len() > 2);
+
+        let reader = relay.client(nostr::Keys::generate(), config.relay_timeout);
+        let retrieved = retrieve_full_with_client(&uba, &relays, config.clone(), &reader)
+            .await
+            .unwrap();
+        let expected = AddressGenerator::new(config)
+            .generate_addresses(seed, Some("big".to_string()))
+            .unwrap();
+        assert_eq!(retrieved.addresses, expected.addresses);
+    }
+
+    #[tokio::test]
+    async fn test_reencrypt_uba_with_mock_relay() {
+        use crate::encryption::generate_random_key;
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let old_key = generate_random_key();
+        let new_key = generate_random_key();
+        let mut config = UbaConfig::default();
+        config.set_encryption_key(old_key);
+        let relay = MockRelay::new();
+        let client = relay.client(nostr::Keys::generate(), config.relay_timeout);
+
+        let uba = generate_with_client(seed, None, &relays, config.clone(), &client)
+            .await
+            .unwrap();
+
+        // A wrong old key is rejected without publishing anything
+        let err = reencrypt_uba_with_client(&uba, &new_key, &new_key, &relays, &client)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, UbaError::Encryption(_)));
+        assert_eq!(relay.len(), 1);
+
+        let new_uba = reencrypt_uba_with_client(&uba, &old_key, &new_key, &relays, &client)
+            .await
+            .unwrap();
+        assert_eq!(relay.len(), 2);
+
+        let original = retrieve_full_with_client(&uba, &relays, config.clone(), &client)
+            .await
+            .unwrap();
+        config.set_encryption_key(new_key);
+        let rotated = retrieve_full_with_client(&new_uba, &relays, config, &client)
+            .await
+            .unwrap();
+        assert_eq!(rotated.addresses, original.addresses);
+    }
+
+    #[tokio::test]
+    async fn test_reencrypt_uba_drops_payload_signature() {
+        use crate::encryption::generate_random_key;
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let old_key = generate_random_key();
+        let new_key = generate_random_key();
+        let keys = generate_nostr_keys_from_seed(seed).unwrap();
+        let signer = keys.public_key().to_hex();
+
+        let mut config = UbaConfig {
+            sign_payload: true,
+            ..Default::default()
+        };
+        config.set_encryption_key(old_key);
+        let relay = MockRelay::new();
+        let client = relay.client(keys, config.relay_timeout);
+
+        let uba = generate_with_client(seed, None, &relays, config.clone(), &client)
+            .await
+            .unwrap();
+        let new_uba = reencrypt_uba_with_client(&uba, &old_key, &new_key, &relays, &client)
+            .await
+            .unwrap();
+
+        // The stale signature is not republished, so the result reads as unsigned
+        // rather than as tampered with
+        config.set_encryption_key(new_key);
+        let rotated = retrieve_full_with_client(&new_uba, &relays, config.clone(), &client)
+            .await
+            .unwrap();
+        assert!(rotated.signature.is_none());
+
+        config.expected_signer = Some(signer);
+        match retrieve_full_with_client(&new_uba, &relays, config, &client).await {
+            Err(UbaError::InvalidSignature(message)) => assert!(message.contains("not signed")),
+            other => panic!("expected InvalidSignature, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reencrypt_uba_signs_with_seed_identity() {
+        use crate::encryption::generate_random_key;
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let old_key = generate_random_key();
+        let new_key = generate_random_key();
+        let mut config = UbaConfig::default();
+        config.set_encryption_key(old_key);
+        let relay = MockRelay::new();
+
+        let identity = generate_nostr_keys_from_seed_with_config(seed, &config).unwrap();
+        let publisher = relay.client(identity.clone(), config.relay_timeout);
+        let uba = generate_with_client(seed, None, &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+        let reader = relay.client(nostr::Keys::generate(), config.relay_timeout);
+
+        // A rotation signed by other keys is not followed as an update
+        let stranger = relay.client(nostr::Keys::generate(), config.relay_timeout);
+        reencrypt_uba_with_client(&uba, &old_key, &new_key, &relays, &stranger)
+            .await
+            .unwrap();
+        assert!(!has_update_with_client(&uba, 0, &relays, &reader).await.unwrap());
+        let history = retrieve_history_with_client(&uba, &relays, config.clone(), &reader)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+
+        // Rotating from a separate client with the seed identity is followed
+        let rotator = relay.client(identity, config.relay_timeout);
+        let rotated = reencrypt_uba_with_client(&uba, &old_key, &new_key, &relays, &rotator)
+            .await
+            .unwrap();
+        assert!(has_update_with_client(&uba, 0, &relays, &reader).await.unwrap());
+
+        // The history now reaches the rotated event, which only the new key can read
+        assert!(retrieve_history_with_client(&uba, &relays, config.clone(), &reader)
+            .await
+            .is_err());
+        config.set_encryption_key(new_key);
+        retrieve_full_with_client(&rotated, &relays, config, &reader)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_with_fallback_clients() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let primary_relays = vec!["wss://primary.mock.relay".to_string()];
+        let fallback_relays = vec!["wss://fallback.mock.relay".to_string()];
+        let config = UbaConfig::default();
+
+        let primary = MockRelay::new();
+        let fallback = MockRelay::new();
+        let primary_client = primary.client(nostr::Keys::generate(), config.relay_timeout);
+        let fallback_client = fallback.client(nostr::Keys::generate(), config.relay_timeout);
+
+        // Only the fallback relays have the event
+        let uba = generate_with_client(seed, None, &fallback_relays, config.clone(), &fallback_client)
+            .await
+            .unwrap();
+
+        let addresses = retrieve_full_with_fallback_clients(
+            &uba,
+            (&primary_relays, &primary_client),
+            (&fallback_relays, &fallback_client),
+            config.clone(),
+        )
+        .await
+        .unwrap();
+        assert!(!addresses.is_empty());
+
+        // Not found in either set
+        fallback.clear();
+        let result = retrieve_full_with_fallback_clients(
+            &uba,
+            (&primary_relays, &primary_client),
+            (&fallback_relays, &fallback_client),
+            config,
+        )
+        .await;
+        assert!(matches!(result, Err(UbaError::NoteNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_generate_cancellable() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let config = UbaConfig::default();
+        let relay = MockRelay::new();
+        let client = relay.client(nostr::Keys::generate(), config.relay_timeout);
+
+        // A cancelled token aborts before anything is published
+        let token = CancellationToken::new();
+        token.cancel();
+        let result =
+            generate_cancellable_with_client(seed, None, &relays, config.clone(), &client, token)
+                .await;
+        assert!(matches!(result, Err(UbaError::Cancelled)));
+        assert!(relay.is_empty());
+
+        // An untouched token behaves like a regular generate
+        let uba = generate_cancellable_with_client(
+            seed,
+            None,
+            &relays,
+            config,
+            &client,
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
+        assert!(uba.starts_with("UBA:"));
+        assert_eq!(relay.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_verify_resolvable_with_mock_relay() {
+        use crate::encryption::generate_random_key;
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let mut config = UbaConfig::default();
+        config.set_encryption_key(generate_random_key());
+        let relay = MockRelay::new();
+        let client = relay.client(nostr::Keys::generate(), config.relay_timeout);
+
+        let uba = generate_with_client(seed, None, &relays, config, &client)
+            .await
+            .unwrap();
+
+        // Works without the encryption key
+        assert!(verify_resolvable_with_client(&uba, &relays, &client).await.unwrap());
+
+        relay.clear();
+        assert!(!verify_resolvable_with_client(&uba, &relays, &client).await.unwrap());
+        assert!(verify_resolvable_with_client("UBA:bad", &relays, &client).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_generate_batch_with_mock_relay() {
+        use crate::testing::MockRelay;
+
+        let seed1 = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let seed2 = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let config = UbaConfig::default();
+        let relay = MockRelay::new();
+        let original_keys = nostr::Keys::generate();
+        let client = relay.client(original_keys.clone(), config.relay_timeout);
+
+        let request = |seed: &str, label: Option<&str>, relay_urls: &[String]| {
+            UbaGenerationRequest {
+                seed: seed.to_string(),
+                label: label.map(String::from),
+                relay_urls: relay_urls.to_vec(),
+                config: config.clone(),
+            }
+        };
+        let requests = vec![
+            request(seed1, Some("alice"), &relays),
+            request("not a valid seed", None, &relays),
+            request(seed2, None, &relays),
+            request(seed2, None, &["not-a-url".to_string()]),
+        ];
+        let results = generate_batch_with_client(requests, &client).await;
+
+        // Invalid entries fail on their own without aborting the batch, and all
+        // entries sharing the relay set use one connection
+        assert_eq!(results.len(), 4);
+        assert!(results[0].as_ref().unwrap().ends_with("&label=alice"));
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+        assert!(results[3].is_err());
+        assert_eq!(relay.len(), 2);
+        assert_eq!(relay.disconnect_count(), 1);
+
+        // Each event is signed by its own seed's keys
+        let authors: Vec<_> = relay.events().iter().map(|event| event.pubkey).collect();
+        assert_eq!(
+            authors,
+            vec![
+                generate_nostr_keys_from_seed(seed1).unwrap().public_key(),
+                generate_nostr_keys_from_seed(seed2).unwrap().public_key(),
+            ]
+        );
+        assert_eq!(client.keys().public_key(), original_keys.public_key());
+
+        let reader = relay.client(nostr::Keys::generate(), config.relay_timeout);
+        let retrieved = retrieve_full_with_client(results[2].as_ref().unwrap(), &relays, config, &reader)
+            .await
+            .unwrap();
+        assert!(!retrieved.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_missing_event_from_mock_relay() {
+        use crate::testing::MockRelay;
+
+        let relays = vec!["wss://mock.relay".to_string()];
+        let relay = MockRelay::new();
+        let reader = relay.client(nostr::Keys::generate(), 10);
+
+        let uba = "UBA:1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef";
+        let result = retrieve_full_with_client(uba, &relays, UbaConfig::default(), &reader).await;
+        assert!(matches!(result.unwrap_err(), UbaError::NoteNotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_full_cached_with_mock_relay() {
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let config = UbaConfig::default();
+        let relay = MockRelay::new();
+        let cache_dir = std::env::temp_dir().join(format!("uba-cache-test-{}", uuid::Uuid::new_v4()));
+        let cache = UbaCache::new(&cache_dir);
+
+        let publisher = relay.client(generate_nostr_keys_from_seed(seed).unwrap(), config.relay_timeout);
+        let uba = generate_with_client(seed, None, &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+
+        // First retrieval hits the relay and populates the cache
+        let reader = relay.client(nostr::Keys::generate(), config.relay_timeout);
+        let first = retrieve_full_cached_with_client(&uba, &relays, config.clone(), &reader, &cache, CacheMode::Default)
+            .await
+            .unwrap();
+
+        // Once the relay forgets the event, only the cache can answer
+        relay.clear();
+        let cached = retrieve_full_cached_with_client(&uba, &relays, config.clone(), &reader, &cache, CacheMode::Default)
+            .await
+            .unwrap();
+        assert_eq!(first.addresses, cached.addresses);
+
+        // Refreshing and bypassing always go to the relay
+        let refreshed = retrieve_full_cached_with_client(&uba, &relays, config.clone(), &reader, &cache, CacheMode::Refresh).await;
+        assert!(matches!(refreshed.unwrap_err(), UbaError::NoteNotFound(_)));
+        let bypassed = retrieve_full_cached_with_client(&uba, &relays, config.clone(), &reader, &cache, CacheMode::Bypass).await;
+        assert!(matches!(bypassed.unwrap_err(), UbaError::NoteNotFound(_)));
+
+        // Cache hits still count against the rate limit
+        let mut limited = config;
+        let limiter = crate::error::validation::RateLimiter::new(1, Duration::from_secs(60));
+        limited.set_rate_limiter(std::sync::Arc::new(std::sync::Mutex::new(limiter)), "client");
+        retrieve_full_cached_with_client(&uba, &relays, limited.clone(), &reader, &cache, CacheMode::Default)
+            .await
+            .unwrap();
+        let result = retrieve_full_cached_with_client(&uba, &relays, limited, &reader, &cache, CacheMode::Default).await;
+        assert!(matches!(result, Err(UbaError::RateLimit(_))));
+
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_full_cached_requires_matching_key() {
+        use crate::encryption::generate_random_key;
+        use crate::testing::MockRelay;
+
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let relays = vec!["wss://mock.relay".to_string()];
+        let mut config = UbaConfig::default();
+        config.set_encryption_key(generate_random_key());
+        let relay = MockRelay::new();
+        let cache_dir = std::env::temp_dir().join(format!("uba-cache-test-{}", uuid::Uuid::new_v4()));
+        let cache = UbaCache::new(&cache_dir);
+
+        let publisher = relay.client(generate_nostr_keys_from_seed(seed).unwrap(), config.relay_timeout);
+        let uba = generate_with_client(seed, None, &relays, config.clone(), &publisher)
+            .await
+            .unwrap();
+
+        // Warm the cache with the right key, then leave only the cache to answer
+        let reader = relay.client(nostr::Keys::generate(), config.relay_timeout);
+        retrieve_full_cached_with_client(&uba, &relays, config.clone(), &reader, &cache, CacheMode::Default)
+            .await
+            .unwrap();
+        relay.clear();
+
+        // A wrong key or no key misses the cache and falls through to the relay
+        let mut wrong_key = config.clone();
+        wrong_key.set_encryption_key(generate_random_key());
+        let result = retrieve_full_cached_with_client(&uba, &relays, wrong_key, &reader, &cache, CacheMode::Default).await;
+        assert!(matches!(result, Err(UbaError::NoteNotFound(_))));
+        let result = retrieve_full_cached_with_client(&uba, &relays, UbaConfig::default(), &reader, &cache, CacheMode::Default).await;
+        assert!(matches!(result, Err(UbaError::NoteNotFound(_))));
+
+        // The right key is still served from the cache
+        retrieve_full_cached_with_client(&uba, &relays, config, &reader, &cache, CacheMode::Default)
+            .await
+            .unwrap();
+
+        std::fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[test]
+    fn test_generate_dry_run() {
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+        let (addresses, event_json) = generate_dry_run(seed, Some("preview"), UbaConfig::default()).unwrap();
+        let event = nostr::Event::from_json(&event_json).unwrap();
+        assert!(event.verify().is_ok());
+
+        // Unencrypted content is the serialized address collection
+        let content: BitcoinAddresses = serde_json::from_str(&event.content).unwrap();
+        assert_eq!(content.addresses, addresses.addresses);
+        assert_eq!(event.pubkey, generate_nostr_keys_from_seed(seed).unwrap().public_key());
+    }
+
+    #[test]
+    fn test_generate_dry_run_encrypted() {
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let mut config = UbaConfig::default();
+        let key = config.generate_random_encryption_key();
+
+        let (addresses, event_json) = generate_dry_run(seed, None, config).unwrap();
+        let event = nostr::Event::from_json(&event_json).unwrap();
+
+        // Content must not be readable JSON, but must decrypt back to the addresses
+        assert!(serde_json::from_str::<BitcoinAddresses>(&event.content).is_err());
+        let decrypted = crate::encryption::UbaEncryption::new(key).decrypt(&event.content).unwrap();
+        let content: BitcoinAddresses = serde_json::from_str(&decrypted).unwrap();
+        assert_eq!(content.addresses, addresses.addresses);
+        assert!(event.tags.iter().any(|tag| tag.as_vec() == ["encrypted", "true"]));
+    }
+
+    #[test]
+    fn test_generate_dry_run_reproducible_with_fixed_timestamp() {
+        let seed = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        let config = UbaConfig {
+            fixed_timestamp: Some(1_700_000_000),
+            ..Default::default()
+        };
+
+        let (_, first) = generate_dry_run(seed, Some("golden"), config.clone()).unwrap();
+        let (_, second) = generate_dry_run(seed, Some("golden"), config).unwrap();
+
+        let first = nostr::Event::from_json(&first).unwrap();
+        let second = nostr::Event::from_json(&second).unwrap();
+        assert_eq!(first.id, second.id);
+        assert_eq!(first.content, second.content);
+    }
+
     #[test]
     fn test_update_uba_timestamp_update() {
         // Test that update function updates the timestamp